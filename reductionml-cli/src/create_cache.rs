@@ -0,0 +1,166 @@
+use std::fs::File;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use owo_colors::OwoColorize;
+use reductionml_core::{
+    compression::{create_data_file, decompress_reader},
+    object_pool::PoolReturnable,
+    parsers::{read_record_batches, CacheHeader, CacheWriter, ColumnarParser},
+    workspace::{Configuration, Workspace},
+    ExampleMetadata,
+};
+
+use crate::{
+    command::Command, ColumnMappingArg, CsvSchemaArg, DataFormat, DsJsonArg, InputConfigArg,
+    COLUMNAR_BATCH_SIZE,
+};
+
+#[derive(Args)]
+pub(crate) struct CreateCacheArgs {
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    data: String,
+
+    #[arg(long)]
+    #[arg(default_value = "vw-text")]
+    data_format: DataFormat,
+
+    #[command(flatten)]
+    csv_schema: CsvSchemaArg,
+
+    #[command(flatten)]
+    dsjson: DsJsonArg,
+
+    #[command(flatten)]
+    column_mapping: ColumnMappingArg,
+
+    /// The hash seed, number of bits and expected data types are taken from
+    /// the config or model. The cache can only be used with models that hash
+    /// in the same way.
+    #[command(flatten)]
+    input_config: InputConfigArg,
+
+    /// File to write the cache to
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    output: String,
+}
+
+pub(crate) struct CreateCacheCommand;
+
+impl Command for CreateCacheCommand {
+    type Args = CreateCacheArgs;
+    fn execute(args: &CreateCacheArgs, quiet: bool) -> Result<()> {
+        let workspace = match (&args.input_config.config, &args.input_config.input_model) {
+            (Some(config_file), None) => {
+                let json = std::fs::read_to_string(config_file)
+                    .with_context(|| format!("Failed to read config file: {}", config_file))?;
+                Workspace::new(Configuration::from_json_str(&json)?).with_context(|| {
+                    format!(
+                        "Failed to create workspace from config file: {}",
+                        config_file
+                    )
+                })?
+            }
+            (None, Some(input_model_file)) => {
+                let data = std::fs::read(input_model_file).with_context(|| {
+                    format!("Failed to read input model file: {}", input_model_file)
+                })?;
+                Workspace::create_from_model(&data).with_context(|| {
+                    format!(
+                        "Failed to create workspace from input model file: {}",
+                        input_model_file
+                    )
+                })?
+            }
+            _ => unreachable!(),
+        };
+
+        let global_config = workspace.global_config();
+        let types = workspace.get_entry_reduction().types();
+        let pool = workspace.features_pool().clone();
+
+        let file = File::open(&args.data)
+            .with_context(|| format!("Failed to open data file: {}", args.data))?;
+        if !quiet {
+            eprintln!(
+                "{}: Reading data file: {}",
+                "info".cyan().bold(),
+                &args.data.bold()
+            );
+        }
+        let output = create_data_file(&args.output)
+            .with_context(|| format!("Failed to create cache file: {}", args.output))?;
+        let mut writer = CacheWriter::new(
+            output,
+            CacheHeader {
+                num_bits: global_config.num_bits(),
+                hash_seed: global_config.hash_seed(),
+                hash_function: global_config.hash_function(),
+                features_type: types.input_features_type(),
+                label_type: types.input_label_type(),
+            },
+        )?;
+
+        let mut num_examples: u64 = 0;
+        match args.data_format.columnar_format() {
+            Some(format) => {
+                let parser = ColumnarParser::new(
+                    &args.column_mapping.load()?.unwrap(),
+                    types.input_features_type(),
+                    types.input_label_type(),
+                    global_config.hash_seed(),
+                    global_config.hash_function(),
+                    global_config.num_bits(),
+                    pool.clone(),
+                )?;
+                for batch in read_record_batches(file, format, COLUMNAR_BATCH_SIZE)? {
+                    for (features, label) in parser.parse_batch(&batch?)? {
+                        writer.write_example(
+                            &features,
+                            label.as_ref(),
+                            &ExampleMetadata::default(),
+                        )?;
+                        features.clear_and_return_object(pool.as_ref());
+                        num_examples += 1;
+                    }
+                }
+            }
+            None => {
+                let parser = args.data_format.get_parser(
+                    types.input_features_type(),
+                    types.input_label_type(),
+                    global_config.hash_seed(),
+                    global_config.hash_function(),
+                    global_config.num_bits(),
+                    pool.clone(),
+                    args.csv_schema.load()?.as_ref(),
+                    args.dsjson.options(),
+                )?;
+                let mut input_file = decompress_reader(file)
+                    .with_context(|| format!("Failed to read data file: {}", args.data))?;
+                let mut buffer = String::new();
+                while let Some(chunk) = parser.get_next_chunk(&mut input_file, buffer)? {
+                    let (features, label, metadata) = parser.parse_chunk(&chunk)?;
+                    writer.write_example(&features, label.as_ref(), &metadata)?;
+                    features.clear_and_return_object(pool.as_ref());
+                    num_examples += 1;
+                    buffer = chunk;
+                }
+            }
+        }
+        writer
+            .into_inner()
+            .finish()
+            .with_context(|| format!("Failed to write cache file: {}", args.output))?;
+
+        if !quiet {
+            eprintln!(
+                "{}: Wrote {} examples to {}",
+                "info".cyan().bold(),
+                num_examples,
+                &args.output.bold()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs::File;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use owo_colors::OwoColorize;
+use prettytable::{format, row, Table};
+use reductionml_core::{
+    compression::decompress_reader, hash_collisions::HashCollisionCounter,
+    interactions::compile_interactions, workspace::Configuration,
+};
+
+use crate::{command::Command, CsvSchemaArg, DataFormat, DsJsonArg, InputConfigArg};
+
+#[derive(Args)]
+pub(crate) struct HashCollisionsArgs {
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    data: String,
+
+    #[arg(long)]
+    #[arg(default_value = "vw-text")]
+    data_format: DataFormat,
+
+    #[command(flatten)]
+    csv_schema: CsvSchemaArg,
+
+    #[command(flatten)]
+    dsjson: DsJsonArg,
+
+    /// The hash seed, interactions and expected data types are taken from the
+    /// config or model.
+    #[command(flatten)]
+    input_config: InputConfigArg,
+
+    /// Number of bits to report collisions for. Can be given more than once to
+    /// compare several values. Defaults to `numBits` of the config or model.
+    #[arg(long, num_args = 1.., value_delimiter = ',')]
+    #[arg(value_parser = clap::value_parser!(u8).range(1..32))]
+    num_bits: Vec<u8>,
+}
+
+pub(crate) struct HashCollisionsCommand;
+
+impl Command for HashCollisionsCommand {
+    type Args = HashCollisionsArgs;
+    fn execute(args: &HashCollisionsArgs, quiet: bool) -> Result<()> {
+        let workspace = match (&args.input_config.config, &args.input_config.input_model) {
+            (Some(config_file), None) => {
+                let json = std::fs::read_to_string(config_file)
+                    .with_context(|| format!("Failed to read config file: {}", config_file))?;
+                reductionml_core::workspace::Workspace::new(Configuration::from_json_str(&json)?)
+                    .with_context(|| {
+                        format!(
+                            "Failed to create workspace from config file: {}",
+                            config_file
+                        )
+                    })?
+            }
+            (None, Some(input_model_file)) => {
+                let data = std::fs::read(input_model_file).with_context(|| {
+                    format!("Failed to read input model file: {}", input_model_file)
+                })?;
+                reductionml_core::workspace::Workspace::create_from_model(&data).with_context(
+                    || {
+                        format!(
+                            "Failed to create workspace from input model file: {}",
+                            input_model_file
+                        )
+                    },
+                )?
+            }
+            _ => unreachable!(),
+        };
+
+        let global_config = workspace.global_config();
+        let interactions = compile_interactions(
+            global_config.interactions(),
+            global_config.interaction_mode(),
+            global_config.hash_seed(),
+            global_config.hash_function(),
+        )?;
+        let num_bits = if args.num_bits.is_empty() {
+            vec![global_config.num_bits()]
+        } else {
+            args.num_bits.clone()
+        };
+        let mut counters: Vec<HashCollisionCounter> = num_bits
+            .iter()
+            .map(|num_bits| {
+                HashCollisionCounter::new(
+                    *num_bits,
+                    global_config.hash_seed(),
+                    global_config.hash_function(),
+                    interactions.clone(),
+                )
+            })
+            .collect();
+
+        let parser = args.data_format.get_parser(
+            workspace
+                .get_entry_reduction()
+                .types()
+                .input_features_type(),
+            workspace.get_entry_reduction().types().input_label_type(),
+            global_config.hash_seed(),
+            global_config.hash_function(),
+            global_config.num_bits(),
+            workspace.features_pool().clone(),
+            args.csv_schema.load()?.as_ref(),
+            args.dsjson.options(),
+        )?;
+
+        let file = File::open(&args.data)
+            .with_context(|| format!("Failed to open data file: {}", args.data))?;
+        if !quiet {
+            eprintln!(
+                "{}: Reading data file: {}",
+                "info".cyan().bold(),
+                &args.data.bold()
+            );
+        }
+        let mut input_file = decompress_reader(file)
+            .with_context(|| format!("Failed to read data file: {}", args.data))?;
+        let mut buffer = String::new();
+        let mut num_examples: u64 = 0;
+        while let Some(chunk) = parser.get_next_chunk(&mut input_file, buffer)? {
+            for names in parser.extract_feature_names(&chunk)? {
+                for counter in &mut counters {
+                    counter.add_example(&names);
+                }
+            }
+            num_examples += 1;
+            buffer = chunk;
+            buffer.clear();
+        }
+        if !quiet {
+            eprintln!("{}: Read {} examples", "info".cyan().bold(), num_examples);
+        }
+
+        for counter in &counters {
+            println!("numBits = {}", counter.num_bits());
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_CLEAN);
+            table.set_titles(row![
+                "Features",
+                "Distinct",
+                "Indices used",
+                "Colliding",
+                "Collision rate",
+                "Expected rate"
+            ]);
+            for summary in counter.summarize() {
+                table.add_row(row![
+                    summary.name,
+                    r->summary.distinct_features,
+                    r->summary.used_indices,
+                    r->summary.colliding_features,
+                    r->format!("{:.4}", summary.collision_rate()),
+                    r->format!("{:.4}", summary.expected_collision_rate)
+                ]);
+            }
+            table.printstd();
+            println!();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use owo_colors::OwoColorize;
+use reductionml_core::vw_model::VwModel;
+
+use crate::command::Command;
+
+#[derive(Args)]
+pub(crate) struct ImportVwModelArgs {
+    /// VW model file, either binary or written by --readable_model or
+    /// --invert_hash
+    #[arg(value_hint = ValueHint::FilePath)]
+    input_file: String,
+
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    output_model: String,
+}
+
+pub(crate) struct ImportVwModelCommand;
+
+impl Command for ImportVwModelCommand {
+    type Args = ImportVwModelArgs;
+    fn execute(args: &ImportVwModelArgs, quiet: bool) -> Result<()> {
+        let data = std::fs::read(&args.input_file)
+            .with_context(|| format!("Failed to read VW model file: {}", args.input_file))?;
+        let model = VwModel::from_bytes(&data)
+            .with_context(|| format!("Failed to read VW model file: {}", args.input_file))?;
+        if !quiet {
+            eprintln!(
+                "{}: VW version {} with options: {}",
+                "info".cyan().bold(),
+                model.version(),
+                model.options().bold()
+            );
+        }
+
+        let workspace = model.to_workspace()?;
+
+        let output = workspace.serialize_model()?;
+        std::fs::write(&args.output_model, output)
+            .with_context(|| format!("Failed to write output model: {}", args.output_model))?;
+        if !quiet {
+            eprintln!(
+                "{}: Imported {} weights into {}",
+                "info".cyan().bold(),
+                model.weights().len(),
+                &args.output_model.bold()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use clap::Args;
+use prettytable::{format, row, Table};
+use reductionml_core::metrics::{get_metric, metric_names};
+
+use crate::command::Command;
+
+use anyhow::Result;
+
+#[derive(Args)]
+pub(crate) struct ListMetricsArgs {}
+
+pub(crate) struct ListMetricsCommand;
+
+impl Command for ListMetricsCommand {
+    type Args = ListMetricsArgs;
+    fn execute(_args: &ListMetricsArgs, _quiet: bool) -> Result<()> {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_CLEAN);
+        table.set_titles(row!["Name", "Label type", "Prediction type", "Description"]);
+        for name in metric_names() {
+            let metric = get_metric(name).unwrap();
+            let label_type = metric
+                .required_label_type()
+                .map_or("any".to_owned(), |x| format!("{:?}", x));
+            let prediction_type = metric
+                .required_prediction_type()
+                .map_or("any".to_owned(), |x| format!("{:?}", x));
+            table.add_row(row![
+                name,
+                label_type,
+                prediction_type,
+                metric.get_description()
+            ]);
+        }
+        table.printstd();
+        Ok(())
+    }
+}
@@ -1,10 +1,15 @@
 use std::sync::Arc;
 
+use anyhow::{anyhow, Context};
 use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
 use owo_colors::OwoColorize;
 use reductionml_core::{
+    hash::HashFunction,
     object_pool::Pool,
-    parsers::{TextModeParser, TextModeParserFactory},
+    parsers::{
+        ColumnMapping, ColumnarFormat, CsvParserFactory, CsvSchema, DsJsonOptions, RewardFunction,
+        TextModeParser, TextModeParserFactory,
+    },
     sparse_namespaced_features::SparseFeatures,
     FeaturesType, LabelType,
 };
@@ -14,11 +19,16 @@ use crate::command::Command;
 mod command;
 mod config;
 mod convert_data;
+mod create_cache;
 mod create_inv_hash_table;
 mod export_model;
 mod gen_completions;
 mod gen_schema;
+mod hash_collisions;
 mod import_model;
+mod import_vw_model;
+mod list_metrics;
+mod serve;
 mod test;
 mod train;
 
@@ -38,32 +48,61 @@ enum DataFormat {
     VWText,
     Dsjson,
     Json,
+    /// Comma separated values, read using a CSV schema
+    Csv,
+    /// Tab separated values, read using a CSV schema
+    Tsv,
+    /// Arrow IPC file, read using a column mapping
+    Arrow,
+    /// Parquet file, read using a column mapping
+    Parquet,
+    /// Binary cache of hashed examples, created with create-cache
+    Cache,
 }
 
+/// Number of rows read from Parquet files at a time.
+const COLUMNAR_BATCH_SIZE: usize = 8192;
+
 impl DataFormat {
+    /// Columnar formats are read a record batch at a time by a
+    /// `ColumnarParser` instead of a text parser.
+    pub fn columnar_format(&self) -> Option<ColumnarFormat> {
+        match self {
+            DataFormat::Arrow => Some(ColumnarFormat::ArrowIpc),
+            DataFormat::Parquet => Some(ColumnarFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn get_parser(
         &self,
         features_type: FeaturesType,
         label_type: LabelType,
         hash_seed: u32,
+        hash_function: HashFunction,
         num_bits: u8,
         pool: Arc<Pool<SparseFeatures>>,
-    ) -> Box<dyn TextModeParser> {
-        match self {
+        csv_schema: Option<&CsvSchema>,
+        dsjson_options: DsJsonOptions,
+    ) -> anyhow::Result<Box<dyn TextModeParser>> {
+        Ok(match self {
             DataFormat::VWText => Box::new(
                 reductionml_core::parsers::VwTextParserFactory::default().create(
                     features_type,
                     label_type,
                     hash_seed,
+                    hash_function,
                     num_bits,
                     pool,
                 ),
             ),
             DataFormat::Dsjson => Box::new(
-                reductionml_core::parsers::DsJsonParserFactory::default().create(
+                reductionml_core::parsers::DsJsonParserFactory::new(dsjson_options).create(
                     features_type,
                     label_type,
                     hash_seed,
+                    hash_function,
                     num_bits,
                     pool,
                 ),
@@ -73,11 +112,35 @@ impl DataFormat {
                     features_type,
                     label_type,
                     hash_seed,
+                    hash_function,
                     num_bits,
                     pool,
                 ),
             ),
-        }
+            DataFormat::Csv | DataFormat::Tsv => {
+                let schema = csv_schema
+                    .ok_or_else(|| anyhow!("A CSV schema is required for CSV and TSV data"))?
+                    .clone();
+                let factory = match self {
+                    DataFormat::Csv => CsvParserFactory::csv(schema),
+                    _ => CsvParserFactory::tsv(schema),
+                };
+                Box::new(factory.try_create(
+                    features_type,
+                    label_type,
+                    hash_seed,
+                    hash_function,
+                    num_bits,
+                    pool,
+                )?)
+            }
+            DataFormat::Arrow | DataFormat::Parquet | DataFormat::Cache => {
+                return Err(anyhow!(
+                    "{} data can only be used for training or creating a cache",
+                    self.to_possible_value().unwrap().get_name()
+                ))
+            }
+        })
     }
 }
 
@@ -93,10 +156,20 @@ enum Commands {
     ExportModel(export_model::ExportModelArgs),
     /// Import a model from a human-readable format
     ImportModel(import_model::ImportModelArgs),
+    /// Import a Vowpal Wabbit model, binary or saved with --readable_model or --invert_hash
+    ImportVwModel(import_vw_model::ImportVwModelArgs),
     /// Create an inverse hash table from data
     CreateInvHashTable(create_inv_hash_table::CreateInvHashTableArgs),
     /// Convert between data formats
     ConvertData(convert_data::ConvertDataArgs),
+    /// Hash data into a binary cache which can be trained on without parsing
+    CreateCache(create_cache::CreateCacheArgs),
+    /// Report how often features collide when hashed, to help choose numBits
+    HashCollisions(hash_collisions::HashCollisionsArgs),
+    /// List available metrics and the label and prediction types they require
+    ListMetrics(list_metrics::ListMetricsArgs),
+    /// Serve a model over a socket, predicting on and learning from examples as they arrive
+    Serve(serve::ServeArgs),
     /// Generate shell completions
     GenCompletions(gen_completions::GenCompletionsArgs),
     /// Generate JSON schema for configuration
@@ -115,6 +188,99 @@ struct InputConfigArg {
     input_model: Option<String>,
 }
 
+#[derive(Args)]
+struct CsvSchemaArg {
+    /// JSON file describing the columns of CSV or TSV data
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[arg(required_if_eq_any([("data_format", "csv"), ("data_format", "tsv")]))]
+    csv_schema: Option<String>,
+}
+
+impl CsvSchemaArg {
+    fn load(&self) -> anyhow::Result<Option<CsvSchema>> {
+        self.csv_schema
+            .as_ref()
+            .map(|path| {
+                let json = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read CSV schema file: {}", path))?;
+                serde_json::from_str(&json)
+                    .with_context(|| format!("Failed to parse CSV schema file: {}", path))
+            })
+            .transpose()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum RewardFunctionArg {
+    Earliest,
+    Average,
+    Sum,
+    Median,
+}
+
+impl From<RewardFunctionArg> for RewardFunction {
+    fn from(r: RewardFunctionArg) -> Self {
+        match r {
+            RewardFunctionArg::Earliest => RewardFunction::Earliest,
+            RewardFunctionArg::Average => RewardFunction::Average,
+            RewardFunctionArg::Sum => RewardFunction::Sum,
+            RewardFunctionArg::Median => RewardFunction::Median,
+        }
+    }
+}
+
+#[derive(Args)]
+struct DsJsonArg {
+    /// Calculate the cost of DSJSON events by combining their outcomes with
+    /// this function, instead of using _label_cost
+    #[arg(long)]
+    reward_function: Option<RewardFunctionArg>,
+
+    /// Reward of DSJSON events which have no outcomes, when a reward function
+    /// is used
+    #[arg(long, requires = "reward_function", allow_negative_numbers = true)]
+    #[arg(default_value = "0")]
+    default_reward: f32,
+
+    /// Use _original_label_cost as the cost of DSJSON events which have it,
+    /// instead of _label_cost
+    #[arg(long, conflicts_with = "reward_function")]
+    original_label_cost: bool,
+}
+
+impl DsJsonArg {
+    fn options(&self) -> DsJsonOptions {
+        DsJsonOptions {
+            reward_function: self.reward_function.map(Into::into),
+            default_reward: self.default_reward,
+            use_original_label_cost: self.original_label_cost,
+        }
+    }
+}
+
+#[derive(Args)]
+struct ColumnMappingArg {
+    /// JSON file mapping the columns of Arrow or Parquet data to namespaces
+    /// and the label
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[arg(required_if_eq_any([("data_format", "arrow"), ("data_format", "parquet")]))]
+    column_mapping: Option<String>,
+}
+
+impl ColumnMappingArg {
+    fn load(&self) -> anyhow::Result<Option<ColumnMapping>> {
+        self.column_mapping
+            .as_ref()
+            .map(|path| {
+                let json = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read column mapping file: {}", path))?;
+                serde_json::from_str(&json)
+                    .with_context(|| format!("Failed to parse column mapping file: {}", path))
+            })
+            .transpose()
+    }
+}
+
 fn handle_args(cli: Cli) -> anyhow::Result<()> {
     match &cli.command {
         Commands::Config(args) => {
@@ -135,9 +301,24 @@ fn handle_args(cli: Cli) -> anyhow::Result<()> {
         Commands::ImportModel(args) => {
             import_model::ImportModelCommand::execute(args, cli.quiet)?;
         }
+        Commands::ImportVwModel(args) => {
+            import_vw_model::ImportVwModelCommand::execute(args, cli.quiet)?;
+        }
         Commands::ConvertData(args) => {
             convert_data::ConvertDataCommand::execute(args, cli.quiet)?;
         }
+        Commands::CreateCache(args) => {
+            create_cache::CreateCacheCommand::execute(args, cli.quiet)?;
+        }
+        Commands::HashCollisions(args) => {
+            hash_collisions::HashCollisionsCommand::execute(args, cli.quiet)?;
+        }
+        Commands::ListMetrics(args) => {
+            list_metrics::ListMetricsCommand::execute(args, cli.quiet)?;
+        }
+        Commands::Serve(args) => {
+            serve::ServeCommand::execute(args, cli.quiet)?;
+        }
         Commands::GenCompletions(args) => {
             gen_completions::GenCompletionsCommand::execute(args, cli.quiet)?;
         }
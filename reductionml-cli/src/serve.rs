@@ -0,0 +1,388 @@
+use std::{
+    io::{self, BufRead, Read, Write},
+    net::TcpListener,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{ArgGroup, Args, ValueHint};
+use owo_colors::OwoColorize;
+use reductionml_core::{
+    object_pool::PoolReturnable,
+    parsers::{CsvSchema, DsJsonOptions, TextModeParser},
+    workspace::Configuration,
+    workspace::Workspace,
+};
+
+use crate::{command::Command, CsvSchemaArg, DataFormat, DsJsonArg, InputConfigArg};
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("listen").required(true).multiple(false)))]
+pub(crate) struct ServeArgs {
+    #[command(flatten)]
+    input_config: InputConfigArg,
+
+    #[arg(long)]
+    #[arg(default_value = "vw-text")]
+    data_format: DataFormat,
+
+    #[command(flatten)]
+    csv_schema: CsvSchemaArg,
+
+    #[command(flatten)]
+    dsjson: DsJsonArg,
+
+    /// Listen for TCP connections on this port
+    #[arg(long, group = "listen")]
+    port: Option<u16>,
+
+    /// Address to bind to when listening on a TCP port
+    #[arg(long, requires = "port")]
+    #[arg(default_value = "127.0.0.1")]
+    host: String,
+
+    /// Listen for connections on a Unix domain socket at this path
+    #[cfg(unix)]
+    #[arg(long, group = "listen", value_hint = ValueHint::FilePath)]
+    unix_socket: Option<String>,
+
+    /// Default path used by the save command when no path is given
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    output_model: Option<String>,
+
+    /// Directory which the save and reload commands can be given paths in.
+    /// Without it they only use --output-model and --input-model.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    model_dir: Option<String>,
+}
+
+/// Workspace shared between all connections. The generation is incremented
+/// whenever the workspace is replaced so that connections know to recreate
+/// their parser.
+struct ServeState {
+    workspace: Workspace,
+    generation: u64,
+}
+
+struct Server {
+    /// Predictions and saves share the lock, so only learning and reloading
+    /// wait for a save to finish.
+    state: RwLock<ServeState>,
+    data_format: DataFormat,
+    csv_schema: Option<CsvSchema>,
+    dsjson_options: DsJsonOptions,
+    default_save_path: Option<String>,
+    default_reload_path: Option<String>,
+    /// Canonical path of --model-dir.
+    model_dir: Option<PathBuf>,
+}
+
+fn load_model(path: impl AsRef<Path>) -> Result<Workspace> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to read model file: {}", path.display()))?;
+    Workspace::create_from_model(&data).with_context(|| {
+        format!(
+            "Failed to create workspace from model file: {}",
+            path.display()
+        )
+    })
+}
+
+/// Splits a command line into the command and the rest of the line, which is
+/// its argument.
+fn split_command(line: &str) -> (&str, Option<&str>) {
+    let line = line.trim();
+    match line.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, Some(argument.trim_start())),
+        None => (line, None),
+    }
+}
+
+fn error_response(err: &anyhow::Error) -> String {
+    serde_json::json!({ "error": format!("{:#}", err) }).to_string()
+}
+
+fn ok_response() -> String {
+    serde_json::json!({ "status": "ok" }).to_string()
+}
+
+impl Server {
+    fn create_parser(&self) -> Result<(Box<dyn TextModeParser>, u64)> {
+        let state = self.state.read().unwrap();
+        let workspace = &state.workspace;
+        let parser = self.data_format.get_parser(
+            workspace
+                .get_entry_reduction()
+                .types()
+                .input_features_type(),
+            workspace.get_entry_reduction().types().input_label_type(),
+            workspace.global_config().hash_seed(),
+            workspace.global_config().hash_function(),
+            workspace.global_config().num_bits(),
+            workspace.features_pool().clone(),
+            self.csv_schema.as_ref(),
+            self.dsjson_options,
+        )?;
+        Ok((parser, state.generation))
+    }
+
+    /// Resolves a path given to a command, which must be inside of
+    /// --model-dir.
+    fn resolve_model_path(&self, path: &str) -> Result<PathBuf> {
+        let model_dir = self.model_dir.as_ref().ok_or_else(|| {
+            anyhow!("Paths can only be given to commands when --model-dir is set")
+        })?;
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "Model paths must be relative to --model-dir and cannot contain '..': {}",
+                path
+            ));
+        }
+        // Symbolic links must not lead out of the directory either.
+        let joined = model_dir.join(relative);
+        let resolved = match joined.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => joined
+                .parent()
+                .and_then(|parent| parent.canonicalize().ok())
+                .zip(joined.file_name())
+                .map(|(parent, file_name)| parent.join(file_name))
+                .ok_or_else(|| anyhow!("Directory of model path does not exist: {}", path))?,
+        };
+        if !resolved.starts_with(model_dir) {
+            return Err(anyhow!("Model path is outside of --model-dir: {}", path));
+        }
+        Ok(resolved)
+    }
+
+    fn command_path(
+        &self,
+        path: Option<&str>,
+        default: Option<&str>,
+        default_flag: &str,
+    ) -> Result<PathBuf> {
+        match (path, default) {
+            (Some(path), _) => self.resolve_model_path(path),
+            (None, Some(default)) => Ok(PathBuf::from(default)),
+            (None, None) => Err(anyhow!("No path given and no {} set", default_flag)),
+        }
+    }
+
+    fn handle_command(&self, line: &str) -> Result<()> {
+        let (command, path) = split_command(line);
+        match command {
+            "save" => {
+                let path =
+                    self.command_path(path, self.default_save_path.as_deref(), "--output-model")?;
+                let data = self.state.read().unwrap().workspace.serialize_model()?;
+                std::fs::write(&path, data)
+                    .with_context(|| format!("Failed to write model file: {}", path.display()))?;
+            }
+            "reload" => {
+                let path =
+                    self.command_path(path, self.default_reload_path.as_deref(), "--input-model")?;
+                let workspace = load_model(path)?;
+                let mut state = self.state.write().unwrap();
+                state.workspace = workspace;
+                state.generation += 1;
+            }
+            _ => return Err(anyhow!("Unknown command: {}", command)),
+        }
+        Ok(())
+    }
+
+    /// Serves a single connection. Each example is answered with its
+    /// prediction as a line of JSON. Lines starting with `!` are commands.
+    fn handle_connection(&self, reader: impl Read, mut writer: impl Write) -> Result<()> {
+        let mut reader = io::BufReader::new(reader);
+        let (mut parser, mut generation) = self.create_parser()?;
+        let mut buffer = String::new();
+        loop {
+            let next_byte = match reader.fill_buf()? {
+                [] => break,
+                [byte, ..] => *byte,
+            };
+
+            let response = if next_byte == b'!' {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                match self.handle_command(&line[1..]) {
+                    Ok(()) => ok_response(),
+                    Err(err) => error_response(&err),
+                }
+            } else {
+                if self.state.read().unwrap().generation != generation {
+                    (parser, generation) = self.create_parser()?;
+                }
+                let chunk = match parser.get_next_chunk(&mut reader, buffer)? {
+                    Some(chunk) => chunk,
+                    None => break,
+                };
+                // The generation is checked under the same lock that the
+                // example is learned or predicted with, so that it is never
+                // given to a reloaded workspace it was not parsed for.
+                let response = loop {
+                    let (mut features, label) = match parser.parse_chunk(&chunk) {
+                        Ok((features, label, _)) => (features, label),
+                        Err(err) => break error_response(&err.into()),
+                    };
+                    let prediction = match &label {
+                        Some(label) => {
+                            let mut state = self.state.write().unwrap();
+                            (state.generation == generation).then(|| {
+                                let prediction =
+                                    state.workspace.predict_then_learn(&mut features, label);
+                                features.clear_and_return_object(
+                                    state.workspace.features_pool().as_ref(),
+                                );
+                                prediction
+                            })
+                        }
+                        None => {
+                            let state = self.state.read().unwrap();
+                            (state.generation == generation).then(|| {
+                                let prediction = state.workspace.predict(&mut features);
+                                features.clear_and_return_object(
+                                    state.workspace.features_pool().as_ref(),
+                                );
+                                prediction
+                            })
+                        }
+                    };
+                    match prediction {
+                        Some(prediction) => break serde_json::to_string(&prediction)?,
+                        // The workspace was reloaded after the example was
+                        // parsed, so parse it again for the new one.
+                        None => (parser, generation) = self.create_parser()?,
+                    }
+                };
+                buffer = chunk;
+                response
+            };
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes a socket file left behind by a server which is no longer running,
+/// so that binding to its path does not fail.
+#[cfg(unix)]
+fn remove_stale_socket(path: &str) -> Result<()> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(anyhow!("Path exists and is not a socket: {}", path));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(anyhow!("Another server is listening on {}", path));
+    }
+    std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket: {}", path))
+}
+
+pub(crate) struct ServeCommand;
+
+impl Command for ServeCommand {
+    type Args = ServeArgs;
+    fn execute(args: &ServeArgs, quiet: bool) -> Result<()> {
+        let workspace = match (&args.input_config.config, &args.input_config.input_model) {
+            (Some(config_file), None) => {
+                let json = std::fs::read_to_string(config_file)
+                    .with_context(|| format!("Failed to read config file: {}", config_file))?;
+                Workspace::new(Configuration::from_json_str(&json)?).with_context(|| {
+                    format!(
+                        "Failed to create workspace from config file: {}",
+                        config_file
+                    )
+                })?
+            }
+            (None, Some(input_model_file)) => load_model(input_model_file)?,
+            _ => unreachable!(),
+        };
+
+        let model_dir = args
+            .model_dir
+            .as_ref()
+            .map(|dir| {
+                Path::new(dir)
+                    .canonicalize()
+                    .with_context(|| format!("Failed to find model directory: {}", dir))
+            })
+            .transpose()?;
+        let server = Arc::new(Server {
+            state: RwLock::new(ServeState {
+                workspace,
+                generation: 0,
+            }),
+            data_format: args.data_format,
+            csv_schema: args.csv_schema.load()?,
+            dsjson_options: args.dsjson.options(),
+            default_save_path: args.output_model.clone(),
+            default_reload_path: args.input_config.input_model.clone(),
+            model_dir,
+        });
+
+        // Fail now if the data format can't be served rather than on every
+        // connection.
+        server.create_parser()?;
+
+        #[cfg(unix)]
+        if let Some(path) = &args.unix_socket {
+            remove_stale_socket(path)?;
+            let listener = std::os::unix::net::UnixListener::bind(path)
+                .with_context(|| format!("Failed to bind to Unix socket: {}", path))?;
+            if !quiet {
+                eprintln!("{}: Listening on {}", "info".cyan().bold(), path.bold());
+            }
+            for stream in listener.incoming() {
+                let stream = stream?;
+                let server = server.clone();
+                std::thread::spawn(move || {
+                    let result = stream
+                        .try_clone()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|writer| server.handle_connection(stream, writer));
+                    if let Err(err) = result {
+                        eprintln!("{}: Connection failed: {:#}", "error".red().bold(), err);
+                    }
+                });
+            }
+            return Ok(());
+        }
+
+        let port = args.port.unwrap();
+        let listener = TcpListener::bind((args.host.as_str(), port))
+            .with_context(|| format!("Failed to bind to {}:{}", args.host, port))?;
+        if !quiet {
+            eprintln!(
+                "{}: Listening on {}",
+                "info".cyan().bold(),
+                listener.local_addr()?.bold()
+            );
+        }
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = server.clone();
+            std::thread::spawn(move || {
+                let result = stream
+                    .try_clone()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|writer| server.handle_connection(stream, writer));
+                if let Err(err) = result {
+                    eprintln!("{}: Connection failed: {:#}", "error".red().bold(), err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
@@ -2,43 +2,77 @@ use atomic_wait::{wait, wake_one};
 use reductionml_core::workspace::Configuration;
 use std::sync::atomic::AtomicU32;
 use std::{
+    borrow::Cow,
     cell::UnsafeCell,
     fs::File,
     io::{self, stdout, Write},
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 
-use clap::{Args, ValueHint};
+use clap::{Args, ValueEnum, ValueHint};
 use owo_colors::OwoColorize;
 // use crossterm::{cursor, terminal, ExecutableCommand};
 
 use crossterm::{cursor, terminal, ExecutableCommand};
 use prettytable::{format, Table};
 use reductionml_core::{
+    compression::{create_data_file, decompress_reader, CompressedWriter},
     metrics::{Metric, MetricValue},
     object_pool::{self, PoolReturnable},
-    Features, Label,
+    parsers::{
+        read_record_batches, CacheHeader, CacheReader, ColumnarFormat, ColumnarParser,
+        TextModeParser,
+    },
+    ExampleMetadata, Features, Label, Prediction,
 };
 
-use crate::{command::Command, DataFormat, InputConfigArg};
+use crate::{
+    command::Command, ColumnMappingArg, CsvSchemaArg, DataFormat, DsJsonArg, InputConfigArg,
+    COLUMNAR_BATCH_SIZE,
+};
 
 // TODO: multipass
 // TODO: test file for metrics
 #[derive(Args)]
 pub(crate) struct TrainArgs {
-    #[arg(short, long, value_hint = ValueHint::FilePath)]
-    data: String,
+    /// Data to train on. Can be given more than once to train on several
+    /// files in order. `-` reads from stdin and glob patterns are expanded to
+    /// the matching files in sorted order.
+    #[arg(short, long, value_hint = ValueHint::FilePath, required = true)]
+    data: Vec<String>,
 
     #[arg(long)]
     #[arg(default_value = "vw-text")]
     data_format: DataFormat,
 
+    #[command(flatten)]
+    csv_schema: CsvSchemaArg,
+
+    #[command(flatten)]
+    dsjson: DsJsonArg,
+
+    #[command(flatten)]
+    column_mapping: ColumnMappingArg,
+
     #[command(flatten)]
     input_config: InputConfigArg,
 
+    /// Only train on examples logged at or after this time, given in RFC 3339
+    /// format such as 2023-06-01T00:00:00Z. Requires data with timestamps.
+    #[arg(long, value_parser = parse_timestamp)]
+    start_time: Option<DateTime<Utc>>,
+
+    /// Only train on examples logged before this time, given in RFC 3339
+    /// format. Requires data with timestamps.
+    #[arg(long, value_parser = parse_timestamp)]
+    end_time: Option<DateTime<Utc>>,
+
     #[arg(long)]
     #[arg(default_value = "*2")]
     #[arg(value_parser = clap::value_parser!(OutputPeriod))]
@@ -48,8 +82,10 @@ pub(crate) struct TrainArgs {
     #[arg(short, long)]
     output_model: Option<String>,
 
-    // Output predictions to file
-    // TODO: implement this
+    /// Write the prediction made for each example, before learning from it,
+    /// to this file. Each line is a JSON object holding the example's id,
+    /// tag, weight and timestamp when it has them, and a "prediction" field.
+    /// The file is compressed when its name ends in .gz or .zst.
     #[arg(short, long)]
     predictions: Option<String>,
 
@@ -58,6 +94,38 @@ pub(crate) struct TrainArgs {
     #[arg(default_value = None, value_parser, num_args = 1.., value_delimiter = ',')]
     metrics: Option<Vec<String>>,
 
+    /// Number of most recent examples to calculate windowed metric values
+    /// over. When supplied, a windowed column is shown for each metric in
+    /// addition to the cumulative and since last report columns.
+    #[arg(long)]
+    #[arg(default_value = None)]
+    metrics_window: Option<usize>,
+
+    /// Write the metric values of every reported row to this file, along with
+    /// the number of examples, elapsed wall time and throughput.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    metrics_output: Option<String>,
+
+    #[arg(long)]
+    #[arg(default_value = "json-lines")]
+    metrics_output_format: MetricsOutputFormat,
+
+    /// Write a checkpoint of the model to the checkpoint directory every N
+    /// examples.
+    #[arg(long, requires = "checkpoint_dir")]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    checkpoint_every: Option<u64>,
+
+    /// Directory that checkpoints are written to and resumed from.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    checkpoint_dir: Option<String>,
+
+    /// Continue from the latest checkpoint in the checkpoint directory,
+    /// skipping the examples it has already consumed. If no checkpoint exists
+    /// training starts from the supplied config or model.
+    #[arg(long, requires = "checkpoint_dir")]
+    resume: bool,
+
     #[arg(long)]
     #[arg(default_value = "512")]
     queue_size: usize,
@@ -70,6 +138,12 @@ pub(crate) struct TrainArgs {
     num_parse_threads: Option<usize>,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum MetricsOutputFormat {
+    JsonLines,
+    Csv,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum OutputPeriod {
     Additive(u32),
@@ -110,10 +184,37 @@ impl FromStr for OutputPeriod {
     }
 }
 
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid timestamp: {}. Must be in RFC 3339 format", e))
+}
+
+/// Examples are only trained on when their timestamp falls within the window.
+struct TimeWindow {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+impl TimeWindow {
+    fn contains(&self, metadata: &ExampleMetadata) -> Result<bool> {
+        if self.start.is_none() && self.end.is_none() {
+            return Ok(true);
+        }
+        let timestamp = metadata.timestamp.ok_or_else(|| {
+            anyhow!("--start-time and --end-time require every example to have a timestamp")
+        })?;
+        Ok(self.start.is_none_or(|start| timestamp >= start)
+            && self.end.is_none_or(|end| timestamp < end))
+    }
+}
+
+type ParsedExample<'a> = (Features<'a>, Option<Label>, ExampleMetadata);
+
 struct ParseResult<'a> {
     // 0 not ready, 1 ready
     ready: AtomicU32,
-    result: UnsafeCell<Option<Result<(Features<'a>, Option<Label>)>>>,
+    result: UnsafeCell<Option<Result<ParsedExample<'a>>>>,
     input: UnsafeCell<Option<String>>,
 }
 
@@ -131,7 +232,7 @@ impl<'a> ParseResult<'a> {
         unsafe { (*self.input.get()).take().unwrap() }
     }
 
-    fn await_result(&self) -> Result<(Features<'a>, Option<Label>)> {
+    fn await_result(&self) -> Result<ParsedExample<'a>> {
         while self.ready.load(Ordering::Relaxed) == 0 {
             wait(&self.ready, 0);
         }
@@ -139,7 +240,7 @@ impl<'a> ParseResult<'a> {
         unsafe { (*self.result.get()).take().unwrap() }
     }
 
-    fn set_result(&self, result: Result<(Features<'a>, Option<Label>)>) {
+    fn set_result(&self, result: Result<ParsedExample<'a>>) {
         assert!(self.ready.load(Ordering::SeqCst) == 0);
         unsafe {
             *self.result.get() = Some(result);
@@ -151,36 +252,293 @@ impl<'a> ParseResult<'a> {
 
 unsafe impl Sync for ParseResult<'_> {}
 
+fn escape_csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+struct MetricsOutput {
+    writer: io::BufWriter<File>,
+    format: MetricsOutputFormat,
+    with_timestamps: bool,
+}
+
+impl MetricsOutput {
+    /// When `with_timestamps` is set each row includes the timestamp of the
+    /// most recent example.
+    fn create(
+        path: &str,
+        format: MetricsOutputFormat,
+        columns: &[String],
+        with_timestamps: bool,
+    ) -> Result<MetricsOutput> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create metrics output file: {}", path))?;
+        let mut writer = io::BufWriter::new(file);
+        if format == MetricsOutputFormat::Csv {
+            let header = ["examples", "wall_time_secs", "examples_per_sec"]
+                .into_iter()
+                .chain(with_timestamps.then_some("timestamp"))
+                .map(Cow::from)
+                .chain(columns.iter().map(|x| escape_csv_field(x)))
+                .collect::<Vec<_>>()
+                .join(",");
+            writeln!(writer, "{}", header)?;
+        }
+        Ok(MetricsOutput {
+            writer,
+            format,
+            with_timestamps,
+        })
+    }
+
+    /// `new_examples` excludes examples consumed before resuming, which
+    /// aren't part of the rate.
+    fn write_row(
+        &mut self,
+        examples: u64,
+        new_examples: u64,
+        elapsed: Duration,
+        timestamp: Option<DateTime<Utc>>,
+        columns: &[String],
+        values: &[MetricValue],
+    ) -> Result<()> {
+        let wall_time_secs = elapsed.as_secs_f64();
+        let examples_per_sec = new_examples as f64 / wall_time_secs;
+        match self.format {
+            MetricsOutputFormat::JsonLines => {
+                let mut row = serde_json::Map::new();
+                row.insert("examples".to_owned(), examples.into());
+                row.insert("wall_time_secs".to_owned(), wall_time_secs.into());
+                row.insert("examples_per_sec".to_owned(), examples_per_sec.into());
+                if let Some(timestamp) = timestamp {
+                    row.insert("timestamp".to_owned(), timestamp.to_rfc3339().into());
+                }
+                for (column, value) in columns.iter().zip(values) {
+                    row.insert(column.clone(), serde_json::to_value(value)?);
+                }
+                writeln!(self.writer, "{}", serde_json::Value::Object(row))?;
+            }
+            MetricsOutputFormat::Csv => {
+                let row = [
+                    examples.to_string(),
+                    wall_time_secs.to_string(),
+                    examples_per_sec.to_string(),
+                ]
+                .into_iter()
+                .chain(
+                    self.with_timestamps
+                        .then(|| timestamp.map(|t| t.to_rfc3339()).unwrap_or_default()),
+                )
+                .chain(
+                    values
+                        .iter()
+                        .map(|v| escape_csv_field(&v.to_string()).into_owned()),
+                )
+                .collect::<Vec<_>>()
+                .join(",");
+                writeln!(self.writer, "{}", row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum TrainInput {
+    Text(Box<dyn TextModeParser>, Box<dyn io::BufRead + Send>),
+    Columnar(ColumnarParser, ColumnarFormat, File),
+    Cache(CacheReader, Box<dyn io::BufRead + Send>),
+}
+
+const CHECKPOINT_FILE_PREFIX: &str = "checkpoint-";
+const CHECKPOINT_FILE_EXTENSION: &str = "model";
+
+/// How far into the data a checkpoint was taken.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+struct CheckpointPosition {
+    /// Examples read, including ones which were not learned from because they
+    /// had no label or were outside of the time window.
+    consumed: u64,
+    /// Examples learned from, which is what the metrics count.
+    learned: u64,
+}
+
+/// Returns the position a checkpoint was taken at, if the path names a
+/// checkpoint file.
+fn checkpoint_position(path: &Path) -> Option<CheckpointPosition> {
+    if path.extension()? != CHECKPOINT_FILE_EXTENSION {
+        return None;
+    }
+    let (consumed, learned) = path
+        .file_stem()?
+        .to_str()?
+        .strip_prefix(CHECKPOINT_FILE_PREFIX)?
+        .split_once('-')?;
+    Some(CheckpointPosition {
+        consumed: consumed.parse().ok()?,
+        learned: learned.parse().ok()?,
+    })
+}
+
+fn find_latest_checkpoint(dir: &Path) -> Result<Option<(PathBuf, CheckpointPosition)>> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut latest: Option<(PathBuf, CheckpointPosition)> = None;
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read checkpoint directory: {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if let Some(position) = checkpoint_position(&path) {
+            if latest
+                .as_ref()
+                .is_none_or(|(_, latest)| position.consumed > latest.consumed)
+            {
+                latest = Some((path, position));
+            }
+        }
+    }
+    Ok(latest)
+}
+
+struct Checkpointer {
+    dir: PathBuf,
+    every: u64,
+    position: CheckpointPosition,
+}
+
+impl Checkpointer {
+    fn new(dir: &Path, every: u64, position: CheckpointPosition) -> Result<Checkpointer> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create checkpoint directory: {}", dir.display()))?;
+        Ok(Checkpointer {
+            dir: dir.to_owned(),
+            every,
+            position,
+        })
+    }
+
+    fn on_example(
+        &mut self,
+        workspace: &reductionml_core::workspace::Workspace,
+        learned: bool,
+    ) -> Result<()> {
+        self.position.consumed += 1;
+        if learned {
+            self.position.learned += 1;
+        }
+        if self.position.consumed.is_multiple_of(self.every) {
+            self.save(workspace)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, workspace: &reductionml_core::workspace::Workspace) -> Result<()> {
+        // Zero padded so that checkpoints sort in order when listed.
+        let path = self.dir.join(format!(
+            "{}{:012}-{:012}.{}",
+            CHECKPOINT_FILE_PREFIX,
+            self.position.consumed,
+            self.position.learned,
+            CHECKPOINT_FILE_EXTENSION
+        ));
+        // Write to a temporary file first so that an interrupted write never
+        // leaves behind a truncated checkpoint.
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, workspace.serialize_model()?)
+            .with_context(|| format!("Failed to write checkpoint: {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &path)
+            .with_context(|| format!("Failed to write checkpoint: {}", path.display()))?;
+        Ok(())
+    }
+}
+
 struct TrainResultManager {
-    iteration: u32,
-    next_output_iteration: u32,
+    iteration: u64,
+    // Iterations learned by an earlier run that this one resumed from.
+    first_iteration: u64,
+    next_output_iteration: u64,
     period: OutputPeriod,
     last_render_height: u16,
     table: Table,
     columns: Vec<String>,
+    metrics_window: Option<usize>,
+    metrics_output: Option<MetricsOutput>,
+    start_time: Instant,
+    timestamp: Option<DateTime<Utc>>,
 }
 
 impl TrainResultManager {
-    fn new(period: OutputPeriod, columns: Vec<String>) -> TrainResultManager {
+    fn new(
+        period: OutputPeriod,
+        metrics: &[Box<dyn Metric>],
+        metrics_window: Option<usize>,
+        metrics_output: Option<(&str, MetricsOutputFormat)>,
+        with_timestamps: bool,
+    ) -> Result<TrainResultManager> {
+        let mut columns = Vec::new();
+        for metric in metrics {
+            let name = metric.get_name();
+            columns.push(name.clone());
+            if metric.get_since_last_value().is_some() {
+                columns.push(format!("{} (since last)", name));
+            }
+            if let Some(window) = metrics_window {
+                if metric.get_window_value().is_some() {
+                    columns.push(format!("{} (last {})", name, window));
+                }
+            }
+        }
+
+        let metrics_output = metrics_output
+            .map(|(path, format)| MetricsOutput::create(path, format, &columns, with_timestamps))
+            .transpose()?;
+
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
         table.set_titles(columns.iter().into());
-        TrainResultManager {
+        Ok(TrainResultManager {
             iteration: 0,
+            first_iteration: 0,
             next_output_iteration: 0,
             period,
             last_render_height: 0,
             table,
             columns,
-        }
+            metrics_window,
+            metrics_output,
+            start_time: Instant::now(),
+            timestamp: None,
+        })
+    }
+
+    /// Continues counting from the examples learned by an earlier run.
+    fn resume_from(&mut self, examples: u64) {
+        self.iteration = examples;
+        self.first_iteration = examples;
+    }
+
+    /// Records the timestamp of the latest example, which is included in the
+    /// metrics output.
+    fn set_timestamp(&mut self, timestamp: Option<DateTime<Utc>>) {
+        self.timestamp = timestamp;
+    }
+
+    fn is_writing_metrics(&self) -> bool {
+        self.metrics_output.is_some()
     }
 
     #[must_use = "Output indicates if results should be added for this iteration."]
     fn inc_iteration(&mut self) -> bool {
         if self.iteration >= self.next_output_iteration {
             self.next_output_iteration = match self.period {
-                OutputPeriod::Additive(period) => self.iteration + period,
-                OutputPeriod::Multiplicative(period) => (self.iteration as f32 * period) as u32,
+                OutputPeriod::Additive(period) => self.iteration + period as u64,
+                OutputPeriod::Multiplicative(period) => {
+                    (self.iteration as f64 * period as f64) as u64
+                }
             };
             self.iteration += 1;
             return true;
@@ -189,13 +547,47 @@ impl TrainResultManager {
         false
     }
 
-    fn add_results(&mut self, results: Vec<MetricValue>) {
-        if results.len() == self.columns.len() {
-            self.table
-                .add_row(results.iter().map(|v| v.to_string()).into());
-        } else {
+    /// Adds a row with the current values of each metric and starts a new
+    /// "since last" period for all of them.
+    fn add_results(&mut self, metrics: &mut [Box<dyn Metric>]) -> Result<()> {
+        let mut results: Vec<MetricValue> = Vec::new();
+        for metric in metrics.iter_mut() {
+            results.push(metric.get_value());
+            if let Some(value) = metric.get_since_last_value() {
+                results.push(value);
+            }
+            if self.metrics_window.is_some() {
+                if let Some(value) = metric.get_window_value() {
+                    results.push(value);
+                }
+            }
+            metric.mark_reported();
+        }
+
+        if results.len() != self.columns.len() {
             panic!("Results columns do not match previous results columns.");
         }
+
+        if let Some(output) = self.metrics_output.as_mut() {
+            output.write_row(
+                self.iteration,
+                self.iteration - self.first_iteration,
+                self.start_time.elapsed(),
+                self.timestamp,
+                &self.columns,
+                &results,
+            )?;
+        }
+        self.table
+            .add_row(results.iter().map(|v| v.to_string()).into());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(output) = self.metrics_output.as_mut() {
+            output.writer.flush()?;
+        }
+        Ok(())
     }
 
     // FIXME: if the period is too low and the training too fast then this will cause rendering issues.
@@ -216,7 +608,29 @@ pub(crate) struct TrainCommand;
 impl Command for TrainCommand {
     type Args = TrainArgs;
     fn execute(args: &TrainArgs, quiet: bool) -> Result<()> {
+        let checkpoint_dir = args.checkpoint_dir.as_deref().map(Path::new);
+        let resume_from = match checkpoint_dir {
+            Some(dir) if args.resume => find_latest_checkpoint(dir)?,
+            _ => None,
+        };
+
         let mut workspace = match (&args.input_config.config, &args.input_config.input_model) {
+            // Resuming from a checkpoint takes precedence over the supplied
+            // config or model
+            _ if resume_from.is_some() => {
+                let (path, _) = resume_from.as_ref().unwrap();
+                let data = std::fs::read(path).with_context(|| {
+                    format!("Failed to read checkpoint file: {}", path.display())
+                })?;
+                reductionml_core::workspace::Workspace::create_from_model(&data).with_context(
+                    || {
+                        format!(
+                            "Failed to create workspace from checkpoint file: {}",
+                            path.display()
+                        )
+                    },
+                )?
+            }
             // Loading from json config
             (Some(config_file), None) => {
                 let json = std::fs::read_to_string(config_file)
@@ -246,13 +660,9 @@ impl Command for TrainCommand {
             _ => unreachable!(),
         };
 
-        let file = File::open(&args.data)
-            .with_context(|| format!("Failed to open data file: {}", args.data))?;
-        eprintln!(
-            "{}: Reading data file: {}",
-            "info".cyan().bold(),
-            &args.data.bold()
-        );
+        let data_sources = expand_data_args(&args.data)?;
+        let column_mapping = args.column_mapping.load()?;
+        let csv_schema = args.csv_schema.load()?;
         eprintln!(
             "{}: Using entry reduction: {}",
             "info".cyan().bold(),
@@ -262,131 +672,304 @@ impl Command for TrainCommand {
 
         let pool = workspace.features_pool().clone();
 
-        let parser = args.data_format.get_parser(
-            workspace
-                .get_entry_reduction()
-                .types()
-                .input_features_type(),
-            workspace.get_entry_reduction().types().input_label_type(),
-            workspace.global_config().hash_seed(),
-            workspace.global_config().num_bits(),
-            pool.clone(),
-        );
-
-        let mut input_file = io::BufReader::new(file);
-        let mut predictions_file = if let Some(pred_file_name) = &args.predictions {
+        let resume_position = resume_from
+            .as_ref()
+            .map_or(CheckpointPosition::default(), |(_, position)| *position);
+        let examples_consumed = resume_position.consumed;
+        if let Some((path, position)) = &resume_from {
             eprintln!(
-                "{}: The format output in the predictions file is currently a placeholder",
-                "warning".yellow().bold()
+                "{}: Resuming from checkpoint {}, skipping {} examples",
+                "info".cyan().bold(),
+                path.display().bold(),
+                position.consumed
             );
-            let file = File::create(pred_file_name).with_context(|| {
+        }
+
+        let mut checkpointer = match (checkpoint_dir, args.checkpoint_every) {
+            (Some(dir), Some(every)) => Some(Checkpointer::new(dir, every, resume_position)?),
+            _ => None,
+        };
+        let mut predictions_file = if let Some(pred_file_name) = &args.predictions {
+            Some(create_data_file(pred_file_name).with_context(|| {
                 format!("Failed to create predictions file: {}", pred_file_name)
-            })?;
-            Some(io::BufWriter::new(file))
+            })?)
         } else {
             None
         };
 
-        let mut metrics = vec![reductionml_core::metrics::get_metric("example_number").unwrap()];
-        if args.metrics.is_some() {
-            args.metrics.as_ref().unwrap().iter().for_each(|name| {
-                metrics.push(reductionml_core::metrics::get_metric(name).unwrap())
-            });
-        };
+        let types = workspace.get_entry_reduction().types();
+        let mut metrics = vec![reductionml_core::metrics::create_metric(
+            "example_number",
+            types,
+        )?];
+        for name in args.metrics.iter().flatten() {
+            metrics.push(
+                reductionml_core::metrics::create_metric(name, types)
+                    .with_context(|| format!("Failed to create metric: {}", name))?,
+            );
+        }
+
+        if let Some(window) = args.metrics_window {
+            metrics
+                .iter_mut()
+                .for_each(|metric| metric.set_window_size(window));
+        }
 
         let mut manager = TrainResultManager::new(
             args.progress,
-            metrics.iter().map(|x| x.get_name()).collect(),
-        );
+            &metrics,
+            args.metrics_window,
+            args.metrics_output
+                .as_deref()
+                .map(|path| (path, args.metrics_output_format)),
+            args.data_format == DataFormat::Dsjson,
+        )?;
+        if resume_position.learned > 0 {
+            manager.resume_from(resume_position.learned);
+            metrics
+                .iter_mut()
+                .for_each(|metric| metric.resume_from(resume_position.learned));
+        }
+        let time_window = TimeWindow {
+            start: args.start_time,
+            end: args.end_time,
+        };
 
         let num_parse_threads = match args.num_parse_threads {
             Some(n) => n,
             None => (num_cpus::get() as i32 - 2).max(0) as usize,
         };
 
-        match num_parse_threads {
-            0 => {
-                let mut buffer = String::new();
-                while let Some(chunk) = parser.get_next_chunk(&mut input_file, buffer).unwrap() {
-                    let (features, label) = parser.parse_chunk(&chunk).unwrap();
-                    buffer = chunk;
-                    process_example(
-                        label,
-                        &mut workspace,
-                        features,
-                        &mut predictions_file,
-                        &mut metrics,
-                        &mut manager,
-                        quiet,
-                        &pool,
-                    );
+        let mut skipped = 0;
+        for (i, source) in data_sources.iter().enumerate() {
+            eprintln!(
+                "{}: Reading data file ({}/{}): {}",
+                "info".cyan().bold(),
+                i + 1,
+                data_sources.len(),
+                source.bold()
+            );
+            let to_skip = examples_consumed - skipped;
+            let input = match args.data_format.columnar_format() {
+                _ if args.data_format == DataFormat::Cache => {
+                    let mut input_file = source.open()?;
+                    let reader = CacheReader::new(&mut input_file, pool.clone())
+                        .with_context(|| format!("Failed to read cache file: {}", source))?;
+                    check_cache_header(reader.header(), &workspace)?;
+                    TrainInput::Cache(reader, input_file)
                 }
-            }
-            n => {
-                let string_pool = object_pool::Pool::<String>::new();
-                let (parse_sender, parse_receiver) = flume::bounded(args.queue_size);
-                let (learn_sender, learn_receiver) = flume::bounded(args.queue_size);
-                std::thread::scope(|s| -> Result<()> {
-                    // Input thread
-                    s.spawn(|| {
-                        loop {
-                            if let Some(chunk) = parser
-                                .get_next_chunk(&mut input_file, string_pool.get_object())
-                                .unwrap()
-                            {
-                                let res = Arc::new(ParseResult::new(chunk));
-                                parse_sender.send(res.clone()).unwrap();
-                                learn_sender.send(res).unwrap();
-                            } else {
-                                break;
-                            }
+                Some(format) => {
+                    let parser = ColumnarParser::new(
+                        column_mapping.as_ref().unwrap(),
+                        workspace
+                            .get_entry_reduction()
+                            .types()
+                            .input_features_type(),
+                        workspace.get_entry_reduction().types().input_label_type(),
+                        workspace.global_config().hash_seed(),
+                        workspace.global_config().hash_function(),
+                        workspace.global_config().num_bits(),
+                        pool.clone(),
+                    )?;
+                    TrainInput::Columnar(parser, format, source.open_file()?)
+                }
+                None => TrainInput::Text(
+                    args.data_format.get_parser(
+                        workspace
+                            .get_entry_reduction()
+                            .types()
+                            .input_features_type(),
+                        workspace.get_entry_reduction().types().input_label_type(),
+                        workspace.global_config().hash_seed(),
+                        workspace.global_config().hash_function(),
+                        workspace.global_config().num_bits(),
+                        pool.clone(),
+                        csv_schema.as_ref(),
+                        args.dsjson.options(),
+                    )?,
+                    source.open()?,
+                ),
+            };
+
+            skipped += match input {
+                TrainInput::Columnar(parser, format, file) => {
+                    let mut skipped = 0;
+                    for batch in read_record_batches(file, format, COLUMNAR_BATCH_SIZE)? {
+                        let mut batch = batch?;
+                        if skipped < to_skip {
+                            let skip = (to_skip - skipped).min(batch.num_rows() as u64);
+                            skipped += skip;
+                            batch = batch.slice(skip as usize, batch.num_rows() - skip as usize);
                         }
-                        std::mem::drop(parse_sender);
-                        std::mem::drop(learn_sender);
-                    });
-
-                    for _ in 0..n {
-                        s.spawn(|| loop {
-                            match parse_receiver.recv() {
-                                Ok(res) => {
-                                    let input = res.get_input();
-                                    let parsed = parser.parse_chunk(&input);
-                                    string_pool.return_object(input);
-                                    res.set_result(parsed.map_err(|e| anyhow::anyhow!(e)));
-                                }
-                                Err(_) => break,
+                        for (features, label) in parser.parse_batch(&batch)? {
+                            let metadata = ExampleMetadata::default();
+                            process_example(
+                                label,
+                                &mut workspace,
+                                features,
+                                metadata,
+                                &mut predictions_file,
+                                &mut metrics,
+                                &mut manager,
+                                &mut checkpointer,
+                                &time_window,
+                                quiet,
+                                &pool,
+                            )?;
+                        }
+                    }
+                    skipped
+                }
+                TrainInput::Cache(reader, mut input_file) => {
+                    let mut buffer = Vec::new();
+                    let mut skipped = 0;
+                    while skipped < to_skip {
+                        match reader.get_next_chunk(&mut input_file, std::mem::take(&mut buffer))? {
+                            Some(chunk) => {
+                                buffer = chunk;
+                                skipped += 1;
                             }
-                        });
+                            None => break,
+                        }
                     }
-
-                    loop {
-                        let res = learn_receiver.recv();
-                        match res {
-                            Ok(result) => {
-                                let (features, label) = result.await_result().unwrap();
+                    while let Some(chunk) = reader.get_next_chunk(&mut input_file, buffer)? {
+                        let (features, label, metadata) = reader.parse_chunk(&chunk)?;
+                        buffer = chunk;
+                        process_example(
+                            label,
+                            &mut workspace,
+                            features,
+                            metadata,
+                            &mut predictions_file,
+                            &mut metrics,
+                            &mut manager,
+                            &mut checkpointer,
+                            &time_window,
+                            quiet,
+                            &pool,
+                        )?;
+                    }
+                    skipped
+                }
+                TrainInput::Text(parser, mut input_file) => {
+                    let skipped = skip_text_examples(parser.as_ref(), &mut input_file, to_skip)?;
+                    match num_parse_threads {
+                        0 => {
+                            let mut buffer = String::new();
+                            while let Some(chunk) =
+                                parser.get_next_chunk(&mut input_file, buffer)?
+                            {
+                                let (features, label, metadata) = parser.parse_chunk(&chunk)?;
+                                buffer = chunk;
                                 process_example(
                                     label,
                                     &mut workspace,
                                     features,
+                                    metadata,
                                     &mut predictions_file,
                                     &mut metrics,
                                     &mut manager,
+                                    &mut checkpointer,
+                                    &time_window,
                                     quiet,
                                     &pool,
-                                );
+                                )?;
                             }
-                            Err(_) => break,
+                        }
+                        n => {
+                            let string_pool = object_pool::Pool::<String>::new();
+                            let parser = parser.as_ref();
+                            let string_pool = &string_pool;
+                            let input_file = &mut input_file;
+                            std::thread::scope(|s| -> Result<()> {
+                                // The channels are owned by this scope so that
+                                // dropping the receivers below, when learning
+                                // fails, stops the input and parse threads
+                                // instead of leaving them blocked.
+                                let (parse_sender, parse_receiver) =
+                                    flume::bounded::<Arc<ParseResult>>(args.queue_size);
+                                let (learn_sender, learn_receiver) =
+                                    flume::bounded::<Arc<ParseResult>>(args.queue_size);
+
+                                // Input thread
+                                let input_thread = s.spawn(move || -> Result<()> {
+                                    while let Some(chunk) = parser
+                                        .get_next_chunk(input_file, string_pool.get_object())?
+                                    {
+                                        let res = Arc::new(ParseResult::new(chunk));
+                                        // Sending only fails once learning has stopped.
+                                        if parse_sender.send(res.clone()).is_err()
+                                            || learn_sender.send(res).is_err()
+                                        {
+                                            break;
+                                        }
+                                    }
+                                    Ok(())
+                                });
+
+                                for _ in 0..n {
+                                    let parse_receiver = parse_receiver.clone();
+                                    s.spawn(move || {
+                                        for res in parse_receiver.iter() {
+                                            let input = res.get_input();
+                                            let parsed = parser.parse_chunk(&input);
+                                            string_pool.return_object(input);
+                                            res.set_result(parsed.map_err(|e| anyhow::anyhow!(e)));
+                                        }
+                                    });
+                                }
+                                std::mem::drop(parse_receiver);
+
+                                let mut learn = || -> Result<()> {
+                                    for result in learn_receiver.iter() {
+                                        let (features, label, metadata) = result.await_result()?;
+                                        process_example(
+                                            label,
+                                            &mut workspace,
+                                            features,
+                                            metadata,
+                                            &mut predictions_file,
+                                            &mut metrics,
+                                            &mut manager,
+                                            &mut checkpointer,
+                                            &time_window,
+                                            quiet,
+                                            &pool,
+                                        )?;
+                                    }
+                                    Ok(())
+                                };
+                                let learned = learn();
+                                std::mem::drop(learn_receiver);
+
+                                let read = input_thread.join().expect("Input thread panicked");
+                                learned.and(read)
+                            })?;
                         }
                     }
-                    Ok(())
-                })?;
-            }
+
+                    skipped
+                }
+            };
+        }
+        if skipped < examples_consumed {
+            eprintln!(
+                "{}: Data contains fewer examples ({}) than the checkpoint has consumed",
+                "warning".yellow().bold(),
+                skipped
+            );
         }
 
+        if !quiet || manager.is_writing_metrics() {
+            manager.add_results(&mut metrics)?;
+        }
         if !quiet {
-            manager.add_results(metrics.iter().map(|x| x.get_value()).collect());
             manager.render_table_to_stdout();
         }
+        manager.flush()?;
+        if let Some(file) = predictions_file {
+            file.finish().context("Failed to write predictions file")?;
+        }
 
         if let Some(file) = &args.output_model {
             let data = workspace.serialize_model().unwrap();
@@ -397,39 +980,187 @@ impl Command for TrainCommand {
     }
 }
 
+/// Where training data is read from.
+enum DataSource {
+    Stdin,
+    File(PathBuf),
+}
+
+impl std::fmt::Display for DataSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataSource::Stdin => write!(f, "stdin"),
+            DataSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl DataSource {
+    /// Opens the source for reading, decompressing it if needed.
+    fn open(&self) -> Result<Box<dyn io::BufRead + Send>> {
+        let reader = match self {
+            DataSource::Stdin => decompress_reader(io::stdin()),
+            DataSource::File(_) => decompress_reader(self.open_file()?),
+        };
+        reader.with_context(|| format!("Failed to read data from {}", self))
+    }
+
+    /// Opens the file itself, for formats which need random access.
+    fn open_file(&self) -> Result<File> {
+        match self {
+            DataSource::Stdin => Err(anyhow!("Arrow and Parquet data can't be read from stdin")),
+            DataSource::File(path) => File::open(path)
+                .with_context(|| format!("Failed to open data file: {}", path.display())),
+        }
+    }
+}
+
+/// Expands the `--data` arguments into the sources to read, in order. `-` is
+/// stdin and any argument which isn't an existing file is treated as a glob
+/// pattern, which must match at least one file.
+fn expand_data_args(args: &[String]) -> Result<Vec<DataSource>> {
+    let mut sources = Vec::new();
+    for arg in args {
+        if arg == "-" {
+            sources.push(DataSource::Stdin);
+        } else if Path::new(arg).exists() {
+            sources.push(DataSource::File(PathBuf::from(arg)));
+        } else {
+            let mut paths = glob::glob(arg)
+                .with_context(|| format!("Invalid data pattern: {}", arg))?
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .with_context(|| format!("Failed to expand data pattern: {}", arg))?;
+            if paths.is_empty() {
+                return Err(anyhow!("No data files found matching: {}", arg));
+            }
+            paths.sort();
+            sources.extend(paths.into_iter().map(DataSource::File));
+        }
+    }
+    Ok(sources)
+}
+
+/// Cached feature indices are only valid for the hashing they were created
+/// with, and the examples must be of the types the model takes.
+fn check_cache_header(
+    header: &CacheHeader,
+    workspace: &reductionml_core::workspace::Workspace,
+) -> Result<()> {
+    let global_config = workspace.global_config();
+    let types = workspace.get_entry_reduction().types();
+    let expected = CacheHeader {
+        num_bits: global_config.num_bits(),
+        hash_seed: global_config.hash_seed(),
+        hash_function: global_config.hash_function(),
+        features_type: types.input_features_type(),
+        label_type: types.input_label_type(),
+    };
+    if *header != expected {
+        return Err(anyhow!(
+            "Cache was created with {:?} but the model uses {:?}",
+            header,
+            expected
+        ));
+    }
+    Ok(())
+}
+
+/// Skips examples which have already been trained on, returning how many were
+/// skipped.
+fn skip_text_examples(
+    parser: &dyn TextModeParser,
+    input_file: &mut dyn io::BufRead,
+    examples: u64,
+) -> Result<u64> {
+    let mut buffer = String::new();
+    let mut skipped = 0;
+    while skipped < examples {
+        match parser.get_next_chunk(input_file, buffer)? {
+            Some(chunk) => {
+                buffer = chunk;
+                skipped += 1;
+            }
+            None => break,
+        }
+    }
+    Ok(skipped)
+}
+
 #[allow(clippy::too_many_arguments)]
 fn process_example(
     label: Option<Label>,
     workspace: &mut reductionml_core::workspace::Workspace,
     mut features: Features<'_>,
-    predictions_file: &mut Option<io::BufWriter<File>>,
+    metadata: ExampleMetadata,
+    predictions_file: &mut Option<CompressedWriter<io::BufWriter<File>>>,
     metrics: &mut [Box<dyn Metric>],
     manager: &mut TrainResultManager,
+    checkpointer: &mut Option<Checkpointer>,
+    time_window: &TimeWindow,
     quiet: bool,
     pool: &std::sync::Arc<
         object_pool::Pool<reductionml_core::sparse_namespaced_features::SparseFeatures>,
     >,
-) {
-    let label = label.unwrap();
-    if !quiet || predictions_file.is_some() {
-        let prediction = workspace.predict_then_learn(&mut features, &label);
-        if let Some(file) = predictions_file.as_mut() {
-            writeln!(file, "{}", serde_json::to_string(&prediction).unwrap()).unwrap();
-        }
+) -> Result<()> {
+    // Examples outside of the time window are skipped, but still count as
+    // consumed for checkpoints.
+    let mut learned = false;
+    if time_window.contains(&metadata)? {
+        manager.set_timestamp(metadata.timestamp);
+        match label {
+            // Examples without a label, such as DSJSON events marked with
+            // _skipLearn, are only predicted on.
+            None => {
+                if let Some(file) = predictions_file.as_mut() {
+                    let prediction = workspace.predict(&mut features);
+                    write_prediction(file, &metadata, &prediction)?;
+                }
+            }
+            Some(label) if !quiet || predictions_file.is_some() || manager.is_writing_metrics() => {
+                learned = true;
+                let prediction = workspace.predict_then_learn(&mut features, &label);
+                if let Some(file) = predictions_file.as_mut() {
+                    write_prediction(file, &metadata, &prediction)?;
+                }
 
-        for metric in metrics.iter_mut() {
-            metric.add_point(&features, &label, &prediction);
-        }
+                for metric in metrics.iter_mut() {
+                    metric.add_point(&features, &label, &prediction);
+                }
 
-        let should_output = manager.inc_iteration();
-        if should_output {
-            manager.add_results(metrics.iter().map(|x| x.get_value()).collect());
-            manager.render_table_to_stdout();
+                let should_output = manager.inc_iteration();
+                if should_output {
+                    manager.add_results(metrics)?;
+                    if !quiet {
+                        manager.render_table_to_stdout();
+                    }
+                }
+            }
+            Some(label) => {
+                learned = true;
+                workspace.learn(&mut features, &label);
+            }
         }
-    } else {
-        workspace.learn(&mut features, &label);
     }
 
     // Put feature objects back into the pool for reuse.
     features.clear_and_return_object(pool.as_ref());
+
+    if let Some(checkpointer) = checkpointer.as_mut() {
+        checkpointer.on_example(workspace, learned)?;
+    }
+    Ok(())
+}
+
+/// Each line of the predictions file is the prediction along with the
+/// metadata of the example, so that it can be joined back to the input.
+fn write_prediction(
+    file: &mut impl Write,
+    metadata: &ExampleMetadata,
+    prediction: &Prediction,
+) -> Result<()> {
+    let mut record = serde_json::to_value(metadata)?;
+    record["prediction"] = serde_json::to_value(prediction)?;
+    serde_json::to_writer(&mut *file, &record)?;
+    writeln!(file)?;
+    Ok(())
 }
@@ -1,5 +1,5 @@
 use assert_cmd::prelude::*;
-use assert_fs::prelude::FileWriteStr;
+use assert_fs::prelude::{FileWriteStr, PathAssert, PathChild, PathCreateDir};
 // Add methods on commands
 use predicates::prelude::*; // Used for writing assertions
 use std::process::Command; // Run programs
@@ -17,3 +17,1079 @@ fn invalid_config_fails_check() -> Result<(), Box<dyn std::error::Error>> {
         .stderr(predicate::str::contains("Failed to parse configuration"));
     Ok(())
 }
+
+#[test]
+fn unknown_metric_fails_train() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reml")?;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str("1 | a b c\n")?;
+
+    cmd.arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--metrics")
+        .arg("not_a_metric");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown metric: not_a_metric"));
+    Ok(())
+}
+
+#[test]
+fn incompatible_metric_fails_train() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reml")?;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str("1 | a b c\n")?;
+
+    cmd.arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--metrics")
+        .arg("ips");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("requires label type CB"));
+    Ok(())
+}
+
+#[test]
+fn train_writes_metrics_output_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reml")?;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str("1 | a b c\n0 | a d\n1 | b c\n")?;
+    let output = assert_fs::NamedTempFile::new("metrics.csv")?;
+
+    cmd.arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--metrics")
+        .arg("mse")
+        .arg("--metrics-output")
+        .arg(output.path())
+        .arg("--metrics-output-format")
+        .arg("csv");
+    cmd.assert().success();
+
+    let contents = std::fs::read_to_string(output.path())?;
+    let mut lines = contents.lines();
+    assert_eq!(
+        lines.next(),
+        Some("examples,wall_time_secs,examples_per_sec,Example #,MeanSquaredError,MeanSquaredError (since last)")
+    );
+    let last = lines.last().unwrap();
+    assert!(last.starts_with("3,"));
+    // Rows with no new examples leave the "since last" value empty.
+    assert!(!contents.contains("NaN"));
+    Ok(())
+}
+
+#[test]
+fn train_checkpoints_and_resumes() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    // The unlabeled example is consumed but not learned from.
+    data.write_str("1 | a b c\n| a d\n1 | b c\n0 | d\n1 | e\n")?;
+    let checkpoint_dir = assert_fs::TempDir::new()?;
+
+    let train = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("reml")?;
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data.path())
+            .arg("--checkpoint-every")
+            .arg("2")
+            .arg("--checkpoint-dir")
+            .arg(checkpoint_dir.path());
+        Ok(cmd)
+    };
+
+    train()?.assert().success();
+    checkpoint_dir
+        .child("checkpoint-000000000002-000000000001.model")
+        .assert(predicate::path::is_file());
+    checkpoint_dir
+        .child("checkpoint-000000000004-000000000003.model")
+        .assert(predicate::path::is_file());
+
+    let metrics = assert_fs::NamedTempFile::new("metrics.jsonl")?;
+    train()?
+        .arg("--resume")
+        .arg("--metrics-output")
+        .arg(metrics.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("skipping 4 examples"));
+
+    // Counting continues from the examples learned before the checkpoint.
+    let contents = std::fs::read_to_string(metrics.path())?;
+    let last: serde_json::Value = serde_json::from_str(contents.lines().last().unwrap())?;
+    assert_eq!(last["examples"], 4);
+    assert_eq!(last["Example #"], 3);
+    Ok(())
+}
+
+#[test]
+fn train_errors_stop_parse_threads() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    // Enough examples to fill the queues between the threads.
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str(&"1 | a b c\n0 | a d\n".repeat(2500))?;
+    let bad_data = assert_fs::NamedTempFile::new("bad_data.json")?;
+    bad_data.write_str(
+        &(r#"{"label": 1.0, "features": {"a": ["x"]}}"#.to_owned() + "\n").repeat(100),
+    )?;
+    bad_data.write_str(r#"{"label": 1.0, "features": {"a": [1, "x"]}}"#)?;
+
+    for num_parse_threads in ["0", "2"] {
+        // VW text has no timestamps, so learning fails on the first example.
+        assert_cmd::Command::cargo_bin("reml")?
+            .arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data.path())
+            .arg("--num-parse-threads")
+            .arg(num_parse_threads)
+            .arg("--start-time")
+            .arg("2023-01-01T00:00:00Z")
+            .timeout(std::time::Duration::from_secs(20))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("timestamp"));
+
+        assert_cmd::Command::cargo_bin("reml")?
+            .arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(bad_data.path())
+            .arg("--data-format")
+            .arg("json")
+            .arg("--num-parse-threads")
+            .arg(num_parse_threads)
+            .timeout(std::time::Duration::from_secs(20))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("mixes a number with a string"));
+    }
+    Ok(())
+}
+
+#[test]
+fn train_on_multiple_files_globs_and_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let dir = assert_fs::TempDir::new()?;
+    let day1 = dir.child("day1.txt");
+    day1.write_str("1 | a b c\n0 | a d\n")?;
+    let day2 = dir.child("day2.txt");
+    day2.write_str("1 | b c\n")?;
+    let all = dir.child("all.data");
+    all.write_str("1 | a b c\n0 | a d\n1 | b c\n")?;
+
+    let train = |model: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("reml").unwrap();
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--output-model")
+            .arg(model);
+        cmd
+    };
+    let expected = dir.child("expected.model");
+    train(expected.path())
+        .arg("--data")
+        .arg(all.path())
+        .assert()
+        .success();
+
+    let model = dir.child("files.model");
+    train(model.path())
+        .arg("--data")
+        .arg(day1.path())
+        .arg("--data")
+        .arg(day2.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Reading data file (2/2)"));
+    assert_eq!(
+        std::fs::read(expected.path())?,
+        std::fs::read(model.path())?
+    );
+
+    let model = dir.child("glob.model");
+    train(model.path())
+        .arg("--data")
+        .arg(dir.path().join("day*.txt"))
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(expected.path())?,
+        std::fs::read(model.path())?
+    );
+
+    let model = dir.child("stdin.model");
+    train(model.path())
+        .arg("--data")
+        .arg("-")
+        .stdin(std::fs::File::open(all.path())?)
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(expected.path())?,
+        std::fs::read(model.path())?
+    );
+
+    train(dir.child("none.model").path())
+        .arg("--data")
+        .arg(dir.path().join("missing*.txt"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No data files found matching"));
+    Ok(())
+}
+
+#[test]
+fn train_on_parquet_matches_json() -> Result<(), Box<dyn std::error::Error>> {
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+    use std::sync::Arc;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let json_data = assert_fs::NamedTempFile::new("data.json")?;
+    json_data.write_str(concat!(
+        r#"{"label": 1, "features": {"a": {"x": 0.5, "c": "red"}}}"#,
+        "\n",
+        r#"{"label": 0, "features": {"a": {"x": 2.0, "c": "blue"}}}"#,
+        "\n"
+    ))?;
+
+    let parquet_data = assert_fs::NamedTempFile::new("data.parquet")?;
+    let batch = RecordBatch::try_from_iter([
+        (
+            "x",
+            Arc::new(Float64Array::from(vec![0.5, 2.0])) as ArrayRef,
+        ),
+        (
+            "c",
+            Arc::new(StringArray::from(vec!["red", "blue"])) as ArrayRef,
+        ),
+        (
+            "y",
+            Arc::new(Float64Array::from(vec![1.0, 0.0])) as ArrayRef,
+        ),
+    ])?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(
+        std::fs::File::create(parquet_data.path())?,
+        batch.schema(),
+        None,
+    )?;
+    writer.write(&batch)?;
+    writer.close()?;
+    let mapping = assert_fs::NamedTempFile::new("mapping.json")?;
+    mapping.write_str(r#"{"namespaces": {"a": ["x", "c"]}, "label": "y"}"#)?;
+
+    let json_model = assert_fs::NamedTempFile::new("json.model")?;
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(json_data.path())
+        .arg("--data-format")
+        .arg("json")
+        .arg("--output-model")
+        .arg(json_model.path())
+        .assert()
+        .success();
+
+    let parquet_model = assert_fs::NamedTempFile::new("parquet.model")?;
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(parquet_data.path())
+        .arg("--data-format")
+        .arg("parquet")
+        .arg("--column-mapping")
+        .arg(mapping.path())
+        .arg("--output-model")
+        .arg(parquet_model.path())
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read(json_model.path())?,
+        std::fs::read(parquet_model.path())?
+    );
+
+    // A column mapping is required for columnar data.
+    Command::cargo_bin("reml")?
+        .arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(parquet_data.path())
+        .arg("--data-format")
+        .arg("parquet")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--column-mapping"));
+    Ok(())
+}
+
+#[test]
+fn train_on_csv_matches_json() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let json_data = assert_fs::NamedTempFile::new("data.json")?;
+    json_data.write_str(concat!(
+        r#"{"label": 1, "features": {"a": {"x": 0.5, "c": "red"}}}"#,
+        "\n",
+        r#"{"label": 0, "features": {"a": {"x": 2.0, "c": "blue"}}}"#,
+        "\n"
+    ))?;
+    let csv_data = assert_fs::NamedTempFile::new("data.csv")?;
+    csv_data.write_str("y,x,c\n1,0.5,red\n0,2.0,blue\n")?;
+    let schema = assert_fs::NamedTempFile::new("schema.json")?;
+    schema.write_str(
+        r#"{"label": "y", "namespaces": {"a": {"numeric": ["x"], "categorical": ["c"]}}}"#,
+    )?;
+
+    let train = |data: &std::path::Path, format: &str, model: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("reml").unwrap();
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data)
+            .arg("--data-format")
+            .arg(format)
+            .arg("--csv-schema")
+            .arg(schema.path())
+            .arg("--output-model")
+            .arg(model);
+        cmd
+    };
+    let json_model = assert_fs::NamedTempFile::new("json.model")?;
+    train(json_data.path(), "json", json_model.path())
+        .assert()
+        .success();
+    let csv_model = assert_fs::NamedTempFile::new("csv.model")?;
+    train(csv_data.path(), "csv", csv_model.path())
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(json_model.path())?,
+        std::fs::read(csv_model.path())?
+    );
+    Ok(())
+}
+
+#[test]
+fn train_on_cache_matches_text() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str("1 |a x:0.5 red\n0 |a x:2.0 blue\n1 |b y\n")?;
+    let cache = assert_fs::NamedTempFile::new("data.cache")?;
+
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("create-cache")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--output")
+        .arg(cache.path())
+        .assert()
+        .success();
+    cache.assert(predicate::path::exists());
+
+    let train = |data: &std::path::Path, format: &str, model: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("reml").unwrap();
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data)
+            .arg("--data-format")
+            .arg(format)
+            .arg("--output-model")
+            .arg(model);
+        cmd
+    };
+    let text_model = assert_fs::NamedTempFile::new("text.model")?;
+    train(data.path(), "vw-text", text_model.path())
+        .assert()
+        .success();
+    let cache_model = assert_fs::NamedTempFile::new("cache.model")?;
+    train(cache.path(), "cache", cache_model.path())
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(text_model.path())?,
+        std::fs::read(cache_model.path())?
+    );
+
+    // A cache hashed with a different number of bits can't be used.
+    let other_config = assert_fs::NamedTempFile::new("other.json")?;
+    other_config.write_str(
+        r#"{"globalConfig": {"numBits": 10}, "entryReduction": {"typename": "Coin"}}"#,
+    )?;
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(other_config.path())
+        .arg("--data")
+        .arg(cache.path())
+        .arg("--data-format")
+        .arg("cache")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("num_bits"));
+
+    // Nor can one holding examples of other types.
+    let cb_config = assert_fs::NamedTempFile::new("cb.json")?;
+    cb_config.write_str(
+        r#"{"globalConfig": {}, "entryReduction": {"typename": "CbExploreAdfGreedy"}}"#,
+    )?;
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(cb_config.path())
+        .arg("--data")
+        .arg(cache.path())
+        .arg("--data-format")
+        .arg("cache")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("SparseCBAdf"));
+    Ok(())
+}
+
+#[test]
+fn train_on_compressed_data() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let text = "1 |a x:0.5 red\n0 |a x:2.0 blue\n1 |b y\n";
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str(text)?;
+    let gzip_data = assert_fs::NamedTempFile::new("data.txt.gz")?;
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(gzip_data.path())?,
+        flate2::Compression::default(),
+    );
+    encoder.write_all(text.as_bytes())?;
+    encoder.finish()?;
+    // The extension doesn't matter when reading, only the contents.
+    let zstd_data = assert_fs::NamedTempFile::new("data.txt")?;
+    std::fs::write(zstd_data.path(), zstd::encode_all(text.as_bytes(), 0)?)?;
+
+    let train = |data: &std::path::Path, model: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("reml").unwrap();
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data)
+            .arg("--output-model")
+            .arg(model);
+        cmd
+    };
+    let model = assert_fs::NamedTempFile::new("text.model")?;
+    train(data.path(), model.path()).assert().success();
+    for compressed in [gzip_data.path(), zstd_data.path()] {
+        let compressed_model = assert_fs::NamedTempFile::new("compressed.model")?;
+        train(compressed, compressed_model.path())
+            .assert()
+            .success();
+        assert_eq!(
+            std::fs::read(model.path())?,
+            std::fs::read(compressed_model.path())?
+        );
+    }
+
+    // Predictions are compressed according to their extension.
+    let predictions = assert_fs::NamedTempFile::new("predictions.json.zst")?;
+    train(gzip_data.path(), model.path())
+        .arg("--predictions")
+        .arg(predictions.path())
+        .assert()
+        .success();
+    let predictions = zstd::decode_all(std::fs::File::open(predictions.path())?)?;
+    assert_eq!(String::from_utf8(predictions)?.lines().count(), 3);
+    Ok(())
+}
+
+#[test]
+fn train_writes_tags_and_weights_with_predictions() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str("1 2 'first|a x\n0 |a y\n 'third|b z\n")?;
+    let predictions = assert_fs::NamedTempFile::new("predictions.json")?;
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--predictions")
+        .arg(predictions.path())
+        .assert()
+        .success();
+
+    let predictions = std::fs::read_to_string(predictions.path())?;
+    let records: Vec<serde_json::Value> = predictions
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["tag"], "first");
+    assert_eq!(records[0]["weight"], 2.0);
+    assert!(records[1].get("tag").is_none());
+    assert!(records[1].get("weight").is_none());
+    // Examples without a label are still predicted on.
+    assert_eq!(records[2]["tag"], "third");
+    Ok(())
+}
+
+#[test]
+fn train_dsjson_with_reward_function_and_skip_learn() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(
+        r#"{"globalConfig": {}, "entryReduction": {"typename": "CbExploreAdfGreedy"}}"#,
+    )?;
+    let event = |cost: f32, outcomes: &str, extra: &str| {
+        format!(
+            r#"{{"_label_cost": {}, "_label_probability": 0.5, "_labelIndex": 0, "o": [{}], {} "c": {{"user": {{"name": "Tom"}}, "_multi": [{{"a": {{"x": 1}}}}, {{"a": {{"y": 1}}}}]}}}}"#,
+            cost, outcomes, extra
+        )
+    };
+    // The outcomes of the logged events disagree with _label_cost.
+    let logged = assert_fs::NamedTempFile::new("logged.json")?;
+    logged.write_str(
+        &[
+            event(0.0, r#"{"v": 1}, {"v": 2}"#, ""),
+            event(0.0, r#"{"v": 1}"#, r#""_skipLearn": true,"#),
+            event(0.0, "", ""),
+        ]
+        .join("\n"),
+    )?;
+    let expected = assert_fs::NamedTempFile::new("expected.json")?;
+    expected.write_str(&[event(-3.0, "", ""), event(0.5, "", "")].join("\n"))?;
+
+    let train = |data: &std::path::Path, model: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("reml").unwrap();
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data)
+            .arg("--data-format")
+            .arg("dsjson")
+            .arg("--output-model")
+            .arg(model);
+        cmd
+    };
+    let model = assert_fs::NamedTempFile::new("logged.model")?;
+    let predictions = assert_fs::NamedTempFile::new("predictions.json")?;
+    train(logged.path(), model.path())
+        .arg("--reward-function")
+        .arg("sum")
+        .arg("--default-reward")
+        .arg("-0.5")
+        .arg("--predictions")
+        .arg(predictions.path())
+        .assert()
+        .success();
+    // Events marked with _skipLearn are still predicted on.
+    assert_eq!(
+        std::fs::read_to_string(predictions.path())?.lines().count(),
+        3
+    );
+
+    let expected_model = assert_fs::NamedTempFile::new("expected.model")?;
+    train(expected.path(), expected_model.path())
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(model.path())?,
+        std::fs::read(expected_model.path())?
+    );
+    Ok(())
+}
+
+#[test]
+fn train_dsjson_on_time_window_with_event_ids() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(
+        r#"{"globalConfig": {}, "entryReduction": {"typename": "CbExploreAdfGreedy"}}"#,
+    )?;
+    let event = |id: &str, timestamp: &str, cost: f32| {
+        format!(
+            r#"{{"_label_cost": {}, "_label_probability": 0.5, "_labelIndex": 0, "EventId": "{}", "Timestamp": "{}", "c": {{"user": {{"name": "Tom"}}, "_multi": [{{"a": {{"x": 1}}}}, {{"a": {{"y": 1}}}}]}}}}"#,
+            cost, id, timestamp
+        )
+    };
+    let first = event("event-1", "2023-01-01T10:00:00.0000000Z", 1.0);
+    let second = event("event-2", "2023-01-02T10:00:00.0000000Z", -1.0);
+    let third = event("event-3", "2023-01-03T10:00:00.0000000Z", 0.5);
+    let data = assert_fs::NamedTempFile::new("data.json")?;
+    data.write_str(&[first, second.clone(), third].join("\n"))?;
+    let expected = assert_fs::NamedTempFile::new("expected.json")?;
+    expected.write_str(&second)?;
+
+    let train = |data: &std::path::Path, model: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("reml").unwrap();
+        cmd.arg("--quiet")
+            .arg("train")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data)
+            .arg("--data-format")
+            .arg("dsjson")
+            .arg("--output-model")
+            .arg(model);
+        cmd
+    };
+    let model = assert_fs::NamedTempFile::new("window.model")?;
+    let predictions = assert_fs::NamedTempFile::new("predictions.json")?;
+    train(data.path(), model.path())
+        .arg("--start-time")
+        .arg("2023-01-02T00:00:00Z")
+        .arg("--end-time")
+        .arg("2023-01-03T10:00:00Z")
+        .arg("--predictions")
+        .arg(predictions.path())
+        .assert()
+        .success();
+
+    // Only the event inside the window is predicted on and learned from.
+    let predictions = std::fs::read_to_string(predictions.path())?;
+    let records: Vec<serde_json::Value> = predictions
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["id"], "event-2");
+    assert_eq!(records[0]["timestamp"], "2023-01-02T10:00:00Z");
+    assert!(!records[0]["prediction"].is_null());
+
+    let expected_model = assert_fs::NamedTempFile::new("expected.model")?;
+    train(expected.path(), expected_model.path())
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read(model.path())?,
+        std::fs::read(expected_model.path())?
+    );
+
+    // Caches keep the IDs and timestamps, so training on one is the same.
+    let cache = assert_fs::NamedTempFile::new("data.cache")?;
+    Command::cargo_bin("reml")?
+        .arg("create-cache")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--data-format")
+        .arg("dsjson")
+        .arg("--output")
+        .arg(cache.path())
+        .assert()
+        .success();
+    let cache_model = assert_fs::NamedTempFile::new("cache.model")?;
+    let cache_predictions = assert_fs::NamedTempFile::new("cache_predictions.json")?;
+    Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("train")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(cache.path())
+        .arg("--data-format")
+        .arg("cache")
+        .arg("--output-model")
+        .arg(cache_model.path())
+        .arg("--start-time")
+        .arg("2023-01-02T00:00:00Z")
+        .arg("--end-time")
+        .arg("2023-01-03T10:00:00Z")
+        .arg("--predictions")
+        .arg(cache_predictions.path())
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(cache_predictions.path())?,
+        predictions
+    );
+    assert_eq!(
+        std::fs::read(cache_model.path())?,
+        std::fs::read(model.path())?
+    );
+
+    // A window can't be applied to events without a timestamp.
+    let untimed = assert_fs::NamedTempFile::new("untimed.json")?;
+    untimed.write_str(
+        r#"{"_label_cost": 1.0, "_label_probability": 0.5, "_labelIndex": 0, "c": {"_multi": [{"a": {"x": 1}}]}}"#,
+    )?;
+    train(untimed.path(), model.path())
+        .arg("--start-time")
+        .arg("2023-01-02T00:00:00Z")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("timestamp"));
+    Ok(())
+}
+
+/// Sends each line to a server and returns the response lines.
+fn serve_requests(
+    stream: impl std::io::Read + std::io::Write,
+    lines: &[&str],
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    use std::io::{BufRead, BufReader};
+
+    let mut reader = BufReader::new(stream);
+    let mut responses = Vec::new();
+    for line in lines {
+        writeln!(reader.get_mut(), "{}", line)?;
+        let mut response = String::new();
+        reader.read_line(&mut response)?;
+        responses.push(response.trim().to_owned());
+    }
+    Ok(responses)
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_predicts_learns_and_saves() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+    let dir = assert_fs::TempDir::new()?;
+    let socket_path = dir.path().join("reml.sock");
+    let models = dir.child("models");
+    models.create_dir_all()?;
+
+    // A socket left behind by a previous server is replaced.
+    drop(UnixListener::bind(&socket_path)?);
+
+    let mut child = Command::cargo_bin("reml")?
+        .arg("--quiet")
+        .arg("serve")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--unix-socket")
+        .arg(&socket_path)
+        .arg("--model-dir")
+        .arg(models.path())
+        .arg("--output-model")
+        .arg(models.child("default.bin").path())
+        .spawn()?;
+
+    let mut attempts = 0;
+    let stream = loop {
+        match UnixStream::connect(&socket_path) {
+            Ok(stream) => break stream,
+            Err(_) if attempts < 100 => {
+                attempts += 1;
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            Err(err) => {
+                child.kill()?;
+                return Err(err.into());
+            }
+        }
+    };
+
+    let outside = dir.child("outside.bin");
+    let responses = serve_requests(
+        stream,
+        &[
+            "1 | a b c",
+            "| a b c",
+            "!save my model.bin",
+            "!save",
+            "!reload my model.bin",
+            "!save ../outside.bin",
+            &format!("!save {}", outside.path().display()),
+            "!unknown",
+        ],
+    );
+    child.kill()?;
+    child.wait()?;
+
+    let responses = responses?;
+    assert!(responses[0].contains("prediction"));
+    assert!(responses[1].contains("prediction"));
+    for response in &responses[2..5] {
+        assert_eq!(response, r#"{"status":"ok"}"#);
+    }
+    for response in &responses[5..] {
+        assert!(response.contains("error"), "{}", response);
+    }
+    models
+        .child("my model.bin")
+        .assert(predicate::path::is_file());
+    models
+        .child("default.bin")
+        .assert(predicate::path::is_file());
+    outside.assert(predicate::path::missing());
+    Ok(())
+}
+
+#[test]
+fn serve_listens_on_tcp() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpStream;
+    use std::process::Stdio;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+
+    // Port 0 picks a free port, which is reported when listening starts.
+    let mut child = Command::cargo_bin("reml")?
+        .arg("serve")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--port")
+        .arg("0")
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    let port = loop {
+        line.clear();
+        if stderr.read_line(&mut line)? == 0 {
+            child.kill()?;
+            return Err("Server exited before listening".into());
+        }
+        if let Some((_, rest)) = line.split_once("127.0.0.1:") {
+            let port: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            break port.parse::<u16>()?;
+        }
+    };
+
+    let responses = TcpStream::connect(("127.0.0.1", port))
+        .map_err(Into::into)
+        .and_then(|stream| {
+            serve_requests(
+                stream,
+                &["1 | a b c", "| a b c", "!save model.bin", "!reload"],
+            )
+        });
+    child.kill()?;
+    child.wait()?;
+
+    let responses = responses?;
+    assert!(responses[0].contains("prediction"));
+    assert!(responses[1].contains("prediction"));
+    // Paths can only be given with --model-dir.
+    assert!(responses[2].contains("--model-dir"));
+    assert!(responses[3].contains("--input-model"));
+    Ok(())
+}
+
+#[test]
+fn serve_rejects_cache_format() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(r#"{"globalConfig": {}, "entryReduction": {"typename": "Coin"}}"#)?;
+
+    Command::cargo_bin("reml")?
+        .arg("serve")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data-format")
+        .arg("cache")
+        .arg("--port")
+        .arg("0")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("only be used for training"));
+    Ok(())
+}
+
+#[test]
+fn hash_collisions_reports_namespaces_and_interactions() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("reml")?;
+
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(
+        r#"{"globalConfig": {"interactions": [[{"Name": "a"}, {"Name": "b"}]]}, "entryReduction": {"typename": "Coin"}}"#,
+    )?;
+    let data = assert_fs::NamedTempFile::new("data.txt")?;
+    data.write_str("1 |a x y z |b w\n0 |a x |b v\n")?;
+
+    cmd.arg("hash-collisions")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--num-bits")
+        .arg("1,20");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("numBits = 1"))
+        .stdout(predicate::str::contains("numBits = 20"))
+        .stdout(predicate::str::is_match(r"a\*b +4 ")?)
+        .stdout(predicate::str::is_match(r"All +9 ")?);
+    Ok(())
+}
+
+#[test]
+fn hash_collisions_reads_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(
+        r#"{"globalConfig": {"interactions": [[{"Name": "a"}, {"Name": "b"}]]}, "entryReduction": {"typename": "Coin"}}"#,
+    )?;
+    let data = assert_fs::NamedTempFile::new("data.csv")?;
+    data.write_str("y,p,q,r\n1,x,y,w\n0,x,,v\n")?;
+    let schema = assert_fs::NamedTempFile::new("schema.json")?;
+    schema.write_str(
+        r#"{"label": "y", "namespaces": {"a": {"categorical": ["p", "q"]}, "b": {"categorical": ["r"]}}}"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("reml")?;
+    cmd.arg("hash-collisions")
+        .arg("--config")
+        .arg(config.path())
+        .arg("--data")
+        .arg(data.path())
+        .arg("--data-format")
+        .arg("csv")
+        .arg("--csv-schema")
+        .arg(schema.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(r"a\*b +3 ")?)
+        .stdout(predicate::str::is_match(r"All +7 ")?);
+    Ok(())
+}
+
+#[test]
+fn hash_collisions_analyzes_each_action_separately() -> Result<(), Box<dyn std::error::Error>> {
+    let config = assert_fs::NamedTempFile::new("config.json")?;
+    config.write_str(
+        r#"{"globalConfig": {"interactions": [[{"Name": "s"}, {"Name": "a"}], [{"Name": "a"}, {"Name": "b"}]]}, "entryReduction": {"typename": "CbExploreAdfGreedy"}}"#,
+    )?;
+    // Each action is paired with the shared features, but the features of
+    // different actions are never interacted.
+    let dsjson = assert_fs::NamedTempFile::new("data.json")?;
+    dsjson.write_str(
+        r#"{"c": {"s": {"u": 1}, "_multi": [{"a": {"x": 1}}, {"a": {"y": 1}}, {"b": {"z": 1}}]}}"#,
+    )?;
+    let json = assert_fs::NamedTempFile::new("data.json")?;
+    json.write_str(
+        r#"{"shared": {"s": ["u"]}, "actions": [{"a": ["x"]}, {"a": ["y"]}, {"b": ["z"]}]}"#,
+    )?;
+
+    for (data, data_format) in [(&dsjson, "dsjson"), (&json, "json")] {
+        let mut cmd = Command::cargo_bin("reml")?;
+        cmd.arg("hash-collisions")
+            .arg("--config")
+            .arg(config.path())
+            .arg("--data")
+            .arg(data.path())
+            .arg("--data-format")
+            .arg(data_format);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::is_match(r"s\*a +2 ")?)
+            .stdout(predicate::str::is_match(r"a\*b +0 ")?)
+            .stdout(predicate::str::contains("Default").not())
+            .stdout(predicate::str::is_match(r"All +6 ")?);
+    }
+    Ok(())
+}
+
+#[test]
+fn import_vw_readable_model() -> Result<(), Box<dyn std::error::Error>> {
+    let vw_model = assert_fs::NamedTempFile::new("model.txt")?;
+    vw_model.write_str(
+        "Version 9.8.0\nId \nMin label:-1\nMax label:2\nbits:18\nlda:0\n0 ngram:\n0 skip:\noptions: --cb_explore_adf --cb_type mtr --csoaa_ldf multiline --csoaa_rank --epsilon 0.1\nChecksum: 1234\nevent_sum 7\naction_sum 21\n:0\n116060:0.25\n203039:0.5\n",
+    )?;
+    let output = assert_fs::NamedTempFile::new("model.bin")?;
+
+    let mut cmd = Command::cargo_bin("reml")?;
+    cmd.arg("import-vw-model")
+        .arg(vw_model.path())
+        .arg("--output-model")
+        .arg(output.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Imported 2 weights"));
+    output.assert(predicate::path::exists());
+
+    let mut cmd = Command::cargo_bin("reml")?;
+    cmd.arg("export-model").arg(output.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("CbExploreAdfGreedy"))
+        .stdout(predicate::str::contains("Murmur3"));
+    Ok(())
+}
+
+#[test]
+fn import_vw_binary_model() -> Result<(), Box<dyn std::error::Error>> {
+    let vw_model = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../reductionml-core/tests/data/vw_model/cb_explore_adf_squarecb.model");
+    let output = assert_fs::NamedTempFile::new("model.bin")?;
+
+    let mut cmd = Command::cargo_bin("reml")?;
+    cmd.arg("import-vw-model")
+        .arg(vw_model)
+        .arg("--output-model")
+        .arg(output.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Imported 2 weights"));
+
+    let mut cmd = Command::cargo_bin("reml")?;
+    cmd.arg("export-model").arg(output.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("CbExploreAdfSquareCb"))
+        .stdout(predicate::str::contains("Prefix"));
+    Ok(())
+}
@@ -30,7 +30,7 @@ pub fn learn_coin_no_interactions_rcv1(c: &mut Criterion) {
     ];
     c.bench_function("learn_coin_no_interactions_rcv1", |b| {
         b.iter(|| {
-            for (feats, label) in inputs.iter_mut() {
+            for (feats, label, _) in inputs.iter_mut() {
                 workspace.learn(feats, label.as_ref().unwrap());
             }
         })
@@ -63,7 +63,7 @@ pub fn learn_coin_interactions_rcv1(c: &mut Criterion) {
     ];
     c.bench_function("learn_coin_interactions_rcv1", |b| {
         b.iter(|| {
-            for (feats, label) in inputs.iter_mut() {
+            for (feats, label, _) in inputs.iter_mut() {
                 workspace.learn(feats, label.as_ref().unwrap());
             }
         })
@@ -0,0 +1,177 @@
+//! Transparent gzip and zstd compression of data files.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression of a stream from the magic bytes at its start.
+    pub fn detect(header: &[u8]) -> Compression {
+        if header.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Chooses the compression of an output file from its extension, `.gz` or
+    /// `.zst`.
+    pub fn from_path(path: &Path) -> Compression {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Wraps a reader so that gzip and zstd compressed input is decompressed.
+/// Anything else is passed through unchanged.
+pub fn decompress_reader<R: Read + Send + 'static>(
+    mut reader: R,
+) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut header = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut reader)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut header)?;
+    let compression = Compression::detect(&header);
+    let reader = io::Cursor::new(header).chain(reader);
+    Ok(match compression {
+        Compression::None => Box::new(BufReader::new(reader)),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::new(reader)?)),
+    })
+}
+
+/// Opens a data file for reading, decompressing it if needed.
+pub fn open_data_file<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead + Send>> {
+    decompress_reader(File::open(path)?)
+}
+
+/// Creates a data file for writing, compressed according to its extension.
+/// [`CompressedWriter::finish`] must be called once everything is written.
+pub fn create_data_file<P: AsRef<Path>>(path: P) -> io::Result<CompressedWriter<BufWriter<File>>> {
+    let compression = Compression::from_path(path.as_ref());
+    CompressedWriter::new(BufWriter::new(File::create(path)?), compression)
+}
+
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(inner: W, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => CompressedWriter::Plain(inner),
+            Compression::Gzip => {
+                CompressedWriter::Gzip(GzEncoder::new(inner, flate2::Compression::default()))
+            }
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(inner, 0)?),
+        })
+    }
+
+    /// Writes the end of the compressed stream and flushes the underlying
+    /// writer.
+    pub fn finish(self) -> io::Result<W> {
+        let mut inner = match self {
+            CompressedWriter::Plain(inner) => inner,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        inner.flush()?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(inner) => inner.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(compression: Compression) -> Vec<u8> {
+        let mut writer = CompressedWriter::new(Vec::new(), compression).unwrap();
+        writer.write_all(b"1 |a x\n0 |b y\n").unwrap();
+        let compressed = writer.finish().unwrap();
+        assert_eq!(Compression::detect(&compressed), compression);
+
+        let mut reader = decompress_reader(io::Cursor::new(compressed)).unwrap();
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            assert_eq!(round_trip(compression), b"1 |a x\n0 |b y\n");
+        }
+    }
+
+    #[test]
+    fn decompress_short_and_empty_input() {
+        let mut output = String::new();
+        decompress_reader(io::Cursor::new(b"1".to_vec()))
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "1");
+
+        output.clear();
+        decompress_reader(io::empty())
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn compression_from_path() {
+        assert_eq!(
+            Compression::from_path(Path::new("data.json.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("data.zst")),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("data.txt")),
+            Compression::None
+        );
+    }
+}
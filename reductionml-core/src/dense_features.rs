@@ -0,0 +1,146 @@
+use approx::AbsDiffEq;
+
+use crate::sparse_namespaced_features::Namespace;
+
+/// Features given as a contiguous block of values, such as an embedding.
+///
+/// The values are split into namespaces, each a slice of the block. Value `i`
+/// of a namespace uses the weight at the namespace hash plus `i`, so there is
+/// no hashing per feature. This is the same index a JSON array of numbers in
+/// that namespace is given.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct DenseFeatures {
+    values: Vec<f32>,
+    /// Namespace and the end of its values, in the order they were added.
+    namespaces: Vec<(Namespace, usize)>,
+}
+
+impl AbsDiffEq for DenseFeatures {
+    type Epsilon = f32;
+
+    fn default_epsilon() -> Self::Epsilon {
+        f32::EPSILON
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        self.namespaces == other.namespaces
+            && self.values.len() == other.values.len()
+            && self
+                .values
+                .iter()
+                .zip(other.values.iter())
+                .all(|(a, b)| a.abs_diff_eq(b, epsilon))
+    }
+}
+
+impl DenseFeatures {
+    pub fn new() -> DenseFeatures {
+        DenseFeatures::default()
+    }
+
+    /// All values are placed in the default namespace.
+    pub fn from_values(values: Vec<f32>) -> DenseFeatures {
+        let len = values.len();
+        DenseFeatures {
+            values,
+            namespaces: vec![(Namespace::Default, len)],
+        }
+    }
+
+    /// Appends the values of a namespace. Each namespace should only be added
+    /// once.
+    pub fn add_namespace(&mut self, namespace: Namespace, values: &[f32]) {
+        self.add_namespace_with_iter(namespace, values.iter().copied());
+    }
+
+    pub fn add_namespace_with_iter<T>(&mut self, namespace: Namespace, values: T)
+    where
+        T: IntoIterator<Item = f32>,
+    {
+        debug_assert!(self.get_namespace(namespace).is_none());
+        self.values.extend(values);
+        self.namespaces.push((namespace, self.values.len()));
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn namespaces(&self) -> impl Iterator<Item = (Namespace, &[f32])> + '_ {
+        let starts = std::iter::once(0).chain(self.namespaces.iter().map(|(_, end)| *end));
+        self.namespaces
+            .iter()
+            .zip(starts)
+            .map(|((namespace, end), start)| (*namespace, &self.values[start..*end]))
+    }
+
+    pub fn get_namespace(&self, namespace: Namespace) -> Option<&[f32]> {
+        self.namespaces()
+            .find(|(ns, _)| *ns == namespace)
+            .map(|(_, values)| values)
+    }
+
+    pub fn get_namespace_mut(&mut self, namespace: Namespace) -> Option<&mut [f32]> {
+        let position = self
+            .namespaces
+            .iter()
+            .position(|(ns, _)| *ns == namespace)?;
+        let start = match position {
+            0 => 0,
+            _ => self.namespaces[position - 1].1,
+        };
+        let end = self.namespaces[position].1;
+        Some(&mut self.values[start..end])
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.namespaces.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashFunction;
+
+    #[test]
+    fn namespaces_are_slices_of_values() {
+        let a = Namespace::from_name("a", 0, HashFunction::default());
+        let b = Namespace::from_name("b", 0, HashFunction::default());
+        let mut features = DenseFeatures::new();
+        features.add_namespace(a, &[1.0, 2.0]);
+        features.add_namespace_with_iter(b, [3.0, 4.0, 5.0]);
+
+        assert_eq!(features.len(), 5);
+        assert_eq!(features.values(), &[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let namespaces: Vec<(Namespace, &[f32])> = features.namespaces().collect();
+        assert_eq!(
+            namespaces,
+            vec![(a, &[1.0, 2.0][..]), (b, &[3.0, 4.0, 5.0][..])]
+        );
+        assert_eq!(features.get_namespace(b), Some(&[3.0, 4.0, 5.0][..]));
+        assert_eq!(features.get_namespace(Namespace::Default), None);
+
+        features.get_namespace_mut(b).unwrap()[0] = 6.0;
+        assert_eq!(features.values(), &[1.0, 2.0, 6.0, 4.0, 5.0]);
+
+        features.clear();
+        assert!(features.is_empty());
+        assert_eq!(features.namespaces().count(), 0);
+
+        let features = DenseFeatures::from_values(vec![1.0, 2.0]);
+        assert_eq!(
+            features.get_namespace(Namespace::Default),
+            Some(&[1.0, 2.0][..])
+        );
+    }
+}
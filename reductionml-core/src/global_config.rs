@@ -3,7 +3,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_default::DefaultFromSerde;
 
-use crate::interactions::Interaction;
+use crate::hash::HashFunction;
+use crate::interactions::{Interaction, InteractionMode};
 
 #[derive(Clone, Serialize, Deserialize, Debug, JsonSchema, DefaultFromSerde, Builder)]
 #[serde(rename_all = "camelCase")]
@@ -15,11 +16,17 @@ pub struct GlobalConfig {
     #[serde(default)]
     hash_seed: u32,
 
+    #[serde(default)]
+    hash_function: HashFunction,
+
     #[serde(default = "default_true")]
     constant_feature_enabled: bool,
 
     #[serde(default)]
     interactions: Vec<Interaction>,
+
+    #[serde(default)]
+    interaction_mode: InteractionMode,
 }
 
 fn default_num_bits() -> u8 {
@@ -40,8 +47,10 @@ impl GlobalConfig {
         GlobalConfig {
             num_bits,
             hash_seed,
+            hash_function: HashFunction::default(),
             constant_feature_enabled,
             interactions: interactions.to_vec(),
+            interaction_mode: InteractionMode::default(),
         }
     }
 
@@ -53,6 +62,14 @@ impl GlobalConfig {
         self.hash_seed
     }
 
+    pub fn hash_function(&self) -> HashFunction {
+        self.hash_function
+    }
+
+    pub fn set_hash_function(&mut self, hash_function: HashFunction) {
+        self.hash_function = hash_function;
+    }
+
     pub fn constant_feature_enabled(&self) -> bool {
         self.constant_feature_enabled
     }
@@ -64,4 +81,12 @@ impl GlobalConfig {
     pub fn set_interactions(&mut self, interactions: &[Interaction]) {
         self.interactions = interactions.to_vec();
     }
+
+    pub fn interaction_mode(&self) -> InteractionMode {
+        self.interaction_mode
+    }
+
+    pub fn set_interaction_mode(&mut self, interaction_mode: InteractionMode) {
+        self.interaction_mode = interaction_mode;
+    }
 }
@@ -1,289 +1,207 @@
-#[inline(always)]
-pub fn hash_bytes(key: &[u8], seed: u32) -> u32 {
-    // murmurhash3_32(key, seed)
-    twox_hash::xxh3::hash64_with_seed(key, seed as u64) as u32
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Hash function used for namespace and feature names. This is saved with the
+/// model so that features always hash to the same indices.
+#[derive(Serialize, Deserialize, Clone, Copy, JsonSchema, Debug, Default, PartialEq, Eq)]
+pub enum HashFunction {
+    #[default]
+    Xxh3,
+    /// 32 bit MurmurHash3, which is what VW uses. Feature indices are the
+    /// same as VW's for the same hash seed.
+    Murmur3,
 }
 
-// fn fmix(mut h: u32) -> u32 {
-//     h ^= h >> 16;
-//     h = h.wrapping_mul(0x85eb_ca6b);
-//     h ^= h >> 13;
-//     h = h.wrapping_mul(0xc2b2_ae35);
-//     h ^= h >> 16;
-//     h
-// }
-
-// #[allow(arithmetic_overflow)]
-// fn murmurhash3_32(key: &[u8], seed: u32) -> u32 {
-//     let num_blocks = key.len() / 4;
-//     let mut h1 = seed;
-
-//     const CONSTANT1: u32 = 0xcc9e_2d51;
-//     const CONSTANT2: u32 = 0x1b87_3593;
-
-//     // Body
-//     for i in 0..num_blocks {
-//         let mut b = Cursor::new(&key[(i * 4)..(i * 4) + 4]);
-//         let mut current_block = b.read_u32::<LittleEndian>().unwrap();
-
-//         current_block = current_block.wrapping_mul(CONSTANT1);
-//         current_block = current_block.rotate_left(15);
-//         current_block = current_block.wrapping_mul(CONSTANT2);
-
-//         h1 ^= current_block;
-//         h1 = h1.rotate_left(13);
-//         h1 = h1.wrapping_mul(5).wrapping_add(0xe654_6b64);
-//     }
-
-//     // Tail
-//     let mut k1: u32 = 0;
-//     let tail = &key[num_blocks * 4..];
-
-//     if !tail.is_empty() {
-//         if tail.len() >= 3 {
-//             k1 ^= u32::from(tail[2]).wrapping_shl(16);
-//         }
-
-//         if tail.len() >= 2 {
-//             k1 ^= u32::from(tail[1]).wrapping_shl(8);
-//         }
+impl HashFunction {
+    #[inline(always)]
+    pub fn hash_bytes(&self, key: &[u8], seed: u32) -> u32 {
+        match self {
+            HashFunction::Xxh3 => twox_hash::xxh3::hash64_with_seed(key, seed as u64) as u32,
+            HashFunction::Murmur3 => murmurhash3_32(key, seed),
+        }
+    }
+}
 
-//         k1 ^= u32::from(tail[0]);
-//         k1 = k1.wrapping_mul(CONSTANT1);
-//         k1 = k1.rotate_left(15);
-//         k1 = k1.wrapping_mul(CONSTANT2);
-//         h1 ^= k1;
-//     }
+fn fmix(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
 
-//     // Finalization
-//     h1 ^= key.len() as u32;
-//     fmix(h1)
-// }
+fn murmurhash3_32(key: &[u8], seed: u32) -> u32 {
+    const CONSTANT1: u32 = 0xcc9e_2d51;
+    const CONSTANT2: u32 = 0x1b87_3593;
+
+    let mut h1 = seed;
+
+    // Body
+    let mut blocks = key.chunks_exact(4);
+    for block in &mut blocks {
+        let mut current_block = u32::from_le_bytes(block.try_into().unwrap());
+
+        current_block = current_block.wrapping_mul(CONSTANT1);
+        current_block = current_block.rotate_left(15);
+        current_block = current_block.wrapping_mul(CONSTANT2);
+
+        h1 ^= current_block;
+        h1 = h1.rotate_left(13);
+        h1 = h1.wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    // Tail
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k1: u32 = 0;
+        if tail.len() >= 3 {
+            k1 ^= u32::from(tail[2]) << 16;
+        }
+
+        if tail.len() >= 2 {
+            k1 ^= u32::from(tail[1]) << 8;
+        }
+
+        k1 ^= u32::from(tail[0]);
+        k1 = k1.wrapping_mul(CONSTANT1);
+        k1 = k1.rotate_left(15);
+        k1 = k1.wrapping_mul(CONSTANT2);
+        h1 ^= k1;
+    }
+
+    // Finalization
+    h1 ^= key.len() as u32;
+    fmix(h1)
+}
 
 pub(crate) const FNV_PRIME: u32 = 16777619;
 
-// // Test truth values calculated using C++ implementation.
-// #[test]
-// fn fmix_tests() {
-//     assert_eq!(fmix(0), 0);
-//     assert_eq!(fmix(1), 1364076727);
-//     assert_eq!(fmix(5), 3423425485);
-//     assert_eq!(fmix(2147483647), 4190899880);
-//     assert_eq!(fmix(4294967295), 2180083513);
-// }
-
-// #[test]
-// fn hash_tests_zero_seed() {
-//     assert_eq!(murmurhash3_32(b"t", 0), 3397902157);
-//     assert_eq!(murmurhash3_32(b"te", 0), 3988319771);
-//     assert_eq!(murmurhash3_32(b"tes", 0), 196677210);
-//     assert_eq!(murmurhash3_32(b"test", 0), 3127628307);
-//     assert_eq!(murmurhash3_32(b"tested", 0), 2247989476);
-//     assert_eq!(
-//         murmurhash3_32(b"8hv20cjwicnsj vw m000'.'.][][]...!!@3", 0),
-//         4212741639
-//     );
-// }
-
-// #[test]
-// fn hash_tests_nonzero_seed() {
-//     assert_eq!(murmurhash3_32(b"t", 25436347), 960607349);
-//     assert_eq!(murmurhash3_32(b"te", 25436347), 2834341637);
-//     assert_eq!(murmurhash3_32(b"tes", 25436347), 1163171263);
-//     assert_eq!(murmurhash3_32(b"tested", 25436347), 3592599130);
-//     assert_eq!(
-//         murmurhash3_32(b"8hv20cjwicnsj vw m000'.'.][][]...!!@3", 25436347),
-//         2503360452
-//     );
-// }
-
-// #[test]
-// fn hash_feature_tests() {
-//     // Hashes calculated using VW CLI
-//     assert_eq!(
-//         hash_feature(
-//             &features::Feature::Simple {
-//                 namespace: "myNamespace".to_string(),
-//                 name: "feature".to_string()
-//             },
-//             0
-//         ),
-//         1717770527
-//     );
-//     assert_eq!(
-//         hash_feature(
-//             &features::Feature::Simple {
-//                 namespace: "a".to_string(),
-//                 name: "a1".to_string()
-//             },
-//             0
-//         ),
-//         2579875658
-//     );
-//     assert_eq!(
-//         hash_feature(
-//             &features::Feature::SimpleWithStringValue {
-//                 namespace: "myNamespace".to_string(),
-//                 name: "feature".to_string(),
-//                 value: "value".to_string()
-//             },
-//             0
-//         ),
-//         3812705603
-//     );
-//     assert_eq!(
-//         hash_feature(
-//             &features::Feature::Anonymous {
-//                 namespace: "anon".to_string(),
-//                 offset: 0
-//             },
-//             0
-//         ),
-//         659962185
-//     );
-//     assert_eq!(
-//         hash_feature(
-//             &features::Feature::Anonymous {
-//                 namespace: "anon".to_string(),
-//                 offset: 1
-//             },
-//             0
-//         ),
-//         659962186
-//     );
-// }
-
-// #[test]
-// fn hash_feature_with_bit_mask_tests() {
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::Simple {
-//                     namespace: "myNamespace".to_string(),
-//                     name: "feature".to_string()
-//                 },
-//                 0
-//             ),
-//             bit_mask(18)
-//         ),
-//         203039
-//     );
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::Simple {
-//                     namespace: "myNamespace".to_string(),
-//                     name: "feature".to_string()
-//                 },
-//                 0
-//             ),
-//             bit_mask(5)
-//         ),
-//         31
-//     );
-
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::SimpleWithStringValue {
-//                     namespace: "myNamespace".to_string(),
-//                     name: "feature".to_string(),
-//                     value: "value".to_string()
-//                 },
-//                 0
-//             ),
-//             bit_mask(18)
-//         ),
-//         83267
-//     );
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::SimpleWithStringValue {
-//                     namespace: "myNamespace".to_string(),
-//                     name: "feature".to_string(),
-//                     value: "value".to_string()
-//                 },
-//                 0
-//             ),
-//             bit_mask(5)
-//         ),
-//         3
-//     );
-
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::Anonymous {
-//                     namespace: "anon".to_string(),
-//                     offset: 0
-//                 },
-//                 0
-//             ),
-//             bit_mask(18)
-//         ),
-//         145737
-//     );
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::Anonymous {
-//                     namespace: "anon".to_string(),
-//                     offset: 1
-//                 },
-//                 0
-//             ),
-//             bit_mask(18)
-//         ),
-//         145738
-//     );
-
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::Anonymous {
-//                     namespace: "anon".to_string(),
-//                     offset: 0
-//                 },
-//                 0
-//             ),
-//             bit_mask(5)
-//         ),
-//         9
-//     );
-//     assert_eq!(
-//         mask_hash(
-//             hash_feature(
-//                 &features::Feature::Anonymous {
-//                     namespace: "anon".to_string(),
-//                     offset: 1
-//                 },
-//                 0
-//             ),
-//             bit_mask(5)
-//         ),
-//         10
-//     );
-// }
-
-// #[test]
-// fn hash_interactions() {
-//     assert_eq!(
-//         hash_feature(
-//             &features::Feature::Interacted {
-//                 terms: vec![
-//                     features::Feature::Simple {
-//                         namespace: "a".to_string(),
-//                         name: "a1".to_string()
-//                     },
-//                     features::Feature::Simple {
-//                         namespace: "b".to_string(),
-//                         name: "b1".to_string()
-//                     }
-//                 ]
-//             },
-//             0
-//         ),
-//         1046402606
-//     );
-// }
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        inverse_hash_table::Feature,
+        object_pool::Pool,
+        parsers::{TextModeParser, TextModeParserFactory, VwTextParserFactory},
+        sparse_namespaced_features::{Namespace, SparseFeatures},
+        utils::AsInner,
+        FeatureIndex, FeatureMask, FeaturesType, LabelType,
+    };
+
+    fn simple(namespace: &str, name: &str) -> Feature {
+        Feature::Simple {
+            namespace: namespace.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    fn vw_hash(feature: &Feature) -> u32 {
+        u32::from(feature.hash(0, HashFunction::Murmur3))
+    }
+
+    fn vw_index(feature: &Feature, num_bits: u8) -> u32 {
+        u32::from(
+            feature
+                .hash(0, HashFunction::Murmur3)
+                .mask(FeatureMask::from_num_bits(num_bits)),
+        )
+    }
+
+    // Test truth values calculated using C++ implementation.
+    #[test]
+    fn fmix_tests() {
+        assert_eq!(fmix(0), 0);
+        assert_eq!(fmix(1), 1364076727);
+        assert_eq!(fmix(5), 3423425485);
+        assert_eq!(fmix(2147483647), 4190899880);
+        assert_eq!(fmix(4294967295), 2180083513);
+    }
+
+    #[test]
+    fn murmur3_zero_seed() {
+        assert_eq!(murmurhash3_32(b"t", 0), 3397902157);
+        assert_eq!(murmurhash3_32(b"te", 0), 3988319771);
+        assert_eq!(murmurhash3_32(b"tes", 0), 196677210);
+        assert_eq!(murmurhash3_32(b"test", 0), 3127628307);
+        assert_eq!(murmurhash3_32(b"tested", 0), 2247989476);
+        assert_eq!(
+            murmurhash3_32(b"8hv20cjwicnsj vw m000'.'.][][]...!!@3", 0),
+            4212741639
+        );
+    }
+
+    #[test]
+    fn murmur3_nonzero_seed() {
+        assert_eq!(murmurhash3_32(b"t", 25436347), 960607349);
+        assert_eq!(murmurhash3_32(b"te", 25436347), 2834341637);
+        assert_eq!(murmurhash3_32(b"tes", 25436347), 1163171263);
+        assert_eq!(murmurhash3_32(b"tested", 25436347), 3592599130);
+        assert_eq!(
+            murmurhash3_32(b"8hv20cjwicnsj vw m000'.'.][][]...!!@3", 25436347),
+            2503360452
+        );
+    }
+
+    // Hashes calculated using VW CLI
+    #[test]
+    fn murmur3_feature_hashes_match_vw() {
+        let with_value = Feature::SimpleWithStringValue {
+            namespace: "myNamespace".to_owned(),
+            name: "feature".to_owned(),
+            value: "value".to_owned(),
+        };
+        let anonymous = |offset| Feature::Anonymous {
+            namespace: "anon".to_owned(),
+            offset,
+        };
+
+        assert_eq!(vw_hash(&simple("myNamespace", "feature")), 1717770527);
+        assert_eq!(vw_hash(&simple("a", "a1")), 2579875658);
+        assert_eq!(vw_hash(&with_value), 3812705603);
+        assert_eq!(vw_hash(&anonymous(0)), 659962185);
+        assert_eq!(vw_hash(&anonymous(1)), 659962186);
+
+        assert_eq!(vw_index(&simple("myNamespace", "feature"), 18), 203039);
+        assert_eq!(vw_index(&simple("myNamespace", "feature"), 5), 31);
+        assert_eq!(vw_index(&with_value, 18), 83267);
+        assert_eq!(vw_index(&with_value, 5), 3);
+        assert_eq!(vw_index(&anonymous(0), 18), 145737);
+        assert_eq!(vw_index(&anonymous(1), 18), 145738);
+        assert_eq!(vw_index(&anonymous(0), 5), 9);
+        assert_eq!(vw_index(&anonymous(1), 5), 10);
+    }
+
+    #[test]
+    fn murmur3_interaction_hash_matches_vw() {
+        let interacted = Feature::Interacted {
+            terms: vec![simple("a", "a1"), simple("b", "b1")],
+        };
+        assert_eq!(vw_hash(&interacted), 1046402606);
+    }
+
+    #[test]
+    fn murmur3_parsed_features_match_vw() {
+        let parser = VwTextParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::Murmur3,
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (features, _, _) = parser.parse_chunk("1 |myNamespace feature").unwrap();
+        let features: &SparseFeatures = features.as_inner().unwrap();
+        let namespace = features
+            .get_namespace(Namespace::from_name(
+                "myNamespace",
+                0,
+                HashFunction::Murmur3,
+            ))
+            .unwrap();
+        let indices: Vec<FeatureIndex> = namespace.iter().map(|(index, _)| index).collect();
+        assert_eq!(indices, vec![FeatureIndex::from(203039)]);
+    }
+}
@@ -0,0 +1,295 @@
+//! Hash collision diagnostics.
+//!
+//! Features are hashed into `2^num_bits` weights, so distinct features can end
+//! up sharing a weight. [`HashCollisionCounter`] collects the distinct features
+//! seen in a dataset and reports how many of them collide for a given number of
+//! bits, which helps with choosing `numBits`.
+//!
+//! Features are identified by their full 32 bit hash, so collisions in the full
+//! hash are not visible.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    hash::HashFunction,
+    interactions::CompiledInteractions,
+    parsers::{FeatureNames, ParsedNamespaceInfo},
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    weights::foreach_interacted_feature_with_repeats,
+    FeatureIndex, FeatureMask,
+};
+
+/// Full hash of each distinct feature and the index it is masked to.
+type FeatureIndices = HashMap<u32, FeatureIndex>;
+
+pub struct HashCollisionCounter {
+    num_bits: u8,
+    hash_seed: u32,
+    hash_function: HashFunction,
+    interactions: CompiledInteractions,
+    namespace_names: HashMap<Namespace, String>,
+    namespaces: HashMap<Namespace, FeatureIndices>,
+    interacted: HashMap<Vec<Namespace>, FeatureIndices>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionSummary {
+    /// Namespace name, interaction as names joined by `*`, or `All`.
+    pub name: String,
+    pub distinct_features: usize,
+    /// Number of weight indices used by the features.
+    pub used_indices: usize,
+    /// Number of features which share an index with another feature.
+    pub colliding_features: usize,
+    /// Fraction of features expected to collide if hashes were uniformly
+    /// distributed.
+    pub expected_collision_rate: f64,
+}
+
+impl CollisionSummary {
+    fn new(name: String, indices: &FeatureIndices, num_bits: u8) -> CollisionSummary {
+        let mut counts: HashMap<FeatureIndex, usize> = HashMap::new();
+        for index in indices.values() {
+            *counts.entry(*index).or_default() += 1;
+        }
+        let colliding_features = counts.values().filter(|count| **count > 1).sum();
+        let num_weights = (1_u64 << num_bits) as f64;
+        let expected_collision_rate = if indices.is_empty() {
+            0.0
+        } else {
+            1.0 - (1.0 - 1.0 / num_weights).powf(indices.len() as f64 - 1.0)
+        };
+        CollisionSummary {
+            name,
+            distinct_features: indices.len(),
+            used_indices: counts.len(),
+            colliding_features,
+            expected_collision_rate,
+        }
+    }
+
+    /// Fraction of features which share an index with another feature.
+    pub fn collision_rate(&self) -> f64 {
+        if self.distinct_features == 0 {
+            0.0
+        } else {
+            self.colliding_features as f64 / self.distinct_features as f64
+        }
+    }
+}
+
+impl HashCollisionCounter {
+    pub fn new(
+        num_bits: u8,
+        hash_seed: u32,
+        hash_function: HashFunction,
+        interactions: CompiledInteractions,
+    ) -> Self {
+        HashCollisionCounter {
+            num_bits,
+            hash_seed,
+            hash_function,
+            interactions,
+            namespace_names: HashMap::new(),
+            namespaces: HashMap::new(),
+            interacted: HashMap::new(),
+        }
+    }
+
+    pub fn num_bits(&self) -> u8 {
+        self.num_bits
+    }
+
+    /// Adds the features of one example, or of one action of a multi-line
+    /// example, as returned by
+    /// [`crate::parsers::TextModeParser::extract_feature_names`].
+    pub fn add_example(&mut self, example: &FeatureNames) {
+        let mask = FeatureMask::from_num_bits(self.num_bits);
+        // Interactions are produced from the masked indices, exactly as in
+        // training, and from the full hashes to identify each interacted
+        // feature.
+        let mut full_features = SparseFeatures::new();
+        let mut masked_features = SparseFeatures::new();
+        for (namespace_info, features) in example {
+            let (namespace, name) = match namespace_info {
+                ParsedNamespaceInfo::Named(name) => (
+                    Namespace::from_name(name, self.hash_seed, self.hash_function),
+                    name.as_ref(),
+                ),
+                ParsedNamespaceInfo::Default => (Namespace::Default, "Default"),
+            };
+            self.namespace_names
+                .entry(namespace)
+                .or_insert_with(|| name.to_owned());
+            let indices = self.namespaces.entry(namespace).or_default();
+            let full_namespace = full_features.get_or_create_named_namespace(namespace, name);
+            let masked_namespace = masked_features.get_or_create_named_namespace(namespace, name);
+            for feature in features {
+                let hash = feature.hash(namespace.hash(), self.hash_function);
+                let index = hash.mask(mask);
+                indices.insert(u32::from(hash), index);
+                full_namespace.add_feature(FeatureIndex::from(u32::from(hash)), 1.0);
+                masked_namespace.add_feature(index, 1.0);
+            }
+        }
+
+        let interacted = &mut self.interacted;
+        self.interactions
+            .foreach_interaction(&masked_features, |interaction, repeat_of| {
+                let mut full_hashes = Vec::new();
+                foreach_interacted_feature_with_repeats(
+                    &full_features,
+                    interaction,
+                    repeat_of,
+                    |_, hash| full_hashes.push(u32::from(hash)),
+                );
+                let indices = interacted.entry(interaction.to_vec()).or_default();
+                let mut full_hashes = full_hashes.into_iter();
+                foreach_interacted_feature_with_repeats(
+                    &masked_features,
+                    interaction,
+                    repeat_of,
+                    |_, hash| {
+                        indices.insert(full_hashes.next().unwrap(), hash.mask(mask));
+                    },
+                );
+            });
+    }
+
+    /// Summaries for each namespace sorted by name, then each interaction,
+    /// then all features together.
+    pub fn summarize(&self) -> Vec<CollisionSummary> {
+        let mut all = FeatureIndices::new();
+        let mut summaries = Vec::new();
+
+        let namespaces: BTreeMap<&str, &FeatureIndices> = self
+            .namespaces
+            .iter()
+            .map(|(namespace, indices)| (self.namespace_names[namespace].as_str(), indices))
+            .collect();
+        for (name, indices) in namespaces {
+            summaries.push(CollisionSummary::new(
+                name.to_owned(),
+                indices,
+                self.num_bits,
+            ));
+            all.extend(indices);
+        }
+
+        let interactions: BTreeMap<String, &FeatureIndices> = self
+            .interacted
+            .iter()
+            .map(|(interaction, indices)| {
+                let names: Vec<&str> = interaction
+                    .iter()
+                    .map(|namespace| self.namespace_names[namespace].as_str())
+                    .collect();
+                (names.join("*"), indices)
+            })
+            .collect();
+        for (name, indices) in interactions {
+            summaries.push(CollisionSummary::new(name, indices, self.num_bits));
+            all.extend(indices);
+        }
+
+        summaries.push(CollisionSummary::new("All".to_owned(), &all, self.num_bits));
+        summaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::interactions::{compile_interactions, InteractionMode, NamespaceDef};
+    use crate::parsers::ParsedFeature;
+
+    fn example<'a>(namespaces: &[(ParsedNamespaceInfo<'a>, &[&'a str])]) -> FeatureNames<'a> {
+        namespaces
+            .iter()
+            .map(|(namespace, names)| {
+                (
+                    namespace.clone(),
+                    names
+                        .iter()
+                        .map(|name| ParsedFeature::Simple {
+                            name: (*name).into(),
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_collisions_per_namespace() {
+        let names: Vec<String> = (0..1000).map(|i| format!("f{}", i)).collect();
+        let names: Vec<&str> = names.iter().map(|x| x.as_str()).collect();
+        let ex = example(&[
+            (ParsedNamespaceInfo::Named("a".into()), &names),
+            (ParsedNamespaceInfo::Default, &["x"]),
+        ]);
+
+        // With 4 bits all 1000 features in "a" must collide.
+        let mut counter = HashCollisionCounter::new(
+            4,
+            0,
+            HashFunction::default(),
+            CompiledInteractions::default(),
+        );
+        counter.add_example(&ex);
+        counter.add_example(&ex);
+        let summaries = counter.summarize();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].name, "Default");
+        assert_eq!(summaries[0].distinct_features, 1);
+        assert_eq!(summaries[0].colliding_features, 0);
+        assert_eq!(summaries[1].name, "a");
+        assert_eq!(summaries[1].distinct_features, 1000);
+        assert_eq!(summaries[1].used_indices, 16);
+        assert_relative_eq!(summaries[1].collision_rate(), 1.0);
+        assert_eq!(summaries[2].name, "All");
+        assert_eq!(summaries[2].distinct_features, 1001);
+
+        // With 24 bits there should be next to no collisions.
+        let mut counter = HashCollisionCounter::new(
+            24,
+            0,
+            HashFunction::default(),
+            CompiledInteractions::default(),
+        );
+        counter.add_example(&ex);
+        let summaries = counter.summarize();
+        assert!(summaries[1].collision_rate() < 0.01);
+        assert!(summaries[1].expected_collision_rate < 0.001);
+    }
+
+    #[test]
+    fn counts_interacted_features() {
+        let interactions = compile_interactions(
+            &[vec![
+                NamespaceDef::Name("a".to_owned()),
+                NamespaceDef::Name("b".to_owned()),
+            ]],
+            InteractionMode::Combinations,
+            0,
+            HashFunction::default(),
+        )
+        .unwrap();
+        let mut counter = HashCollisionCounter::new(18, 0, HashFunction::default(), interactions);
+        counter.add_example(&example(&[
+            (ParsedNamespaceInfo::Named("a".into()), &["1", "2"]),
+            (ParsedNamespaceInfo::Named("b".into()), &["3", "4", "5"]),
+        ]));
+        counter.add_example(&example(&[
+            (ParsedNamespaceInfo::Named("a".into()), &["1"]),
+            (ParsedNamespaceInfo::Named("b".into()), &["6"]),
+        ]));
+        let summaries = counter.summarize();
+        let names: Vec<&str> = summaries.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "a*b", "All"]);
+        assert_eq!(summaries[2].distinct_features, 7);
+        assert_eq!(summaries[3].distinct_features, 13);
+    }
+}
@@ -1,71 +1,429 @@
+use std::collections::BTreeSet;
+
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 
-use crate::{hash::hash_bytes, sparse_namespaced_features::Namespace};
+use crate::{
+    error::{Error, Result},
+    hash::HashFunction,
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+};
 
 #[derive(Serialize, Deserialize, Clone, JsonSchema, Debug)]
 pub enum NamespaceDef {
     Name(String),
     Default,
+    /// Matches every namespace present in an example. This is only valid
+    /// inside of an interaction.
+    Wildcard,
+    /// Matches every namespace present in an example whose name starts with
+    /// the given prefix. Only namespaces created by a parser have a name, so
+    /// this never matches the default namespace or namespaces produced by
+    /// reductions. This is only valid inside of an interaction.
+    Prefix(String),
 }
 
 pub type Interaction = Vec<NamespaceDef>;
-pub type HashedInteraction = Vec<Namespace>;
+
+/// Controls which feature crosses are produced when a namespace appears more
+/// than once in an interaction.
+#[derive(Serialize, Deserialize, Clone, Copy, JsonSchema, Debug, Default, PartialEq, Eq)]
+pub enum InteractionMode {
+    /// Each unordered combination of features is produced once. For example,
+    /// crossing a namespace with itself produces `a*b` but not `b*a`, and
+    /// wildcard expansions which only differ in order are only used once.
+    #[default]
+    Combinations,
+    /// Every ordered permutation of features is produced.
+    Permutations,
+}
+
+pub type HashedInteraction = Vec<HashedNamespaceDef>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum HashedNamespaceDef {
+    Namespace(Namespace),
+    Wildcard,
+    Prefix(String),
+}
 
 impl NamespaceDef {
-    pub fn to_namespace(&self, hash_seed: u32) -> Namespace {
+    pub fn to_namespace(&self, hash_seed: u32, hash_function: HashFunction) -> Result<Namespace> {
         match self {
             NamespaceDef::Name(name) => {
-                let namespace_hash = hash_bytes(name.as_bytes(), hash_seed);
-                Namespace::Named(namespace_hash.into())
+                let namespace_hash = hash_function.hash_bytes(name.as_bytes(), hash_seed);
+                Ok(Namespace::Named(namespace_hash.into()))
+            }
+            NamespaceDef::Default => Ok(Namespace::Default),
+            NamespaceDef::Wildcard => Err(Error::InvalidArgument(
+                "Wildcard namespaces can only be used in interactions".to_owned(),
+            )),
+            NamespaceDef::Prefix(_) => Err(Error::InvalidArgument(
+                "Prefix namespaces can only be used in interactions".to_owned(),
+            )),
+        }
+    }
+
+    pub fn to_hashed(&self, hash_seed: u32, hash_function: HashFunction) -> HashedNamespaceDef {
+        match self {
+            NamespaceDef::Wildcard => HashedNamespaceDef::Wildcard,
+            NamespaceDef::Prefix(prefix) => HashedNamespaceDef::Prefix(prefix.clone()),
+            _ => {
+                HashedNamespaceDef::Namespace(self.to_namespace(hash_seed, hash_function).unwrap())
             }
-            NamespaceDef::Default => Namespace::Default,
         }
     }
 }
 
-pub fn hash_interaction(interaction: &Interaction, hash_seed: u32) -> HashedInteraction {
-    if interaction.is_empty() || interaction.len() > 3 {
-        panic!("Interaction must be between 1 and 3 namespaces")
+pub fn hash_interaction(
+    interaction: &Interaction,
+    hash_seed: u32,
+    hash_function: HashFunction,
+) -> Result<HashedInteraction> {
+    if interaction.is_empty() {
+        return Err(Error::InvalidConfiguration(
+            "Interactions must contain at least one namespace".to_owned(),
+        ));
+    }
+    if interaction
+        .iter()
+        .any(|ns| matches!(ns, NamespaceDef::Prefix(prefix) if prefix.is_empty()))
+    {
+        return Err(Error::InvalidConfiguration(
+            "Namespace prefixes must not be empty, use Wildcard to match every namespace"
+                .to_owned(),
+        ));
     }
+    Ok(interaction
+        .iter()
+        .map(|ns| ns.to_hashed(hash_seed, hash_function))
+        .collect())
+}
+
+/// For each namespace of an interaction, the position of the nearest earlier
+/// occurrence of the same namespace, if combinations are being produced and it
+/// is repeated.
+pub fn repeat_positions(
+    interaction: &[Namespace],
+    mode: InteractionMode,
+) -> impl Iterator<Item = Option<usize>> + '_ {
     interaction
         .iter()
-        .map(|ns| ns.to_namespace(hash_seed))
-        .collect()
+        .enumerate()
+        .map(move |(i, ns)| match mode {
+            InteractionMode::Combinations => interaction[..i].iter().rposition(|x| x == ns),
+            InteractionMode::Permutations => None,
+        })
 }
 
-type InteractionPair = (Namespace, Namespace);
-type InteractionTriple = (Namespace, Namespace, Namespace);
+/// Interactions of this length or shorter are expanded without allocating.
+const INLINE_INTERACTION_LENGTH: usize = 4;
+type ConcreteInteraction = SmallVec<[Namespace; INLINE_INTERACTION_LENGTH]>;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct FixedInteraction {
+    namespaces: Vec<Namespace>,
+    repeat_of: Vec<Option<usize>>,
+}
+
+/// Interactions ready to be used when iterating features. Interactions without
+/// wildcards or prefixes are resolved once up front, the others are expanded
+/// against the namespaces present in each example.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CompiledInteractions {
+    fixed: Vec<FixedInteraction>,
+    /// Keys of the fixed interactions, used to skip expansions which are the
+    /// same as one of them.
+    fixed_keys: BTreeSet<Vec<Namespace>>,
+    patterns: Vec<HashedInteraction>,
+    #[serde(default)]
+    mode: InteractionMode,
+}
 
 pub fn compile_interactions(
     interactions: &[Interaction],
+    mode: InteractionMode,
     hash_seed: u32,
-) -> (Vec<InteractionPair>, Vec<InteractionTriple>) {
-    let pairs: Vec<(Namespace, Namespace)> = interactions
-        .iter()
-        .filter(|interaction| interaction.len() == 2)
-        .map(|interaction| {
-            let hashed_interaction = hash_interaction(interaction, hash_seed);
-            (hashed_interaction[0], hashed_interaction[1])
-        })
-        .collect();
+    hash_function: HashFunction,
+) -> Result<CompiledInteractions> {
+    let mut compiled = CompiledInteractions {
+        mode,
+        ..Default::default()
+    };
+    for interaction in interactions {
+        let hashed = hash_interaction(interaction, hash_seed, hash_function)?;
+        let namespaces: Option<Vec<Namespace>> = hashed
+            .iter()
+            .map(|ns| match ns {
+                HashedNamespaceDef::Namespace(ns) => Some(*ns),
+                HashedNamespaceDef::Wildcard | HashedNamespaceDef::Prefix(_) => None,
+            })
+            .collect();
+        match namespaces {
+            Some(namespaces) => compiled.push_fixed(namespaces),
+            None => compiled.patterns.push(hashed),
+        }
+    }
+    Ok(compiled)
+}
 
-    let triples: Vec<(Namespace, Namespace, Namespace)> = interactions
-        .iter()
-        .filter(|interaction| interaction.len() == 3)
-        .map(|interaction| {
-            let hashed_interaction = hash_interaction(interaction, hash_seed);
-            (
-                hashed_interaction[0],
-                hashed_interaction[1],
-                hashed_interaction[2],
-            )
-        })
-        .collect();
+fn dedup_key(interaction: &[Namespace], mode: InteractionMode) -> ConcreteInteraction {
+    let mut key = ConcreteInteraction::from_slice(interaction);
+    if mode == InteractionMode::Combinations {
+        key.sort();
+    }
+    key
+}
+
+/// Calls `func` with every concrete interaction matching `pattern`, given the
+/// namespaces present in an example along with their names.
+fn expand_pattern<F>(
+    pattern: &[HashedNamespaceDef],
+    present: &[(Namespace, Option<&str>)],
+    concrete: &mut ConcreteInteraction,
+    func: &mut F,
+) where
+    F: FnMut(&ConcreteInteraction),
+{
+    let Some((def, rest)) = pattern.split_first() else {
+        func(concrete);
+        return;
+    };
+    match def {
+        HashedNamespaceDef::Namespace(ns) => {
+            concrete.push(*ns);
+            expand_pattern(rest, present, concrete, func);
+            concrete.pop();
+        }
+        HashedNamespaceDef::Wildcard | HashedNamespaceDef::Prefix(_) => {
+            for (ns, name) in present {
+                if let HashedNamespaceDef::Prefix(prefix) = def {
+                    if !name.is_some_and(|name| name.starts_with(prefix.as_str())) {
+                        continue;
+                    }
+                }
+                concrete.push(*ns);
+                expand_pattern(rest, present, concrete, func);
+                concrete.pop();
+            }
+        }
+    }
+}
+
+impl CompiledInteractions {
+    /// Creates interactions from namespaces which are already hashed, as
+    /// stored by models saved before interactions were compiled.
+    pub fn from_hashed(interactions: Vec<Vec<Namespace>>, mode: InteractionMode) -> Self {
+        let mut compiled = CompiledInteractions {
+            mode,
+            ..Default::default()
+        };
+        for namespaces in interactions {
+            compiled.push_fixed(namespaces);
+        }
+        compiled
+    }
+
+    fn push_fixed(&mut self, namespaces: Vec<Namespace>) {
+        self.fixed_keys
+            .insert(dedup_key(&namespaces, self.mode).into_vec());
+        self.fixed.push(FixedInteraction {
+            repeat_of: repeat_positions(&namespaces, self.mode).collect(),
+            namespaces,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixed.is_empty() && self.patterns.is_empty()
+    }
+
+    pub fn mode(&self) -> InteractionMode {
+        self.mode
+    }
+
+    /// Calls `func` with each concrete interaction that applies to the given
+    /// features, along with its [`repeat_positions`].
+    ///
+    /// Wildcard and prefix expansions which are the same as an interaction
+    /// that was already produced are skipped. In
+    /// [`InteractionMode::Combinations`] the order of namespaces is ignored for
+    /// this, so `[Wildcard, Wildcard]` over namespaces `a` and `b` produces
+    /// `[a, a]`, `[a, b]` and `[b, b]`.
+    pub fn foreach_interaction<F>(&self, features: &SparseFeatures, mut func: F)
+    where
+        F: FnMut(&[Namespace], &[Option<usize>]),
+    {
+        for interaction in &self.fixed {
+            func(&interaction.namespaces, &interaction.repeat_of);
+        }
+
+        if self.patterns.is_empty() {
+            return;
+        }
+
+        let present: SmallVec<[(Namespace, Option<&str>); 16]> = features
+            .namespaces()
+            .filter_map(|(ns, _)| features.get_namespace(ns))
+            .map(|ns| (ns.namespace(), ns.name()))
+            .collect();
+        let mut seen = BTreeSet::new();
+        let mut concrete = ConcreteInteraction::new();
+        for pattern in &self.patterns {
+            expand_pattern(pattern, &present, &mut concrete, &mut |concrete| {
+                let key = dedup_key(concrete, self.mode);
+                if !self.fixed_keys.contains(key.as_slice()) && seen.insert(key) {
+                    let repeat_of: SmallVec<[Option<usize>; INLINE_INTERACTION_LENGTH]> =
+                        repeat_positions(concrete, self.mode).collect();
+                    func(concrete, &repeat_of);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FeatureIndex, NamespaceHash};
+
+    #[test]
+    fn wildcard_expands_to_combinations_of_present_namespaces() {
+        let mut features = SparseFeatures::new();
+        let a = Namespace::Named(NamespaceHash::from(1));
+        let b = Namespace::Named(NamespaceHash::from(2));
+        features
+            .get_or_create_namespace(a)
+            .add_feature(FeatureIndex::from(1), 1.0);
+        features
+            .get_or_create_namespace(b)
+            .add_feature(FeatureIndex::from(2), 1.0);
 
-    if pairs.len() + triples.len() != interactions.len() {
-        panic!("Invalid interaction. Only pairs and triples are supported")
+        let compiled = compile_interactions(
+            &[
+                vec![NamespaceDef::Wildcard, NamespaceDef::Wildcard],
+                vec![
+                    NamespaceDef::Default,
+                    NamespaceDef::Default,
+                    NamespaceDef::Default,
+                    NamespaceDef::Default,
+                ],
+            ],
+            InteractionMode::Combinations,
+            0,
+            HashFunction::default(),
+        )
+        .unwrap();
+
+        let mut produced = Vec::new();
+        compiled.foreach_interaction(&features, |interaction, _| {
+            produced.push(interaction.to_vec())
+        });
+        assert_eq!(
+            produced,
+            vec![
+                vec![Namespace::Default; 4],
+                vec![a, a],
+                vec![a, b],
+                vec![b, b]
+            ]
+        );
     }
 
-    (pairs, triples)
+    #[test]
+    fn wildcard_expands_to_permutations_of_present_namespaces() {
+        let mut features = SparseFeatures::new();
+        let a = Namespace::Named(NamespaceHash::from(1));
+        features
+            .get_or_create_namespace(a)
+            .add_feature(FeatureIndex::from(1), 1.0);
+        features
+            .get_or_create_namespace(Namespace::Default)
+            .add_feature(FeatureIndex::from(2), 1.0);
+
+        let compiled = compile_interactions(
+            &[vec![NamespaceDef::Wildcard, NamespaceDef::Wildcard]],
+            InteractionMode::Permutations,
+            0,
+            HashFunction::default(),
+        )
+        .unwrap();
+
+        let mut produced = Vec::new();
+        compiled.foreach_interaction(&features, |interaction, _| {
+            produced.push(interaction.to_vec())
+        });
+        assert_eq!(
+            produced,
+            vec![
+                vec![a, a],
+                vec![a, Namespace::Default],
+                vec![Namespace::Default, a],
+                vec![Namespace::Default, Namespace::Default]
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_matches_present_namespaces_by_name() {
+        let mut features = SparseFeatures::new();
+        let names = ["user_age", "user_id", "item"];
+        let namespaces: Vec<Namespace> = names
+            .iter()
+            .map(|name| Namespace::from_name(name, 0, HashFunction::default()))
+            .collect();
+        for (namespace, name) in namespaces.iter().zip(names) {
+            features
+                .get_or_create_named_namespace(*namespace, name)
+                .add_feature(FeatureIndex::from(1), 1.0);
+        }
+        // Namespaces without a name never match a prefix.
+        let unnamed = Namespace::Named(NamespaceHash::from(1));
+        features
+            .get_or_create_namespace(unnamed)
+            .add_feature(FeatureIndex::from(2), 1.0);
+
+        let compiled = compile_interactions(
+            &[
+                vec![
+                    NamespaceDef::Prefix("user_".to_owned()),
+                    NamespaceDef::Name("item".to_owned()),
+                ],
+                vec![
+                    NamespaceDef::Name("user_id".to_owned()),
+                    NamespaceDef::Name("item".to_owned()),
+                ],
+            ],
+            InteractionMode::Combinations,
+            0,
+            HashFunction::default(),
+        )
+        .unwrap();
+
+        let mut produced = Vec::new();
+        compiled.foreach_interaction(&features, |interaction, repeat_of| {
+            assert_eq!(repeat_of, &[None, None]);
+            produced.push(interaction.to_vec());
+        });
+        let [user_age, user_id, item] = namespaces[..] else {
+            unreachable!()
+        };
+        // The expansion matching the fixed interaction is skipped.
+        assert_eq!(produced, vec![vec![user_id, item], vec![user_age, item]]);
+    }
+
+    #[test]
+    fn invalid_interactions_are_rejected() {
+        for interaction in [vec![], vec![NamespaceDef::Prefix(String::new())]] {
+            assert!(matches!(
+                compile_interactions(
+                    &[interaction],
+                    InteractionMode::Combinations,
+                    0,
+                    HashFunction::default()
+                ),
+                Err(Error::InvalidConfiguration(_))
+            ));
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    hash::{hash_bytes, FNV_PRIME},
+    hash::{HashFunction, FNV_PRIME},
     parsers::ParsedFeature,
     FeatureHash, FeatureIndex,
 };
@@ -31,35 +31,39 @@ pub enum Feature {
 }
 
 impl Feature {
-    pub fn hash(&self, hash_seed: u32) -> FeatureHash {
+    pub fn hash(&self, hash_seed: u32, hash_function: HashFunction) -> FeatureHash {
         match &self {
             Feature::Simple { namespace, name } => {
-                let namespace_hash = hash_bytes(namespace.as_bytes(), hash_seed);
-                hash_bytes(name.as_bytes(), namespace_hash).into()
+                let namespace_hash = hash_function.hash_bytes(namespace.as_bytes(), hash_seed);
+                hash_function
+                    .hash_bytes(name.as_bytes(), namespace_hash)
+                    .into()
             }
             Feature::SimpleWithStringValue {
                 namespace,
                 name,
                 value,
             } => {
-                let namespace_hash = hash_bytes(namespace.as_bytes(), hash_seed);
-                let name_key_hash = hash_bytes(name.as_bytes(), namespace_hash);
-                hash_bytes(value.as_bytes(), name_key_hash).into()
+                let namespace_hash = hash_function.hash_bytes(namespace.as_bytes(), hash_seed);
+                let name_key_hash = hash_function.hash_bytes(name.as_bytes(), namespace_hash);
+                hash_function
+                    .hash_bytes(value.as_bytes(), name_key_hash)
+                    .into()
             }
             Feature::Anonymous { namespace, offset } => {
-                let namespace_hash = hash_bytes(namespace.as_bytes(), hash_seed);
+                let namespace_hash = hash_function.hash_bytes(namespace.as_bytes(), hash_seed);
                 (namespace_hash + offset).into()
             }
             // In a very cool property hashing of the interacted feature does not need to take into account bit masking until the very end
             // In fact, the produced result is idenitical if interim values are masked or just the final value.
             Feature::Interacted { terms } => {
-                let val0 = Feature::hash(terms.first().unwrap(), hash_seed);
+                let val0 = Feature::hash(terms.first().unwrap(), hash_seed, hash_function);
                 let mut hash_so_far = (FNV_PRIME).wrapping_mul(*val0);
                 for term in terms[1..terms.len() - 1].iter() {
-                    hash_so_far =
-                        (FNV_PRIME).wrapping_mul(hash_so_far ^ *Feature::hash(term, hash_seed));
+                    hash_so_far = (FNV_PRIME)
+                        .wrapping_mul(hash_so_far ^ *Feature::hash(term, hash_seed, hash_function));
                 }
-                hash_so_far ^= *Feature::hash(terms.last().unwrap(), hash_seed);
+                hash_so_far ^= *Feature::hash(terms.last().unwrap(), hash_seed, hash_function);
                 hash_so_far.into()
             }
         }
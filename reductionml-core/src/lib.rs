@@ -1,9 +1,12 @@
+pub mod compression;
 pub mod config_schema;
+pub mod dense_features;
 pub mod dense_weights;
 pub mod error;
 pub mod explore;
 pub mod global_config;
 pub mod hash;
+pub mod hash_collisions;
 pub mod interactions;
 pub mod inverse_hash_table;
 pub mod loss_function;
@@ -16,6 +19,7 @@ pub mod reduction_registry;
 pub mod reductions;
 pub mod sparse_namespaced_features;
 pub mod types;
+pub mod vw_model;
 pub mod weights;
 pub mod workspace;
 
@@ -1,3 +1,4 @@
+mod accumulator;
 mod example_number;
 mod ips;
 mod mean_squared_error;
@@ -5,6 +6,7 @@ mod metric;
 mod metric_registry;
 mod parsed_features;
 
+pub use accumulator::*;
 pub use example_number::*;
 pub use ips::*;
 pub use mean_squared_error::*;
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+/// Accumulates per example values of a metric so that it can be reported over
+/// all points, over a sliding window of the most recent points and over the
+/// points added since the last report. The window is only kept once a window
+/// size has been set.
+#[derive(Default)]
+pub struct MetricAccumulator {
+    total_sum: f64,
+    total_count: u64,
+    since_last_sum: f64,
+    since_last_count: u64,
+    window: Option<VecDeque<f32>>,
+    window_sum: f64,
+    window_size: usize,
+}
+
+impl MetricAccumulator {
+    pub fn new(window_size: usize) -> MetricAccumulator {
+        let mut accumulator = MetricAccumulator::default();
+        accumulator.set_window_size(window_size);
+        accumulator
+    }
+
+    pub fn add(&mut self, value: f32) {
+        self.total_sum += value as f64;
+        self.total_count += 1;
+        self.since_last_sum += value as f64;
+        self.since_last_count += 1;
+
+        let Some(window) = self.window.as_mut() else {
+            return;
+        };
+        if window.len() == self.window_size {
+            if let Some(oldest) = window.pop_front() {
+                self.window_sum -= oldest as f64;
+            }
+        }
+        window.push_back(value);
+        self.window_sum += value as f64;
+    }
+
+    /// A size of zero stops keeping the window.
+    pub fn set_window_size(&mut self, window_size: usize) {
+        self.window_size = window_size;
+        if window_size == 0 {
+            self.window = None;
+            self.window_sum = 0.0;
+            return;
+        }
+        let window = self
+            .window
+            .get_or_insert_with(|| VecDeque::with_capacity(window_size));
+        while window.len() > window_size {
+            if let Some(oldest) = window.pop_front() {
+                self.window_sum -= oldest as f64;
+            }
+        }
+    }
+
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+
+    /// Starts a new "since last" period.
+    pub fn reset_since_last(&mut self) {
+        self.since_last_sum = 0.0;
+        self.since_last_count = 0;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total_count
+    }
+
+    pub fn sum(&self) -> f32 {
+        self.total_sum as f32
+    }
+
+    pub fn mean(&self) -> f32 {
+        (self.total_sum / self.total_count as f64) as f32
+    }
+
+    /// None if there is no window. Sums are kept as `f64`, so they are exact
+    /// for integer values.
+    pub fn window_sum(&self) -> Option<f64> {
+        self.window.as_ref().map(|_| self.window_sum)
+    }
+
+    /// None if there is no window or it is empty.
+    pub fn window_mean(&self) -> Option<f32> {
+        match &self.window {
+            Some(window) if !window.is_empty() => {
+                Some((self.window_sum / window.len() as f64) as f32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn since_last_sum(&self) -> f64 {
+        self.since_last_sum
+    }
+
+    /// None if no points have been added since the last report.
+    pub fn since_last_mean(&self) -> Option<f32> {
+        if self.since_last_count == 0 {
+            return None;
+        }
+        Some((self.since_last_sum / self.since_last_count as f64) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn accumulator_window_and_since_last() {
+        let mut acc = MetricAccumulator::new(2);
+        acc.add(1.0);
+        acc.add(2.0);
+        acc.add(3.0);
+
+        assert_eq!(acc.count(), 3);
+        assert_relative_eq!(acc.mean(), 2.0);
+        assert_relative_eq!(acc.window_mean().unwrap(), 2.5);
+        assert_relative_eq!(acc.window_sum().unwrap(), 5.0);
+        assert_relative_eq!(acc.since_last_mean().unwrap(), 2.0);
+
+        acc.reset_since_last();
+        assert_eq!(acc.since_last_mean(), None);
+        acc.add(5.0);
+        assert_relative_eq!(acc.since_last_mean().unwrap(), 5.0);
+        assert_relative_eq!(acc.window_mean().unwrap(), 4.0);
+        assert_relative_eq!(acc.mean(), 2.75);
+
+        acc.set_window_size(1);
+        assert_relative_eq!(acc.window_mean().unwrap(), 5.0);
+    }
+
+    #[test]
+    fn accumulator_without_window() {
+        let mut acc = MetricAccumulator::default();
+        acc.add(1.0);
+        assert!(acc.window.is_none());
+        assert_eq!(acc.window_mean(), None);
+        assert_eq!(acc.window_sum(), None);
+        assert_relative_eq!(acc.mean(), 1.0);
+    }
+}
@@ -38,4 +38,12 @@ impl Metric for ExampleNumberMetric {
     fn get_name(&self) -> String {
         "Example #".to_owned()
     }
+
+    fn get_description(&self) -> String {
+        "Zero based index of the current example".to_owned()
+    }
+
+    fn resume_from(&mut self, examples: u64) {
+        self.count += examples;
+    }
 }
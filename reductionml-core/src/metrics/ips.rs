@@ -1,17 +1,18 @@
-use crate::{metrics::Metric, utils::AsInner, ActionProbsPrediction, CBLabel, Features};
+use crate::{
+    metrics::Metric, utils::AsInner, ActionProbsPrediction, CBLabel, Features, LabelType,
+    PredictionType,
+};
 
-use super::MetricValue;
+use super::{MetricAccumulator, MetricValue};
 
 pub struct IpsMetric {
-    pub examples_count: u64,
-    pub weighted_reward: f32,
+    pub accumulator: MetricAccumulator,
 }
 
 impl IpsMetric {
     pub fn new() -> IpsMetric {
         IpsMetric {
-            examples_count: 0,
-            weighted_reward: 0.0,
+            accumulator: MetricAccumulator::default(),
         }
     }
 }
@@ -42,15 +43,50 @@ impl Metric for IpsMetric {
 
         let w = p_pred / p_log;
 
-        self.weighted_reward += (-1.0 * label.cost) * w;
-        self.examples_count += 1;
+        self.accumulator.add(-label.cost * w);
     }
 
     fn get_value(&self) -> MetricValue {
-        MetricValue::Float(self.weighted_reward / (self.examples_count as f32))
+        MetricValue::Float(self.accumulator.mean())
     }
 
     fn get_name(&self) -> String {
         "Estimated reward (IPS)".to_owned()
     }
+
+    fn get_description(&self) -> String {
+        "Inverse propensity score estimate of the reward of the learned policy".to_owned()
+    }
+
+    fn required_label_type(&self) -> Option<LabelType> {
+        Some(LabelType::CB)
+    }
+
+    fn required_prediction_type(&self) -> Option<PredictionType> {
+        Some(PredictionType::ActionProbs)
+    }
+
+    fn get_window_value(&self) -> Option<MetricValue> {
+        Some(
+            self.accumulator
+                .window_mean()
+                .map_or(MetricValue::Missing, MetricValue::Float),
+        )
+    }
+
+    fn get_since_last_value(&self) -> Option<MetricValue> {
+        Some(
+            self.accumulator
+                .since_last_mean()
+                .map_or(MetricValue::Missing, MetricValue::Float),
+        )
+    }
+
+    fn mark_reported(&mut self) {
+        self.accumulator.reset_since_last();
+    }
+
+    fn set_window_size(&mut self, window_size: usize) {
+        self.accumulator.set_window_size(window_size);
+    }
 }
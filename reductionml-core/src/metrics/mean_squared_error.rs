@@ -1,17 +1,18 @@
-use crate::{metrics::Metric, utils::AsInner, Features, ScalarPrediction, SimpleLabel};
+use crate::{
+    metrics::Metric, utils::AsInner, Features, LabelType, PredictionType, ScalarPrediction,
+    SimpleLabel,
+};
 
-use super::MetricValue;
+use super::{MetricAccumulator, MetricValue};
 
 pub struct MeanSquaredErrorMetric {
-    pub value: f32,
-    pub count: u64,
+    pub accumulator: MetricAccumulator,
 }
 
 impl MeanSquaredErrorMetric {
     pub fn new() -> MeanSquaredErrorMetric {
         MeanSquaredErrorMetric {
-            value: 0.0,
-            count: 0,
+            accumulator: MetricAccumulator::default(),
         }
     }
 }
@@ -31,15 +32,51 @@ impl Metric for MeanSquaredErrorMetric {
     ) {
         let label: &SimpleLabel = label.as_inner().unwrap();
         let pred: &ScalarPrediction = prediction.as_inner().unwrap();
-        self.value += (label.value() - pred.prediction) * (label.value() - pred.prediction);
-        self.count += 1;
+        self.accumulator
+            .add((label.value() - pred.prediction) * (label.value() - pred.prediction));
     }
 
     fn get_value(&self) -> MetricValue {
-        MetricValue::Float(self.value / self.count as f32)
+        MetricValue::Float(self.accumulator.mean())
     }
 
     fn get_name(&self) -> String {
         "MeanSquaredError".to_owned()
     }
+
+    fn get_description(&self) -> String {
+        "Mean squared error between the label and the scalar prediction".to_owned()
+    }
+
+    fn required_label_type(&self) -> Option<LabelType> {
+        Some(LabelType::Simple)
+    }
+
+    fn required_prediction_type(&self) -> Option<PredictionType> {
+        Some(PredictionType::Scalar)
+    }
+
+    fn get_window_value(&self) -> Option<MetricValue> {
+        Some(
+            self.accumulator
+                .window_mean()
+                .map_or(MetricValue::Missing, MetricValue::Float),
+        )
+    }
+
+    fn get_since_last_value(&self) -> Option<MetricValue> {
+        Some(
+            self.accumulator
+                .since_last_mean()
+                .map_or(MetricValue::Missing, MetricValue::Float),
+        )
+    }
+
+    fn mark_reported(&mut self) {
+        self.accumulator.reset_since_last();
+    }
+
+    fn set_window_size(&mut self, window_size: usize) {
+        self.accumulator.set_window_size(window_size);
+    }
 }
@@ -1,5 +1,7 @@
+use serde::Serialize;
+
 use crate::{
-    types::{Label, Prediction},
+    types::{Label, LabelType, Prediction, PredictionType},
     Features,
 };
 
@@ -7,13 +9,54 @@ pub trait Metric {
     fn add_point(&mut self, features: &Features, label: &Label, prediction: &Prediction);
     fn get_value(&self) -> MetricValue;
     fn get_name(&self) -> String;
+    fn get_description(&self) -> String;
+
+    /// The label type this metric needs to be calculated. None if the metric
+    /// works with any label type.
+    fn required_label_type(&self) -> Option<LabelType> {
+        None
+    }
+
+    /// The prediction type this metric needs to be calculated. None if the
+    /// metric works with any prediction type.
+    fn required_prediction_type(&self) -> Option<PredictionType> {
+        None
+    }
+
+    /// Value over the most recent points, bounded by the window size. Returns
+    /// None if the metric does not support windowed values.
+    fn get_window_value(&self) -> Option<MetricValue> {
+        None
+    }
+
+    /// Value over the points added since the last call to `mark_reported`.
+    /// Returns None if the metric does not support progressive values.
+    fn get_since_last_value(&self) -> Option<MetricValue> {
+        None
+    }
+
+    /// Called after the values of this metric have been reported to start a
+    /// new "since last" period.
+    fn mark_reported(&mut self) {}
+
+    fn set_window_size(&mut self, _window_size: usize) {}
+
+    /// Called when training resumes after `examples` examples were consumed
+    /// by an earlier run, so that metrics which count examples carry on.
+    fn resume_from(&mut self, _examples: u64) {}
 }
 
+#[derive(Serialize)]
+#[serde(untagged)]
 pub enum MetricValue {
     Bool(bool),
     Float(f32),
     Int(i32),
     String(String),
+    /// There is no value to report, such as a "since last" value when no
+    /// points have been added since the last report. Written as an empty
+    /// field or null.
+    Missing,
 }
 
 impl ToString for MetricValue {
@@ -23,6 +66,7 @@ impl ToString for MetricValue {
             MetricValue::Float(f) => f.to_string(),
             MetricValue::Int(i) => i.to_string(),
             MetricValue::String(s) => s.clone(),
+            MetricValue::Missing => String::new(),
         }
     }
 }
@@ -1,13 +1,97 @@
-use crate::metrics::{ips, parsed_features};
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+
+use crate::{
+    error::{Error, Result},
+    metrics::{ips, parsed_features},
+    reduction::ReductionTypeDescription,
+};
 
 use super::{example_number, mean_squared_error, Metric};
 
+type MetricConstructor = fn() -> Box<dyn Metric>;
+
+pub static METRIC_REGISTRY: Lazy<BTreeMap<&'static str, MetricConstructor>> = Lazy::new(|| {
+    let mut registry: BTreeMap<&'static str, MetricConstructor> = BTreeMap::new();
+    registry.insert("mse", || {
+        Box::new(mean_squared_error::MeanSquaredErrorMetric::new())
+    });
+    registry.insert("ips", || Box::new(ips::IpsMetric::new()));
+    registry.insert("parsed_features", || {
+        Box::new(parsed_features::ParsedFeaturesMetric::new())
+    });
+    registry.insert("example_number", || {
+        Box::new(example_number::ExampleNumberMetric::new())
+    });
+    registry
+});
+
 pub fn get_metric(name: &str) -> Option<Box<dyn Metric>> {
-    match name {
-        "mse" => Some(Box::new(mean_squared_error::MeanSquaredErrorMetric::new())),
-        "ips" => Some(Box::new(ips::IpsMetric::new())),
-        "parsed_features" => Some(Box::new(parsed_features::ParsedFeaturesMetric::new())),
-        "example_number" => Some(Box::new(example_number::ExampleNumberMetric::new())),
-        _ => None,
+    METRIC_REGISTRY.get(name).map(|constructor| constructor())
+}
+
+pub fn metric_names() -> impl Iterator<Item = &'static str> {
+    METRIC_REGISTRY.keys().copied()
+}
+
+/// Creates the metric with the given name and checks that it can be
+/// calculated from the labels and predictions of a reduction with the given
+/// types.
+pub fn create_metric(name: &str, types: &ReductionTypeDescription) -> Result<Box<dyn Metric>> {
+    let metric = get_metric(name).ok_or_else(|| {
+        Error::InvalidArgument(format!(
+            "Unknown metric: {}. Available metrics are: {}",
+            name,
+            metric_names().collect::<Vec<_>>().join(", ")
+        ))
+    })?;
+
+    if let Some(label_type) = metric.required_label_type() {
+        if label_type != types.input_label_type() {
+            return Err(Error::InvalidArgument(format!(
+                "Metric {} requires label type {:?} but the entry reduction uses {:?}",
+                name,
+                label_type,
+                types.input_label_type()
+            )));
+        }
+    }
+
+    if let Some(prediction_type) = metric.required_prediction_type() {
+        if prediction_type != types.output_prediction_type() {
+            return Err(Error::InvalidArgument(format!(
+                "Metric {} requires prediction type {:?} but the entry reduction produces {:?}",
+                name,
+                prediction_type,
+                types.output_prediction_type()
+            )));
+        }
+    }
+
+    Ok(metric)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        reduction::ReductionTypeDescriptionBuilder, FeaturesType, LabelType, PredictionType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn create_metric_checks_types() {
+        let types = ReductionTypeDescriptionBuilder::new(
+            LabelType::Simple,
+            FeaturesType::SparseSimple,
+            PredictionType::Scalar,
+        )
+        .build();
+
+        assert!(create_metric("mse", &types).is_ok());
+        assert!(create_metric("example_number", &types).is_ok());
+        assert!(create_metric("ips", &types).is_err());
+        assert!(create_metric("does_not_exist", &types).is_err());
     }
 }
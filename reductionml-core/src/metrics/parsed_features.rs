@@ -1,17 +1,26 @@
 use crate::{metrics::Metric, Features};
 
-use super::MetricValue;
+use super::{MetricAccumulator, MetricValue};
 
 pub struct ParsedFeaturesMetric {
     pub count: u64,
+    pub accumulator: MetricAccumulator,
 }
 
 impl ParsedFeaturesMetric {
     pub fn new() -> ParsedFeaturesMetric {
-        ParsedFeaturesMetric { count: 0 }
+        ParsedFeaturesMetric {
+            count: 0,
+            accumulator: MetricAccumulator::default(),
+        }
     }
 }
 
+/// Counts are reported as integers unless they are too large for one.
+fn count_value(count: u64) -> MetricValue {
+    i32::try_from(count).map_or_else(|_| MetricValue::Float(count as f32), MetricValue::Int)
+}
+
 impl Default for ParsedFeaturesMetric {
     fn default() -> Self {
         Self::new()
@@ -25,39 +34,99 @@ impl Metric for ParsedFeaturesMetric {
         _label: &crate::types::Label,
         _prediction: &crate::types::Prediction,
     ) {
+        let mut count = 0;
         match features {
-            Features::SparseSimple(s) => self.count += s.all_features().count() as u64,
-            Features::SparseSimpleRef(s) => self.count += s.all_features().count() as u64,
+            Features::SparseSimple(s) => count += s.all_features().count() as u64,
+            Features::SparseSimpleRef(s) => count += s.all_features().count() as u64,
             Features::SparseCBAdf(feats) => {
-                self.count += feats
+                count += feats
                     .shared
                     .as_ref()
                     .map_or(0, |x| x.all_features().count()) as u64;
-                self.count += feats
+                count += feats
                     .actions
                     .iter()
                     .map(|x| x.all_features().count())
                     .sum::<usize>() as u64;
             }
             Features::SparseCBAdfRef(feats) => {
-                self.count += feats
+                count += feats
                     .shared
                     .as_ref()
                     .map_or(0, |x| x.all_features().count()) as u64;
-                self.count += feats
+                count += feats
                     .actions
                     .iter()
                     .map(|x| x.all_features().count())
                     .sum::<usize>() as u64;
             }
+            Features::DenseSimple(feats) => count += feats.len() as u64,
+            Features::DenseSimpleRef(feats) => count += feats.len() as u64,
+            Features::MixedSimple(feats) => {
+                count += (feats.dense.len() + feats.sparse.all_features().count()) as u64;
+            }
+            Features::MixedSimpleRef(feats) => {
+                count += (feats.dense.len() + feats.sparse.all_features().count()) as u64;
+            }
         }
+        self.count += count;
+        self.accumulator.add(count as f32);
     }
 
     fn get_value(&self) -> MetricValue {
-        MetricValue::Int(self.count.try_into().unwrap())
+        count_value(self.count)
     }
 
     fn get_name(&self) -> String {
         "Parsed features".to_owned()
     }
+
+    fn get_description(&self) -> String {
+        "Total number of features parsed from the input".to_owned()
+    }
+
+    fn get_window_value(&self) -> Option<MetricValue> {
+        Some(
+            self.accumulator
+                .window_sum()
+                .map_or(MetricValue::Missing, |sum| count_value(sum as u64)),
+        )
+    }
+
+    fn get_since_last_value(&self) -> Option<MetricValue> {
+        Some(count_value(self.accumulator.since_last_sum() as u64))
+    }
+
+    fn mark_reported(&mut self) {
+        self.accumulator.reset_since_last();
+    }
+
+    fn set_window_size(&mut self, window_size: usize) {
+        self.accumulator.set_window_size(window_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed_features_values() {
+        let mut metric = ParsedFeaturesMetric::new();
+        assert!(matches!(
+            metric.get_window_value(),
+            Some(MetricValue::Missing)
+        ));
+        metric.set_window_size(2);
+        assert!(matches!(
+            metric.get_window_value(),
+            Some(MetricValue::Int(0))
+        ));
+
+        assert!(matches!(
+            count_value(16_777_217),
+            MetricValue::Int(16_777_217)
+        ));
+        assert!(matches!(count_value(1 << 40), MetricValue::Float(_)));
+    }
 }
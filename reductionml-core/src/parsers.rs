@@ -7,26 +7,182 @@ mod dsjson_parser;
 pub use dsjson_parser::*;
 mod json_parser;
 pub use json_parser::*;
+mod csv_parser;
+pub use csv_parser::*;
+mod cache;
+pub use cache::*;
+#[cfg(feature = "columnar")]
+mod columnar_parser;
+#[cfg(feature = "columnar")]
+pub use columnar_parser::*;
 
-use crate::{hash::hash_bytes, FeatureHash, NamespaceHash};
+use std::borrow::Cow;
 
+use serde_json_borrow::Value;
+
+use crate::{
+    hash::HashFunction,
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    FeatureHash, FeatureMask, NamespaceHash,
+};
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ParsedFeature<'a> {
-    Simple { name: &'a str },
-    SimpleWithStringValue { name: &'a str, value: &'a str },
-    Anonymous { offset: u32 },
+    Simple {
+        name: Cow<'a, str>,
+    },
+    SimpleWithStringValue {
+        name: Cow<'a, str>,
+        value: Cow<'a, str>,
+    },
+    Anonymous {
+        offset: u32,
+    },
 }
 
 impl<'a> ParsedFeature<'a> {
-    pub fn hash(&self, namespace_hash: NamespaceHash) -> FeatureHash {
+    pub fn hash(&self, namespace_hash: NamespaceHash, hash_function: HashFunction) -> FeatureHash {
         match &self {
             ParsedFeature::Simple { name } => {
-                FeatureHash::from(hash_bytes(name.as_bytes(), *namespace_hash))
+                FeatureHash::from(hash_function.hash_bytes(name.as_bytes(), *namespace_hash))
             }
             ParsedFeature::SimpleWithStringValue { name, value } => {
-                let name_key_hash = hash_bytes(name.as_bytes(), *namespace_hash);
-                FeatureHash::from(hash_bytes(value.as_bytes(), name_key_hash))
+                let name_key_hash = hash_function.hash_bytes(name.as_bytes(), *namespace_hash);
+                FeatureHash::from(hash_function.hash_bytes(value.as_bytes(), name_key_hash))
             }
-            ParsedFeature::Anonymous { offset } => (*namespace_hash + offset).into(),
+            ParsedFeature::Anonymous { offset } => namespace_hash.wrapping_add(*offset).into(),
+        }
+    }
+}
+
+/// Receives the features of an example as a parser walks its input, so that
+/// the same walk can either hash the features or collect their names.
+pub(crate) trait FeatureSink<'a> {
+    /// Features added after this go into the named namespace until it is
+    /// popped.
+    fn push_namespace(&mut self, name: Cow<'a, str>);
+    fn pop_namespace(&mut self);
+    fn add_feature(&mut self, feature: ParsedFeature<'a>, value: f32);
+}
+
+/// Hashes features into [`SparseFeatures`].
+pub(crate) struct HashingSink<'s, 'a> {
+    output: &'s mut SparseFeatures,
+    namespaces: Vec<(Namespace, Cow<'a, str>)>,
+    hash_seed: u32,
+    hash_function: HashFunction,
+    mask: FeatureMask,
+    keep_empty_namespaces: bool,
+}
+
+impl<'s, 'a> HashingSink<'s, 'a> {
+    pub(crate) fn new(
+        output: &'s mut SparseFeatures,
+        hash_seed: u32,
+        hash_function: HashFunction,
+        num_bits: u8,
+    ) -> HashingSink<'s, 'a> {
+        HashingSink {
+            output,
+            namespaces: Vec::new(),
+            hash_seed,
+            hash_function,
+            mask: FeatureMask::from_num_bits(num_bits),
+            keep_empty_namespaces: false,
+        }
+    }
+
+    /// Creates every pushed namespace, even if no features are added to it.
+    pub(crate) fn keep_empty_namespaces(mut self) -> HashingSink<'s, 'a> {
+        self.keep_empty_namespaces = true;
+        self
+    }
+}
+
+impl<'a> FeatureSink<'a> for HashingSink<'_, 'a> {
+    fn push_namespace(&mut self, name: Cow<'a, str>) {
+        let namespace = Namespace::from_name(&name, self.hash_seed, self.hash_function);
+        if self.keep_empty_namespaces {
+            self.output
+                .get_or_create_named_namespace(namespace, name.as_ref());
+        }
+        self.namespaces.push((namespace, name));
+    }
+
+    fn pop_namespace(&mut self) {
+        self.namespaces.pop();
+    }
+
+    fn add_feature(&mut self, feature: ParsedFeature<'a>, value: f32) {
+        let (namespace, name) = self
+            .namespaces
+            .last()
+            .expect("features are always added to a namespace");
+        let hash = feature
+            .hash(namespace.hash(), self.hash_function)
+            .mask(self.mask);
+        self.output
+            .get_or_create_named_namespace(*namespace, name.as_ref())
+            .add_feature(hash, value);
+    }
+}
+
+/// Unlike `Value::get`, the returned value keeps the lifetime of the parsed
+/// input so that feature names can borrow from it.
+pub(crate) fn field<'v, 'a>(value: &'v Value<'a>, key: &str) -> &'v Value<'a> {
+    const NULL: &Value = &Value::Null;
+    match value {
+        Value::Object(obj) => obj.iter().find(|(k, _)| *k == key).map_or(NULL, |(_, v)| v),
+        _ => NULL,
+    }
+}
+
+/// Collects the names of features, as returned by
+/// [`TextModeParser::extract_feature_names`].
+#[derive(Default)]
+pub(crate) struct NameSink<'a> {
+    names: FeatureNames<'a>,
+    namespaces: Vec<ParsedNamespaceInfo<'a>>,
+}
+
+impl<'a> NameSink<'a> {
+    /// Starts from names which were already collected, such as the shared
+    /// features of a multi-line example.
+    pub(crate) fn with_names(names: FeatureNames<'a>) -> NameSink<'a> {
+        NameSink {
+            names,
+            namespaces: Vec::new(),
         }
     }
+
+    pub(crate) fn into_names(self) -> FeatureNames<'a> {
+        self.names
+    }
+}
+
+impl<'a> FeatureSink<'a> for NameSink<'a> {
+    fn push_namespace(&mut self, name: Cow<'a, str>) {
+        // The same names map to the default namespace as in
+        // `Namespace::from_name`.
+        let namespace = match name.as_ref() {
+            " " | ":default" => ParsedNamespaceInfo::Default,
+            _ => ParsedNamespaceInfo::Named(name),
+        };
+        self.namespaces.push(namespace);
+    }
+
+    fn pop_namespace(&mut self) {
+        self.namespaces.pop();
+    }
+
+    fn add_feature(&mut self, feature: ParsedFeature<'a>, _value: f32) {
+        let namespace = self
+            .namespaces
+            .last()
+            .expect("features are always added to a namespace");
+        self.names
+            .entry(namespace.clone())
+            .or_default()
+            .push(feature);
+    }
 }
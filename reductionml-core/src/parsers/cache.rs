@@ -0,0 +1,624 @@
+//! Binary cache of hashed examples.
+//!
+//! Examples are stored after hashing, as the feature indices and values of
+//! each namespace along with the label, so reading them back involves no
+//! string parsing or hashing. Because the indices are already masked, a cache
+//! can only be used with the number of bits, hash seed and hash function it
+//! was created with. These are recorded in its [`CacheHeader`] along with the
+//! features and label types of the examples.
+//!
+//! The cache starts with the header, followed by each example prefixed by its
+//! length in bytes. Each example holds its label, features and then its
+//! [`ExampleMetadata`]. All numbers are little endian.
+
+use std::io::{Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::DateTime;
+
+use crate::{
+    error::{Error, Result},
+    hash::HashFunction,
+    object_pool::Pool,
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    utils::AsInner,
+    BinaryLabel, CBAdfFeatures, CBLabel, ExampleMetadata, FeatureIndex, Features, FeaturesType,
+    Label, LabelType, NamespaceHash, SimpleLabel,
+};
+
+const MAGIC: &[u8; 8] = b"REMLCACH";
+const VERSION: u32 = 4;
+
+/// Examples longer than this are assumed to come from a corrupt cache rather
+/// than being allocated.
+const MAX_EXAMPLE_LENGTH: usize = 1 << 28;
+
+const NO_LABEL: u8 = 0;
+const SIMPLE_LABEL: u8 = 1;
+const BINARY_LABEL: u8 = 2;
+const CB_LABEL: u8 = 3;
+
+const SPARSE_SIMPLE: u8 = 0;
+const SPARSE_CB_ADF: u8 = 1;
+
+// Flags for which metadata fields are present.
+const HAS_ID: u8 = 1;
+const HAS_TAG: u8 = 2;
+const HAS_WEIGHT: u8 = 4;
+const HAS_TIMESTAMP: u8 = 8;
+
+const DEFAULT_NAMESPACE: u8 = 0;
+const NAMED_NAMESPACE: u8 = 1;
+const RAW_HASH_NAMESPACE: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheHeader {
+    pub num_bits: u8,
+    pub hash_seed: u32,
+    pub hash_function: HashFunction,
+    pub features_type: FeaturesType,
+    pub label_type: LabelType,
+}
+
+impl CacheHeader {
+    fn write(&self, output: &mut impl Write) -> Result<()> {
+        output.write_all(MAGIC)?;
+        output.write_u32::<LittleEndian>(VERSION)?;
+        output.write_u8(self.num_bits)?;
+        output.write_u32::<LittleEndian>(self.hash_seed)?;
+        output.write_u8(match self.hash_function {
+            HashFunction::Xxh3 => 0,
+            HashFunction::Murmur3 => 1,
+        })?;
+        output.write_u8(match self.features_type {
+            FeaturesType::SparseSimple => SPARSE_SIMPLE,
+            FeaturesType::SparseCBAdf => SPARSE_CB_ADF,
+            FeaturesType::DenseSimple | FeaturesType::MixedSimple => {
+                return Err(Error::InvalidArgument(
+                    "Only sparse features can be written to a cache".to_owned(),
+                ))
+            }
+        })?;
+        output.write_u8(match self.label_type {
+            LabelType::Simple => SIMPLE_LABEL,
+            LabelType::Binary => BINARY_LABEL,
+            LabelType::CB => CB_LABEL,
+        })?;
+        Ok(())
+    }
+
+    fn read(input: &mut dyn Read) -> Result<CacheHeader> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::ParserError("Input is not a cache file".to_owned()));
+        }
+        let version = input.read_u32::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(Error::ParserError(format!(
+                "Unsupported cache version {}, expected {}",
+                version, VERSION
+            )));
+        }
+        let num_bits = input.read_u8()?;
+        let hash_seed = input.read_u32::<LittleEndian>()?;
+        let hash_function = match input.read_u8()? {
+            0 => HashFunction::Xxh3,
+            1 => HashFunction::Murmur3,
+            x => {
+                return Err(Error::ParserError(format!(
+                    "Unknown hash function {} in cache header",
+                    x
+                )))
+            }
+        };
+        let features_type = match input.read_u8()? {
+            SPARSE_SIMPLE => FeaturesType::SparseSimple,
+            SPARSE_CB_ADF => FeaturesType::SparseCBAdf,
+            x => {
+                return Err(Error::ParserError(format!(
+                    "Unknown features type {} in cache header",
+                    x
+                )))
+            }
+        };
+        let label_type = match input.read_u8()? {
+            SIMPLE_LABEL => LabelType::Simple,
+            BINARY_LABEL => LabelType::Binary,
+            CB_LABEL => LabelType::CB,
+            x => {
+                return Err(Error::ParserError(format!(
+                    "Unknown label type {} in cache header",
+                    x
+                )))
+            }
+        };
+        Ok(CacheHeader {
+            num_bits,
+            hash_seed,
+            hash_function,
+            features_type,
+            label_type,
+        })
+    }
+}
+
+fn write_sparse_features(output: &mut Vec<u8>, features: &SparseFeatures) -> Result<()> {
+    // Namespaces returned to the pool stay in the map but are inactive.
+    let active: Vec<_> = features
+        .namespaces()
+        .filter_map(|(namespace, _)| {
+            features
+                .get_namespace(namespace)
+                .map(|feats| (namespace, feats))
+        })
+        .collect();
+    output.write_u32::<LittleEndian>(active.len() as u32)?;
+    for (namespace, feats) in active {
+        let (kind, hash) = match namespace {
+            Namespace::Default => (DEFAULT_NAMESPACE, 0),
+            Namespace::Named(hash) => (NAMED_NAMESPACE, *hash),
+            Namespace::RawHash(hash) => (RAW_HASH_NAMESPACE, *hash),
+        };
+        output.write_u8(kind)?;
+        output.write_u32::<LittleEndian>(hash)?;
+        // Names are kept so that interactions can match namespaces by prefix.
+        // Namespaces without one have an empty name.
+        write_string(output, feats.name().unwrap_or_default())?;
+        output.write_u32::<LittleEndian>(feats.size() as u32)?;
+        for (index, value) in feats.indices().iter().zip(feats.values()) {
+            output.write_u32::<LittleEndian>(**index)?;
+            output.write_f32::<LittleEndian>(*value)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_sparse_features(input: &mut &[u8], output: &mut SparseFeatures) -> Result<()> {
+    let num_namespaces = input.read_u32::<LittleEndian>()?;
+    for _ in 0..num_namespaces {
+        let kind = input.read_u8()?;
+        let hash = NamespaceHash::from(input.read_u32::<LittleEndian>()?);
+        let namespace = match kind {
+            DEFAULT_NAMESPACE => Namespace::Default,
+            NAMED_NAMESPACE => Namespace::Named(hash),
+            RAW_HASH_NAMESPACE => Namespace::RawHash(hash),
+            x => {
+                return Err(Error::ParserError(format!(
+                    "Unknown namespace kind {} in cache",
+                    x
+                )))
+            }
+        };
+        let name = read_str(input)?;
+        let num_features = input.read_u32::<LittleEndian>()? as usize;
+        let ns = if name.is_empty() {
+            output.get_or_create_namespace_with_capacity(namespace, num_features)
+        } else {
+            output.get_or_create_named_namespace(namespace, name)
+        };
+        for _ in 0..num_features {
+            let index = FeatureIndex::from(input.read_u32::<LittleEndian>()?);
+            ns.add_feature(index, input.read_f32::<LittleEndian>()?);
+        }
+    }
+    Ok(())
+}
+
+fn write_string(output: &mut Vec<u8>, value: &str) -> Result<()> {
+    output.write_u32::<LittleEndian>(value.len() as u32)?;
+    output.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn read_str<'a>(input: &mut &'a [u8]) -> Result<&'a str> {
+    let length = input.read_u32::<LittleEndian>()? as usize;
+    if input.len() < length {
+        return Err(Error::ParserError("Truncated string in cache".to_owned()));
+    }
+    let (bytes, rest) = input.split_at(length);
+    *input = rest;
+    std::str::from_utf8(bytes).map_err(|_| Error::ParserError("Invalid string in cache".to_owned()))
+}
+
+fn write_metadata(output: &mut Vec<u8>, metadata: &ExampleMetadata) -> Result<()> {
+    let flags = (metadata.id.is_some() as u8 * HAS_ID)
+        | (metadata.tag.is_some() as u8 * HAS_TAG)
+        | (metadata.weight.is_some() as u8 * HAS_WEIGHT)
+        | (metadata.timestamp.is_some() as u8 * HAS_TIMESTAMP);
+    output.write_u8(flags)?;
+    if let Some(id) = &metadata.id {
+        write_string(output, id)?;
+    }
+    if let Some(tag) = &metadata.tag {
+        write_string(output, tag)?;
+    }
+    if let Some(weight) = metadata.weight {
+        output.write_f32::<LittleEndian>(weight)?;
+    }
+    if let Some(timestamp) = metadata.timestamp {
+        output.write_i64::<LittleEndian>(timestamp.timestamp())?;
+        output.write_u32::<LittleEndian>(timestamp.timestamp_subsec_nanos())?;
+    }
+    Ok(())
+}
+
+fn read_metadata(input: &mut &[u8]) -> Result<ExampleMetadata> {
+    let flags = input.read_u8()?;
+    let mut metadata = ExampleMetadata::default();
+    if flags & HAS_ID != 0 {
+        metadata.id = Some(read_str(input)?.to_owned());
+    }
+    if flags & HAS_TAG != 0 {
+        metadata.tag = Some(read_str(input)?.to_owned());
+    }
+    if flags & HAS_WEIGHT != 0 {
+        metadata.weight = Some(input.read_f32::<LittleEndian>()?);
+    }
+    if flags & HAS_TIMESTAMP != 0 {
+        let seconds = input.read_i64::<LittleEndian>()?;
+        let nanos = input.read_u32::<LittleEndian>()?;
+        metadata.timestamp = Some(
+            DateTime::from_timestamp(seconds, nanos)
+                .ok_or_else(|| Error::ParserError("Invalid timestamp in cache".to_owned()))?,
+        );
+    }
+    Ok(metadata)
+}
+
+/// Writes examples to a cache.
+pub struct CacheWriter<W: Write> {
+    output: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> CacheWriter<W> {
+    pub fn new(mut output: W, header: CacheHeader) -> Result<CacheWriter<W>> {
+        header.write(&mut output)?;
+        Ok(CacheWriter {
+            output,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Only sparse features, simple or CB ADF, can be cached.
+    pub fn write_example(
+        &mut self,
+        features: &Features,
+        label: Option<&Label>,
+        metadata: &ExampleMetadata,
+    ) -> Result<()> {
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        match label {
+            None => buffer.write_u8(NO_LABEL)?,
+            Some(Label::Simple(label)) => {
+                buffer.write_u8(SIMPLE_LABEL)?;
+                buffer.write_f32::<LittleEndian>(label.value())?;
+                buffer.write_f32::<LittleEndian>(label.weight())?;
+            }
+            Some(Label::Binary(label)) => {
+                buffer.write_u8(BINARY_LABEL)?;
+                buffer.write_u8(label.0 as u8)?;
+            }
+            Some(Label::CB(label)) => {
+                buffer.write_u8(CB_LABEL)?;
+                buffer.write_u32::<LittleEndian>(label.action as u32)?;
+                buffer.write_f32::<LittleEndian>(label.cost)?;
+                buffer.write_f32::<LittleEndian>(label.probability)?;
+            }
+        }
+
+        if let Some(features) = AsInner::<SparseFeatures>::as_inner(features) {
+            buffer.write_u8(SPARSE_SIMPLE)?;
+            write_sparse_features(buffer, features)?;
+        } else if let Some(features) = AsInner::<CBAdfFeatures>::as_inner(features) {
+            buffer.write_u8(SPARSE_CB_ADF)?;
+            buffer.write_u8(features.shared.is_some() as u8)?;
+            if let Some(shared) = &features.shared {
+                write_sparse_features(buffer, shared)?;
+            }
+            buffer.write_u32::<LittleEndian>(features.actions.len() as u32)?;
+            for action in &features.actions {
+                write_sparse_features(buffer, action)?;
+            }
+        } else {
+            return Err(Error::InvalidArgument(
+                "Only sparse features can be written to a cache".to_owned(),
+            ));
+        }
+        write_metadata(buffer, metadata)?;
+        if buffer.len() > MAX_EXAMPLE_LENGTH {
+            return Err(Error::InvalidArgument(format!(
+                "Example is {} bytes, which is more than the {} bytes a cache can hold",
+                buffer.len(),
+                MAX_EXAMPLE_LENGTH
+            )));
+        }
+
+        self.output
+            .write_u32::<LittleEndian>(self.buffer.len() as u32)?;
+        self.output.write_all(&self.buffer)?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+/// Reads examples from a cache. Like a [`super::TextModeParser`], reading the
+/// next example and decoding it are separate steps.
+pub struct CacheReader {
+    header: CacheHeader,
+    pool: std::sync::Arc<Pool<SparseFeatures>>,
+}
+
+impl CacheReader {
+    /// Reads the header from the start of the input.
+    pub fn new(
+        input: &mut dyn Read,
+        pool: std::sync::Arc<Pool<SparseFeatures>>,
+    ) -> Result<CacheReader> {
+        Ok(CacheReader {
+            header: CacheHeader::read(input)?,
+            pool,
+        })
+    }
+
+    pub fn header(&self) -> &CacheHeader {
+        &self.header
+    }
+
+    pub fn get_next_chunk(
+        &self,
+        input: &mut dyn Read,
+        mut output_buffer: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>> {
+        let mut length = [0; 4];
+        let mut read = 0;
+        while read < length.len() {
+            match input.read(&mut length[read..])? {
+                0 if read == 0 => return Ok(None),
+                0 => {
+                    return Err(Error::ParserError(
+                        "Cache ends part way through an example".to_owned(),
+                    ))
+                }
+                n => read += n,
+            }
+        }
+        let length = u32::from_le_bytes(length) as usize;
+        if length > MAX_EXAMPLE_LENGTH {
+            return Err(Error::ParserError(format!(
+                "Example length {} in cache is too large, the cache may be corrupt",
+                length
+            )));
+        }
+        output_buffer.clear();
+        output_buffer.resize(length, 0);
+        input.read_exact(&mut output_buffer)?;
+        Ok(Some(output_buffer))
+    }
+
+    pub fn parse_chunk<'b>(
+        &self,
+        chunk: &[u8],
+    ) -> Result<(Features<'b>, Option<Label>, ExampleMetadata)> {
+        let mut input = chunk;
+        let label = match input.read_u8()? {
+            NO_LABEL => None,
+            SIMPLE_LABEL => Some(
+                SimpleLabel::new(
+                    input.read_f32::<LittleEndian>()?,
+                    input.read_f32::<LittleEndian>()?,
+                )
+                .into(),
+            ),
+            BINARY_LABEL => Some(BinaryLabel(input.read_u8()? != 0).into()),
+            CB_LABEL => Some(
+                CBLabel {
+                    action: input.read_u32::<LittleEndian>()? as usize,
+                    cost: input.read_f32::<LittleEndian>()?,
+                    probability: input.read_f32::<LittleEndian>()?,
+                }
+                .into(),
+            ),
+            x => {
+                return Err(Error::ParserError(format!(
+                    "Unknown label kind {} in cache",
+                    x
+                )))
+            }
+        };
+
+        let features = match input.read_u8()? {
+            SPARSE_SIMPLE => {
+                let mut features = self.pool.get_object();
+                read_sparse_features(&mut input, &mut features)?;
+                features.into()
+            }
+            SPARSE_CB_ADF => {
+                let shared = match input.read_u8()? {
+                    0 => None,
+                    _ => {
+                        let mut shared = self.pool.get_object();
+                        read_sparse_features(&mut input, &mut shared)?;
+                        Some(shared)
+                    }
+                };
+                let num_actions = input.read_u32::<LittleEndian>()?;
+                let mut actions = Vec::with_capacity(num_actions as usize);
+                for _ in 0..num_actions {
+                    let mut action = self.pool.get_object();
+                    read_sparse_features(&mut input, &mut action)?;
+                    actions.push(action);
+                }
+                CBAdfFeatures { shared, actions }.into()
+            }
+            x => {
+                return Err(Error::ParserError(format!(
+                    "Unknown features kind {} in cache",
+                    x
+                )))
+            }
+        };
+        let metadata = read_metadata(&mut input)?;
+        Ok((features, label, metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::{
+        parsers::{TextModeParser, TextModeParserFactory, VwTextParserFactory},
+        FeaturesType, LabelType,
+    };
+
+    fn namespace_names(features: &Features) -> Vec<(Namespace, Option<String>)> {
+        let sparse: Vec<&SparseFeatures> = match features {
+            Features::SparseSimple(features) => vec![features],
+            Features::SparseCBAdf(features) => {
+                features.shared.iter().chain(&features.actions).collect()
+            }
+            _ => unreachable!(),
+        };
+        sparse
+            .into_iter()
+            .flat_map(|features| {
+                features.namespaces().filter_map(|(namespace, _)| {
+                    let feats = features.get_namespace(namespace)?;
+                    Some((namespace, feats.name().map(str::to_owned)))
+                })
+            })
+            .collect()
+    }
+
+    fn round_trip(features_type: FeaturesType, label_type: LabelType, input: &str) {
+        let pool = Arc::new(Pool::new());
+        let parser = VwTextParserFactory.create(
+            features_type,
+            label_type,
+            0,
+            HashFunction::default(),
+            18,
+            pool.clone(),
+        );
+        let header = CacheHeader {
+            num_bits: 18,
+            hash_seed: 0,
+            hash_function: HashFunction::default(),
+            features_type,
+            label_type,
+        };
+        let mut writer = CacheWriter::new(Vec::new(), header).unwrap();
+        let mut expected = Vec::new();
+        let mut text_input = input.as_bytes();
+        while let Some(chunk) = parser
+            .get_next_chunk(&mut text_input, String::new())
+            .unwrap()
+        {
+            let (features, label, mut metadata) = parser.parse_chunk(&chunk).unwrap();
+            metadata.id = Some(format!("example-{}", expected.len()));
+            metadata.timestamp = DateTime::from_timestamp(1_600_000_000, 123_456_789);
+            writer
+                .write_example(&features, label.as_ref(), &metadata)
+                .unwrap();
+            expected.push((features, label, metadata));
+        }
+
+        let data = writer.into_inner();
+        let mut cache_input = data.as_slice();
+        let reader = CacheReader::new(&mut cache_input, pool).unwrap();
+        assert_eq!(reader.header(), &header);
+        let mut actual = Vec::new();
+        while let Some(chunk) = reader.get_next_chunk(&mut cache_input, Vec::new()).unwrap() {
+            actual.push(reader.parse_chunk(&chunk).unwrap());
+        }
+
+        assert_eq!(actual.len(), expected.len());
+        for ((features, label, metadata), (expected_features, expected_label, expected_metadata)) in
+            actual.iter().zip(&expected)
+        {
+            assert_abs_diff_eq!(features, expected_features);
+            assert_eq!(
+                namespace_names(features),
+                namespace_names(expected_features)
+            );
+            assert_eq!(metadata, expected_metadata);
+            assert_eq!(
+                serde_json::to_value(label).unwrap(),
+                serde_json::to_value(expected_label).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn cache_round_trip_simple() {
+        round_trip(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            "1 2 'tag|a x y:2.5 |b z\n|a x\n0.5 | 3 4\n",
+        );
+    }
+
+    #[test]
+    fn cache_round_trip_cb_adf() {
+        round_trip(
+            FeaturesType::SparseCBAdf,
+            LabelType::CB,
+            "shared | s\n0:1.0:0.5 |a x\n|a y\n\n| z\n\n",
+        );
+    }
+
+    #[test]
+    fn cache_errors() {
+        let pool = Arc::new(Pool::new());
+        assert!(matches!(
+            CacheReader::new(&mut "not a cache".as_bytes(), pool.clone()),
+            Err(Error::ParserError(_))
+        ));
+
+        let header = CacheHeader {
+            num_bits: 18,
+            hash_seed: 0,
+            hash_function: HashFunction::default(),
+            features_type: FeaturesType::SparseSimple,
+            label_type: LabelType::Simple,
+        };
+        let mut data = CacheWriter::new(Vec::new(), header).unwrap().into_inner();
+        data.extend([5, 0]);
+        let mut input = data.as_slice();
+        let reader = CacheReader::new(&mut input, pool.clone()).unwrap();
+        assert!(matches!(
+            reader.get_next_chunk(&mut input, Vec::new()),
+            Err(Error::ParserError(_))
+        ));
+
+        // A corrupt length is not allocated.
+        let mut data = CacheWriter::new(Vec::new(), header).unwrap().into_inner();
+        data.extend([0xff; 4]);
+        let mut input = data.as_slice();
+        let reader = CacheReader::new(&mut input, pool).unwrap();
+        assert!(matches!(
+            reader.get_next_chunk(&mut input, Vec::new()),
+            Err(Error::ParserError(_))
+        ));
+
+        assert!(matches!(
+            CacheWriter::new(
+                Vec::new(),
+                CacheHeader {
+                    features_type: FeaturesType::DenseSimple,
+                    ..header
+                }
+            ),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,556 @@
+//! Reads examples from Arrow IPC and Parquet files.
+//!
+//! Unlike the text parsers, which work on one chunk of text per example, the
+//! columnar parser produces the examples of a whole record batch at once. A
+//! [`ColumnMapping`] says which columns hold features of which namespace and
+//! which hold the label.
+//!
+//! Each column produces features in the same way as the equivalent JSON, so
+//! a model trained on one can be used with the other:
+//! - A number is a feature named after the column with that value.
+//! - A boolean is a feature named after the column with value 1 when true.
+//! - A string is a feature named after the column and the string, like
+//!   `{"column": "value"}`.
+//! - A list of numbers uses the namespace hash plus the position in the list,
+//!   like a JSON array of numbers.
+//! - A list of strings has a feature named after each string.
+//!
+//! Null values produce no features.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::File,
+    io::{Cursor, Read, Seek},
+    sync::Arc,
+};
+
+use arrow_array::{
+    cast::AsArray, types::Float32Type, Array, ArrayRef, RecordBatch, RecordBatchReader,
+};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field};
+use bytes::Bytes;
+use parquet::{arrow::arrow_reader::ParquetRecordBatchReaderBuilder, file::reader::ChunkReader};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compression::{decompress_reader, Compression},
+    error::{Error, Result},
+    hash::HashFunction,
+    object_pool::Pool,
+    parsers::ParsedFeature,
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    BinaryLabel, FeatureMask, Features, FeaturesType, Label, LabelType, SimpleLabel,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ColumnMapping {
+    /// Namespace name to the columns whose values are features in it.
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, Vec<String>>,
+    /// Column containing the label. Examples have no label when this is not
+    /// given or the value is null.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Column containing the weight of a simple label. Defaults to 1 when this
+    /// is not given or the value is null.
+    #[serde(default)]
+    pub weight: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColumnarFormat {
+    /// Arrow IPC file format, also known as Feather V2.
+    ArrowIpc,
+    Parquet,
+}
+
+fn arrow_error(e: impl Display) -> Error {
+    Error::ParserError(e.to_string())
+}
+
+/// Reads the record batches of a file. Parquet files are read `batch_size`
+/// rows at a time, Arrow IPC files in the batches they were written with.
+///
+/// Both formats need random access, so a gzip or zstd compressed file is
+/// decompressed into memory first.
+pub fn read_record_batches(
+    mut file: File,
+    format: ColumnarFormat,
+    batch_size: usize,
+) -> Result<Box<dyn Iterator<Item = Result<RecordBatch>> + Send>> {
+    let mut header = Vec::new();
+    (&mut file).take(4).read_to_end(&mut header)?;
+    file.rewind()?;
+    let reader: Box<dyn RecordBatchReader + Send> = match Compression::detect(&header) {
+        Compression::None => match format {
+            ColumnarFormat::ArrowIpc => {
+                Box::new(arrow_ipc::reader::FileReader::try_new(file, None).map_err(arrow_error)?)
+            }
+            ColumnarFormat::Parquet => parquet_reader(file, batch_size)?,
+        },
+        _ => {
+            let mut data = Vec::new();
+            decompress_reader(file)?.read_to_end(&mut data)?;
+            match format {
+                ColumnarFormat::ArrowIpc => Box::new(
+                    arrow_ipc::reader::FileReader::try_new(Cursor::new(data), None)
+                        .map_err(arrow_error)?,
+                ),
+                ColumnarFormat::Parquet => parquet_reader(Bytes::from(data), batch_size)?,
+            }
+        }
+    };
+    Ok(Box::new(reader.map(|batch| batch.map_err(arrow_error))))
+}
+
+fn parquet_reader<T: ChunkReader + 'static>(
+    input: T,
+    batch_size: usize,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    Ok(Box::new(
+        ParquetRecordBatchReaderBuilder::try_new(input)
+            .map_err(arrow_error)?
+            .with_batch_size(batch_size)
+            .build()
+            .map_err(arrow_error)?,
+    ))
+}
+
+pub struct ColumnarParser {
+    namespaces: Vec<(Namespace, String, Vec<String>)>,
+    label: Option<String>,
+    weight: Option<String>,
+    label_type: LabelType,
+    hash_function: HashFunction,
+    num_bits: u8,
+    pool: Arc<Pool<SparseFeatures>>,
+}
+
+fn column_by_name<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| Error::ParserError(format!("Column {} not found", name)))
+}
+
+fn is_string(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => true,
+        DataType::Dictionary(_, value_type) => is_string(value_type),
+        _ => false,
+    }
+}
+
+impl ColumnarParser {
+    pub fn new(
+        mapping: &ColumnMapping,
+        features_type: FeaturesType,
+        label_type: LabelType,
+        hash_seed: u32,
+        hash_function: HashFunction,
+        num_bits: u8,
+        pool: Arc<Pool<SparseFeatures>>,
+    ) -> Result<ColumnarParser> {
+        if features_type != FeaturesType::SparseSimple {
+            return Err(Error::InvalidArgument(format!(
+                "Columnar input only supports SparseSimple features, not {:?}",
+                features_type
+            )));
+        }
+        match label_type {
+            LabelType::Simple => {}
+            LabelType::Binary if mapping.weight.is_none() => {}
+            LabelType::Binary => {
+                return Err(Error::InvalidArgument(
+                    "A weight column can only be used with simple labels".to_owned(),
+                ))
+            }
+            LabelType::CB => {
+                return Err(Error::InvalidArgument(
+                    "Columnar input does not support CB labels".to_owned(),
+                ))
+            }
+        }
+
+        Ok(ColumnarParser {
+            namespaces: mapping
+                .namespaces
+                .iter()
+                .map(|(name, columns)| {
+                    (
+                        Namespace::from_name(name, hash_seed, hash_function),
+                        name.clone(),
+                        columns.clone(),
+                    )
+                })
+                .collect(),
+            label: mapping.label.clone(),
+            weight: mapping.weight.clone(),
+            label_type,
+            hash_function,
+            num_bits,
+            pool,
+        })
+    }
+
+    /// Returns the examples of each row of the batch in order.
+    pub fn parse_batch<'b>(
+        &self,
+        batch: &RecordBatch,
+    ) -> Result<Vec<(Features<'b>, Option<Label>)>> {
+        let mut rows: Vec<SparseFeatures> = (0..batch.num_rows())
+            .map(|_| self.pool.get_object())
+            .collect();
+        for (namespace, namespace_name, columns) in &self.namespaces {
+            for column in columns {
+                self.add_column(
+                    &mut rows,
+                    *namespace,
+                    namespace_name,
+                    column,
+                    column_by_name(batch, column)?,
+                )?;
+            }
+        }
+        let labels = self.parse_labels(batch)?;
+        Ok(rows
+            .into_iter()
+            .zip(labels)
+            .map(|(features, label)| (features.into(), label))
+            .collect())
+    }
+
+    fn add_column(
+        &self,
+        rows: &mut [SparseFeatures],
+        namespace: Namespace,
+        namespace_name: &str,
+        name: &str,
+        array: &ArrayRef,
+    ) -> Result<()> {
+        let mask = FeatureMask::from_num_bits(self.num_bits);
+        let ns_hash = namespace.hash();
+        let index = ParsedFeature::Simple { name: name.into() }
+            .hash(ns_hash, self.hash_function)
+            .mask(mask);
+        match array.data_type() {
+            DataType::Boolean => {
+                for (row, value) in rows.iter_mut().zip(array.as_boolean().iter()) {
+                    if value == Some(true) {
+                        row.get_or_create_named_namespace(namespace, namespace_name)
+                            .add_feature(index, 1.0);
+                    }
+                }
+            }
+            data_type if data_type.is_numeric() => {
+                let values = cast(array, &DataType::Float32).map_err(arrow_error)?;
+                for (row, value) in rows
+                    .iter_mut()
+                    .zip(values.as_primitive::<Float32Type>().iter())
+                {
+                    if let Some(value) = value {
+                        row.get_or_create_named_namespace(namespace, namespace_name)
+                            .add_feature(index, value);
+                    }
+                }
+            }
+            data_type if is_string(data_type) => {
+                let values = cast(array, &DataType::Utf8).map_err(arrow_error)?;
+                for (row, value) in rows.iter_mut().zip(values.as_string::<i32>().iter()) {
+                    if let Some(value) = value {
+                        let feat = ParsedFeature::SimpleWithStringValue {
+                            name: name.into(),
+                            value: value.into(),
+                        };
+                        row.get_or_create_named_namespace(namespace, namespace_name)
+                            .add_feature(feat.hash(ns_hash, self.hash_function).mask(mask), 1.0);
+                    }
+                }
+            }
+            DataType::List(field)
+            | DataType::LargeList(field)
+            | DataType::FixedSizeList(field, _)
+                if field.data_type().is_numeric() =>
+            {
+                let item = Field::new("item", DataType::Float32, true);
+                let lists = cast(array, &DataType::List(Arc::new(item))).map_err(arrow_error)?;
+                for (row, values) in rows.iter_mut().zip(lists.as_list::<i32>().iter()) {
+                    let Some(values) = values else { continue };
+                    let ns = row.get_or_create_named_namespace(namespace, namespace_name);
+                    for (offset, value) in values.as_primitive::<Float32Type>().iter().enumerate() {
+                        if let Some(value) = value {
+                            let feat = ParsedFeature::Anonymous {
+                                offset: offset as u32,
+                            };
+                            ns.add_feature(
+                                feat.hash(ns_hash, self.hash_function).mask(mask),
+                                value,
+                            );
+                        }
+                    }
+                }
+            }
+            DataType::List(field)
+            | DataType::LargeList(field)
+            | DataType::FixedSizeList(field, _)
+                if is_string(field.data_type()) =>
+            {
+                let item = Field::new("item", DataType::Utf8, true);
+                let lists = cast(array, &DataType::List(Arc::new(item))).map_err(arrow_error)?;
+                for (row, values) in rows.iter_mut().zip(lists.as_list::<i32>().iter()) {
+                    let Some(values) = values else { continue };
+                    let ns = row.get_or_create_named_namespace(namespace, namespace_name);
+                    for name in values.as_string::<i32>().iter().flatten() {
+                        let feat = ParsedFeature::Simple { name: name.into() };
+                        ns.add_feature(feat.hash(ns_hash, self.hash_function).mask(mask), 1.0);
+                    }
+                }
+            }
+            data_type => {
+                return Err(Error::ParserError(format!(
+                    "Column {} has unsupported type {}",
+                    name, data_type
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_labels(&self, batch: &RecordBatch) -> Result<Vec<Option<Label>>> {
+        let label = match &self.label {
+            Some(label) => column_by_name(batch, label)?,
+            None => return Ok(vec![None; batch.num_rows()]),
+        };
+        match self.label_type {
+            LabelType::Simple => {
+                let values = cast(label, &DataType::Float32).map_err(arrow_error)?;
+                let weights = match &self.weight {
+                    Some(weight) => Some(
+                        cast(column_by_name(batch, weight)?, &DataType::Float32)
+                            .map_err(arrow_error)?,
+                    ),
+                    None => None,
+                };
+                let weights = weights.as_ref().map(|x| x.as_primitive::<Float32Type>());
+                Ok(values
+                    .as_primitive::<Float32Type>()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        let weight = weights
+                            .filter(|x| x.is_valid(i))
+                            .map_or(1.0, |x| x.value(i));
+                        value.map(|value| SimpleLabel::new(value, weight).into())
+                    })
+                    .collect())
+            }
+            LabelType::Binary => {
+                let values = cast(label, &DataType::Boolean).map_err(arrow_error)?;
+                Ok(values
+                    .as_boolean()
+                    .iter()
+                    .map(|value| value.map(|value| BinaryLabel(value).into()))
+                    .collect())
+            }
+            LabelType::CB => unreachable!("Rejected when the parser is created"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use arrow_array::{
+        builder::{ListBuilder, StringBuilder},
+        BooleanArray, DictionaryArray, FixedSizeListArray, Float64Array, Int32Array, StringArray,
+    };
+    use arrow_schema::Schema;
+    use parquet::arrow::ArrowWriter;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        parsers::{JsonParserFactory, TextModeParser, TextModeParserFactory},
+        utils::AsInner,
+    };
+
+    fn mapping() -> ColumnMapping {
+        serde_json::from_value(json!({
+            "namespaces": {
+                "a": ["age", "active", "country"],
+                "b": ["embedding"],
+                "c": ["tags"]
+            },
+            "label": "y",
+            "weight": "w"
+        }))
+        .unwrap()
+    }
+
+    fn batch() -> RecordBatch {
+        let mut tags = ListBuilder::new(StringBuilder::new());
+        tags.append_value([Some("x"), Some("y")]);
+        tags.append_null();
+        let embedding = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            [Some([Some(0.5), Some(1.5)]), Some([Some(2.0), None])],
+            2,
+        );
+        let country: DictionaryArray<arrow_array::types::Int8Type> =
+            ["nz", "us"].into_iter().collect();
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("age", Arc::new(Int32Array::from(vec![Some(30), None]))),
+            ("active", Arc::new(BooleanArray::from(vec![true, false]))),
+            ("country", Arc::new(country)),
+            ("embedding", Arc::new(embedding)),
+            ("tags", Arc::new(tags.finish())),
+            ("y", Arc::new(Float64Array::from(vec![Some(1.0), None]))),
+            ("w", Arc::new(Float64Array::from(vec![None, Some(2.0)]))),
+            ("unused", Arc::new(StringArray::from(vec!["p", "q"]))),
+        ];
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    fn parser() -> ColumnarParser {
+        ColumnarParser::new(
+            &mapping(),
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        )
+        .unwrap()
+    }
+
+    fn json_features(input: &str) -> SparseFeatures {
+        let parser = JsonParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (features, _, _) = parser.parse_chunk(input).unwrap();
+        sparse(&features).clone()
+    }
+
+    fn sparse<'a>(features: &'a Features) -> &'a SparseFeatures {
+        features.as_inner().unwrap()
+    }
+
+    fn check_batch(examples: &[(Features, Option<Label>)]) {
+        assert_eq!(examples.len(), 2);
+        let expected = json_features(
+            r#"{"features": {
+                "a": {"age": 30, "active": true, "country": "nz"},
+                "b": [0.5, 1.5],
+                "c": ["x", "y"]
+            }}"#,
+        );
+        assert_abs_diff_eq!(sparse(&examples[0].0), &expected);
+        let label: &SimpleLabel = examples[0].1.as_ref().unwrap().as_inner().unwrap();
+        assert_eq!((label.value(), label.weight()), (1.0, 1.0));
+
+        // The null in the embedding is skipped without moving later values.
+        let expected = json_features(
+            r#"{"features": {
+                "a": {"country": "us"},
+                "b": [2.0]
+            }}"#,
+        );
+        assert_abs_diff_eq!(sparse(&examples[1].0), &expected);
+        assert!(examples[1].1.is_none());
+    }
+
+    #[test]
+    fn columnar_features_match_json() {
+        check_batch(&parser().parse_batch(&batch()).unwrap());
+    }
+
+    #[test]
+    fn columnar_read_parquet_and_arrow() {
+        let batch = batch();
+        let dir = std::env::temp_dir().join(format!("reductionml-columnar-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let parquet_path = dir.join("data.parquet");
+        let mut writer =
+            ArrowWriter::try_new(File::create(&parquet_path).unwrap(), batch.schema(), None)
+                .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let arrow_path = dir.join("data.arrow");
+        let mut writer = arrow_ipc::writer::FileWriter::try_new(
+            File::create(&arrow_path).unwrap(),
+            &Schema::clone(&batch.schema()),
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        // Compressed files are decompressed into memory.
+        let compressed_parquet_path = dir.join("data.parquet.gz");
+        let mut writer = crate::compression::create_data_file(&compressed_parquet_path).unwrap();
+        std::io::copy(&mut File::open(&parquet_path).unwrap(), &mut writer).unwrap();
+        writer.finish().unwrap();
+        let compressed_arrow_path = dir.join("data.arrow.zst");
+        let mut writer = crate::compression::create_data_file(&compressed_arrow_path).unwrap();
+        std::io::copy(&mut File::open(&arrow_path).unwrap(), &mut writer).unwrap();
+        writer.finish().unwrap();
+
+        let parser = parser();
+        for (path, format) in [
+            (&parquet_path, ColumnarFormat::Parquet),
+            (&arrow_path, ColumnarFormat::ArrowIpc),
+            (&compressed_parquet_path, ColumnarFormat::Parquet),
+            (&compressed_arrow_path, ColumnarFormat::ArrowIpc),
+        ] {
+            let batches: Vec<RecordBatch> =
+                read_record_batches(File::open(path).unwrap(), format, 1024)
+                    .unwrap()
+                    .collect::<Result<_>>()
+                    .unwrap();
+            assert_eq!(batches.len(), 1);
+            check_batch(&parser.parse_batch(&batches[0]).unwrap());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn columnar_errors() {
+        let mut mapping = mapping();
+        mapping
+            .namespaces
+            .insert("d".to_owned(), vec!["missing".to_owned()]);
+        let parser = ColumnarParser::new(
+            &mapping,
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        )
+        .unwrap();
+        assert!(matches!(
+            parser.parse_batch(&batch()),
+            Err(Error::ParserError(_))
+        ));
+
+        assert!(ColumnarParser::new(
+            &ColumnMapping::default(),
+            FeaturesType::SparseCBAdf,
+            LabelType::CB,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        )
+        .is_err());
+    }
+}
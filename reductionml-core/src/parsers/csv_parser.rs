@@ -0,0 +1,663 @@
+//! Reads examples from CSV and TSV data, one example per line.
+//!
+//! A [`CsvSchema`] says which columns hold the label and weight and which hold
+//! the features of each namespace. Numeric columns produce a feature named
+//! after the column with the value of the field, categorical columns produce a
+//! feature named after the column and the value, just like `{"column": 1.5}`
+//! and `{"column": "value"}` in JSON. Empty fields produce no features.
+//!
+//! Fields may be quoted with `"`, in which case they can contain the
+//! delimiter and `""` stands for a quote. Each record must fit on one line.
+
+use std::{borrow::Cow, collections::BTreeMap, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    hash::HashFunction,
+    object_pool::Pool,
+    parsers::{FeatureSink, NameSink, ParsedFeature},
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    BinaryLabel, ExampleMetadata, FeatureMask, Features, FeaturesType, Label, LabelType,
+    SimpleLabel,
+};
+
+use super::{FeatureNames, TextModeParser, TextModeParserFactory};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct CsvSchema {
+    /// Names of the columns in order. When not given, the first line of the
+    /// input is a header naming them.
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+    /// Column containing the label. Examples have no label when this is not
+    /// given or the field is empty.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Column containing the weight of a simple label. Defaults to 1 when this
+    /// is not given or the field is empty.
+    #[serde(default)]
+    pub weight: Option<String>,
+    /// Namespace name to the columns whose values are features in it.
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, CsvNamespace>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct CsvNamespace {
+    #[serde(default)]
+    pub numeric: Vec<String>,
+    #[serde(default)]
+    pub categorical: Vec<String>,
+}
+
+pub struct CsvParserFactory {
+    schema: CsvSchema,
+    delimiter: char,
+}
+
+impl CsvParserFactory {
+    pub fn csv(schema: CsvSchema) -> CsvParserFactory {
+        CsvParserFactory {
+            schema,
+            delimiter: ',',
+        }
+    }
+
+    pub fn tsv(schema: CsvSchema) -> CsvParserFactory {
+        CsvParserFactory {
+            schema,
+            delimiter: '\t',
+        }
+    }
+
+    /// Like [`TextModeParserFactory::create`], but fails straight away if the
+    /// schema can't be used with the given types instead of on the first
+    /// chunk.
+    pub fn try_create(
+        &self,
+        features_type: FeaturesType,
+        label_type: LabelType,
+        hash_seed: u32,
+        hash_function: HashFunction,
+        num_bits: u8,
+        pool: std::sync::Arc<Pool<SparseFeatures>>,
+    ) -> Result<CsvParser> {
+        check_types(&self.schema, features_type, label_type)?;
+        Ok(self.create(
+            features_type,
+            label_type,
+            hash_seed,
+            hash_function,
+            num_bits,
+            pool,
+        ))
+    }
+}
+
+fn check_types(
+    schema: &CsvSchema,
+    features_type: FeaturesType,
+    label_type: LabelType,
+) -> Result<()> {
+    if features_type != FeaturesType::SparseSimple {
+        return Err(Error::InvalidArgument(format!(
+            "CSV input only supports SparseSimple features, not {:?}",
+            features_type
+        )));
+    }
+    match label_type {
+        LabelType::Simple => Ok(()),
+        LabelType::Binary if schema.weight.is_none() => Ok(()),
+        LabelType::Binary => Err(Error::InvalidArgument(
+            "A weight column can only be used with simple labels".to_owned(),
+        )),
+        LabelType::CB => Err(Error::InvalidArgument(
+            "CSV input does not support CB labels".to_owned(),
+        )),
+    }
+}
+
+impl TextModeParserFactory for CsvParserFactory {
+    type Parser = CsvParser;
+
+    fn create(
+        &self,
+        features_type: FeaturesType,
+        label_type: LabelType,
+        hash_seed: u32,
+        hash_function: HashFunction,
+        num_bits: u8,
+        pool: std::sync::Arc<Pool<SparseFeatures>>,
+    ) -> CsvParser {
+        let columns = OnceLock::new();
+        if let Err(e) = check_types(&self.schema, features_type, label_type) {
+            columns.set(Err(e)).ok();
+        } else if let Some(names) = &self.schema.columns {
+            columns
+                .set(ResolvedColumns::new(
+                    &self.schema,
+                    names,
+                    hash_seed,
+                    hash_function,
+                ))
+                .ok();
+        }
+        CsvParser {
+            schema: self.schema.clone(),
+            delimiter: self.delimiter,
+            columns,
+            label_type,
+            hash_seed,
+            hash_function,
+            num_bits,
+            pool,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Numeric,
+    Categorical,
+}
+
+struct FeatureColumn {
+    index: usize,
+    name: String,
+    kind: ColumnKind,
+}
+
+/// Column indices of the schema, which are only known once the header has
+/// been read.
+struct ResolvedColumns {
+    num_columns: usize,
+    label: Option<usize>,
+    weight: Option<usize>,
+    namespaces: Vec<(Namespace, String, Vec<FeatureColumn>)>,
+}
+
+impl ResolvedColumns {
+    fn new(
+        schema: &CsvSchema,
+        names: &[String],
+        hash_seed: u32,
+        hash_function: HashFunction,
+    ) -> Result<ResolvedColumns> {
+        let index_of = |column: &str| {
+            names.iter().position(|x| x == column).ok_or_else(|| {
+                Error::ParserError(format!("Column {} not found in CSV header", column))
+            })
+        };
+        let mut namespaces = Vec::new();
+        for (namespace, columns) in &schema.namespaces {
+            let numeric = columns.numeric.iter().map(|x| (x, ColumnKind::Numeric));
+            let categorical = columns
+                .categorical
+                .iter()
+                .map(|x| (x, ColumnKind::Categorical));
+            let columns = numeric
+                .chain(categorical)
+                .map(|(name, kind)| {
+                    Ok(FeatureColumn {
+                        index: index_of(name)?,
+                        name: name.clone(),
+                        kind,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            namespaces.push((
+                Namespace::from_name(namespace, hash_seed, hash_function),
+                namespace.clone(),
+                columns,
+            ));
+        }
+        Ok(ResolvedColumns {
+            num_columns: names.len(),
+            label: schema.label.as_deref().map(index_of).transpose()?,
+            weight: schema.weight.as_deref().map(index_of).transpose()?,
+            namespaces,
+        })
+    }
+}
+
+pub struct CsvParser {
+    schema: CsvSchema,
+    delimiter: char,
+    columns: OnceLock<Result<ResolvedColumns>>,
+    label_type: LabelType,
+    hash_seed: u32,
+    hash_function: HashFunction,
+    num_bits: u8,
+    pool: std::sync::Arc<Pool<SparseFeatures>>,
+}
+
+/// Splits a line into its fields, removing quotes.
+fn split_fields(line: &str, delimiter: char) -> Result<Vec<Cow<'_, str>>> {
+    let mut fields = Vec::new();
+    let mut rest = line;
+    loop {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) if quoted[i + 1..].starts_with('"') => {
+                        value.push('"');
+                        chars.next();
+                    }
+                    Some((i, '"')) => break i + 1,
+                    Some((_, c)) => value.push(c),
+                    None => {
+                        return Err(Error::ParserError(format!(
+                            "Unterminated quoted field in line: {}",
+                            line
+                        )))
+                    }
+                }
+            };
+            fields.push(Cow::Owned(value));
+            rest = &quoted[end..];
+            if rest.is_empty() {
+                break;
+            }
+            rest = rest.strip_prefix(delimiter).ok_or_else(|| {
+                Error::ParserError(format!(
+                    "Expected a delimiter after a quoted field in line: {}",
+                    line
+                ))
+            })?;
+        } else {
+            match rest.find(delimiter) {
+                Some(i) => {
+                    fields.push(Cow::Borrowed(&rest[..i]));
+                    rest = &rest[i + delimiter.len_utf8()..];
+                }
+                None => {
+                    fields.push(Cow::Borrowed(rest));
+                    break;
+                }
+            }
+        }
+    }
+    Ok(fields)
+}
+
+fn parse_number(field: &str, column: &str) -> Result<f32> {
+    fast_float::parse(field.trim()).map_err(|_| {
+        Error::ParserError(format!(
+            "Column {} must be numeric but contains {}",
+            column, field
+        ))
+    })
+}
+
+impl CsvParser {
+    fn resolved_columns(&self) -> Result<&ResolvedColumns> {
+        match self.columns.get() {
+            Some(Ok(columns)) => Ok(columns),
+            Some(Err(e)) => Err(Error::ParserError(e.to_string())),
+            None => Err(Error::ParserError(
+                "The CSV header must be read before parsing examples".to_owned(),
+            )),
+        }
+    }
+
+    /// Splits a record into its fields, checking that it has every column.
+    fn split_record<'a>(&self, chunk: &'a str) -> Result<(&ResolvedColumns, Vec<Cow<'a, str>>)> {
+        let columns = self.resolved_columns()?;
+        let fields = split_fields(chunk, self.delimiter)?;
+        if fields.len() != columns.num_columns {
+            return Err(Error::ParserError(format!(
+                "Expected {} columns but found {} in line: {}",
+                columns.num_columns,
+                fields.len(),
+                chunk
+            )));
+        }
+        Ok((columns, fields))
+    }
+
+    fn read_line(input: &mut dyn std::io::BufRead, output: &mut String) -> Result<bool> {
+        loop {
+            output.clear();
+            if input.read_line(output)? == 0 {
+                return Ok(false);
+            }
+            output.truncate(output.trim_end_matches(['\r', '\n']).len());
+            if !output.trim().is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn parse_label(&self, fields: &[Cow<str>], columns: &ResolvedColumns) -> Result<Option<Label>> {
+        let value = match columns.label.map(|i| fields[i].trim()) {
+            None | Some("") => return Ok(None),
+            Some(value) => value,
+        };
+        let label_column = self.schema.label.as_deref().unwrap_or_default();
+        match self.label_type {
+            LabelType::Simple => {
+                let weight = match columns.weight.map(|i| fields[i].trim()) {
+                    None | Some("") => 1.0,
+                    Some(weight) => {
+                        parse_number(weight, self.schema.weight.as_deref().unwrap_or_default())?
+                    }
+                };
+                Ok(Some(
+                    SimpleLabel::new(parse_number(value, label_column)?, weight).into(),
+                ))
+            }
+            LabelType::Binary => match value {
+                "1" | "true" | "True" | "TRUE" => Ok(Some(BinaryLabel(true).into())),
+                "0" | "-1" | "false" | "False" | "FALSE" => Ok(Some(BinaryLabel(false).into())),
+                _ => Err(Error::ParserError(format!(
+                    "Column {} must be a binary label but contains {}",
+                    label_column, value
+                ))),
+            },
+            LabelType::CB => Err(Error::ParserError(
+                "CSV input does not support CB labels".to_owned(),
+            )),
+        }
+    }
+}
+
+impl TextModeParser for CsvParser {
+    fn get_next_chunk(
+        &self,
+        input: &mut dyn std::io::BufRead,
+        mut output_buffer: String,
+    ) -> Result<Option<String>> {
+        if self.columns.get().is_none() {
+            if !CsvParser::read_line(input, &mut output_buffer)? {
+                return Ok(None);
+            }
+            let names: Vec<String> = split_fields(&output_buffer, self.delimiter)?
+                .iter()
+                .map(|x| x.trim().to_owned())
+                .collect();
+            self.columns
+                .set(ResolvedColumns::new(
+                    &self.schema,
+                    &names,
+                    self.hash_seed,
+                    self.hash_function,
+                ))
+                .ok();
+        }
+        self.resolved_columns()?;
+        Ok(CsvParser::read_line(input, &mut output_buffer)?.then_some(output_buffer))
+    }
+
+    fn parse_chunk<'b>(
+        &self,
+        chunk: &str,
+    ) -> Result<(Features<'b>, Option<Label>, ExampleMetadata)> {
+        let (columns, fields) = self.split_record(chunk)?;
+
+        let mask = FeatureMask::from_num_bits(self.num_bits);
+        let mut features: SparseFeatures = self.pool.get_object();
+        for (namespace, namespace_name, namespace_columns) in &columns.namespaces {
+            let ns_hash = namespace.hash();
+            for column in namespace_columns {
+                let field = fields[column.index].as_ref();
+                if field.trim().is_empty() {
+                    continue;
+                }
+                let (feat, value) = match column.kind {
+                    ColumnKind::Numeric => (
+                        ParsedFeature::Simple {
+                            name: column.name.as_str().into(),
+                        },
+                        parse_number(field, &column.name)?,
+                    ),
+                    ColumnKind::Categorical => (
+                        ParsedFeature::SimpleWithStringValue {
+                            name: column.name.as_str().into(),
+                            value: field.into(),
+                        },
+                        1.0,
+                    ),
+                };
+                features
+                    .get_or_create_named_namespace(*namespace, namespace_name.as_str())
+                    .add_feature(feat.hash(ns_hash, self.hash_function).mask(mask), value);
+            }
+        }
+        let label = self.parse_label(&fields, columns)?;
+        let metadata = ExampleMetadata {
+            weight: match &label {
+                Some(Label::Simple(label))
+                    if columns.weight.is_some_and(|i| !fields[i].trim().is_empty()) =>
+                {
+                    Some(label.weight())
+                }
+                _ => None,
+            },
+            ..Default::default()
+        };
+        Ok((features.into(), label, metadata))
+    }
+
+    fn extract_feature_names<'a>(&self, chunk: &'a str) -> Result<Vec<FeatureNames<'a>>> {
+        let (columns, fields) = self.split_record(chunk)?;
+        let mut sink = NameSink::default();
+        for (_, namespace_name, namespace_columns) in &columns.namespaces {
+            sink.push_namespace(namespace_name.clone().into());
+            for column in namespace_columns {
+                let field = &fields[column.index];
+                if field.trim().is_empty() {
+                    continue;
+                }
+                match column.kind {
+                    ColumnKind::Numeric => sink.add_feature(
+                        ParsedFeature::Simple {
+                            name: column.name.clone().into(),
+                        },
+                        parse_number(field, &column.name)?,
+                    ),
+                    ColumnKind::Categorical => sink.add_feature(
+                        ParsedFeature::SimpleWithStringValue {
+                            name: column.name.clone().into(),
+                            value: field.clone(),
+                        },
+                        1.0,
+                    ),
+                }
+            }
+            sink.pop_namespace();
+        }
+        Ok(vec![sink.into_names()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufRead, sync::Arc};
+
+    use approx::assert_abs_diff_eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{parsers::JsonParserFactory, utils::AsInner};
+
+    fn schema() -> CsvSchema {
+        serde_json::from_value(json!({
+            "label": "y",
+            "weight": "w",
+            "namespaces": {
+                "a": {"numeric": ["age"], "categorical": ["country"]},
+                ":default": {"categorical": ["city"]}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn parse_all(parser: &CsvParser, input: &str) -> Result<Vec<(SparseFeatures, Option<Label>)>> {
+        let mut input: Box<dyn BufRead> = Box::new(input.as_bytes());
+        let mut examples = Vec::new();
+        let mut buffer = String::new();
+        while let Some(chunk) = parser.get_next_chunk(&mut input, buffer)? {
+            let (features, label, _) = parser.parse_chunk(&chunk)?;
+            let features: &SparseFeatures = features.as_inner().unwrap();
+            examples.push((features.clone(), label));
+            buffer = chunk;
+        }
+        Ok(examples)
+    }
+
+    fn json_features(input: &str) -> SparseFeatures {
+        let parser = JsonParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (features, _, _) = parser.parse_chunk(input).unwrap();
+        let features: &SparseFeatures = features.as_inner().unwrap();
+        features.clone()
+    }
+
+    fn create(factory: CsvParserFactory, label_type: LabelType) -> CsvParser {
+        factory.create(
+            FeaturesType::SparseSimple,
+            label_type,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        )
+    }
+
+    #[test]
+    fn csv_parse_with_header() {
+        let parser = create(CsvParserFactory::csv(schema()), LabelType::Simple);
+        let examples = parse_all(
+            &parser,
+            "y,age,country,unused,city,w\r\n1.5,30,nz,x,\"Wellington, NZ\",2\n\n,,\"\"\"us\"\"\",,,\n",
+        )
+        .unwrap();
+        assert_eq!(examples.len(), 2);
+
+        let expected = json_features(
+            r#"{"features": {"a": {"age": 30, "country": "nz"}, ":default": {"city": "Wellington, NZ"}}}"#,
+        );
+        assert_abs_diff_eq!(examples[0].0, expected);
+        let label: &SimpleLabel = examples[0].1.as_ref().unwrap().as_inner().unwrap();
+        assert_eq!((label.value(), label.weight()), (1.5, 2.0));
+
+        let expected = json_features(r#"{"features": {"a": {"country": "\"us\""}}}"#);
+        assert_abs_diff_eq!(examples[1].0, expected);
+        assert!(examples[1].1.is_none());
+    }
+
+    #[test]
+    fn tsv_parse_with_columns() {
+        let mut schema = schema();
+        schema.columns = Some(
+            ["age", "country", "city", "y", "w"]
+                .map(|x| x.to_owned())
+                .to_vec(),
+        );
+        schema.weight = None;
+        let parser = create(CsvParserFactory::tsv(schema), LabelType::Binary);
+        let examples = parse_all(&parser, "30\tnz\tAuckland\t1\t\n\tus\t\tfalse\t\n").unwrap();
+        assert_eq!(examples.len(), 2);
+        let expected = json_features(
+            r#"{"features": {"a": {"age": 30, "country": "nz"}, ":default": {"city": "Auckland"}}}"#,
+        );
+        assert_abs_diff_eq!(examples[0].0, expected);
+        let expected = json_features(r#"{"features": {"a": {"country": "us"}}}"#);
+        assert_abs_diff_eq!(examples[1].0, expected);
+        let label: &BinaryLabel = examples[0].1.as_ref().unwrap().as_inner().unwrap();
+        assert!(label.0);
+        let label: &BinaryLabel = examples[1].1.as_ref().unwrap().as_inner().unwrap();
+        assert!(!label.0);
+    }
+
+    #[test]
+    fn csv_extract_feature_names() {
+        use crate::parsers::ParsedNamespaceInfo;
+
+        let parser = create(CsvParserFactory::csv(schema()), LabelType::Simple);
+        let mut input: Box<dyn BufRead> =
+            Box::new("y,age,country,city,w\n1,30,\"nz\",,2\n".as_bytes());
+        let chunk = parser
+            .get_next_chunk(&mut input, String::new())
+            .unwrap()
+            .unwrap();
+        let names = parser.extract_feature_names(&chunk).unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(
+            names[0][&ParsedNamespaceInfo::Named("a".into())],
+            vec![
+                ParsedFeature::Simple { name: "age".into() },
+                ParsedFeature::SimpleWithStringValue {
+                    name: "country".into(),
+                    value: "nz".into()
+                }
+            ]
+        );
+        // Empty fields produce no features.
+        assert!(!names[0].contains_key(&ParsedNamespaceInfo::Default));
+    }
+
+    #[test]
+    fn csv_weight_requires_simple_labels() {
+        let factory = CsvParserFactory::csv(schema());
+        let try_create = |label_type| {
+            factory.try_create(
+                FeaturesType::SparseSimple,
+                label_type,
+                0,
+                HashFunction::default(),
+                18,
+                Arc::new(Pool::new()),
+            )
+        };
+        assert!(try_create(LabelType::Simple).is_ok());
+        assert!(matches!(
+            try_create(LabelType::Binary),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        // Parsers created without checking fail on the first chunk.
+        let parser = create(CsvParserFactory::csv(schema()), LabelType::Binary);
+        assert!(parse_all(&parser, "y,age,country,city,w\n1,30,nz,x,2\n").is_err());
+    }
+
+    #[test]
+    fn csv_parse_errors() {
+        let parser = create(CsvParserFactory::csv(schema()), LabelType::Simple);
+        assert!(matches!(
+            parse_all(&parser, "y,age,country,city\n"),
+            Err(Error::ParserError(_))
+        ));
+
+        let header = "y,age,country,city,w\n";
+        for line in [
+            "1,old,nz,x,1",
+            "1,30,nz",
+            "1,30,\"nz,x,1",
+            "1,30,\"nz\"x,y,1",
+        ] {
+            let parser = create(CsvParserFactory::csv(schema()), LabelType::Simple);
+            let parser_input = format!("{}{}\n", header, line);
+            assert!(
+                matches!(
+                    parse_all(&parser, &parser_input),
+                    Err(Error::ParserError(_))
+                ),
+                "{}",
+                line
+            );
+        }
+    }
+}
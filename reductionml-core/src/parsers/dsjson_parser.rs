@@ -1,19 +1,80 @@
 use core::f32;
 
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json_borrow::Value;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::hash::HashFunction;
 
 use crate::object_pool::Pool;
-use crate::parsers::ParsedFeature;
-use crate::sparse_namespaced_features::{Namespace, SparseFeatures};
-use crate::types::{Features, Label, LabelType};
-use crate::{CBAdfFeatures, CBLabel, FeatureMask, FeaturesType};
+use crate::parsers::{field, FeatureSink, HashingSink, NameSink, ParsedFeature};
+use crate::sparse_namespaced_features::SparseFeatures;
+use crate::types::{ExampleMetadata, Features, Label, LabelType};
+use crate::{CBAdfFeatures, CBLabel, FeaturesType};
 
-use super::{ParsedNamespaceInfo, TextModeParser, TextModeParserFactory};
+use super::{FeatureNames, TextModeParser, TextModeParserFactory};
+
+/// How the outcomes recorded in the `o` field of an event are combined into
+/// its reward.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RewardFunction {
+    /// The first outcome.
+    Earliest,
+    Average,
+    Sum,
+    Median,
+}
+
+impl RewardFunction {
+    /// Returns `None` when there are no outcomes.
+    pub fn apply(&self, outcomes: &[f32]) -> Option<f32> {
+        if outcomes.is_empty() {
+            return None;
+        }
+        Some(match self {
+            RewardFunction::Earliest => outcomes[0],
+            RewardFunction::Average => outcomes.iter().sum::<f32>() / outcomes.len() as f32,
+            RewardFunction::Sum => outcomes.iter().sum(),
+            RewardFunction::Median => {
+                let mut sorted = outcomes.to_vec();
+                sorted.sort_by(f32::total_cmp);
+                let mid = sorted.len() / 2;
+                if sorted.len() % 2 == 1 {
+                    sorted[mid]
+                } else {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DsJsonOptions {
+    /// When set, the cost of the label is the negated reward calculated from
+    /// the outcomes of the event instead of `_label_cost`.
+    pub reward_function: Option<RewardFunction>,
+    /// Reward of an event which has no outcomes, when a reward function is
+    /// used.
+    pub default_reward: f32,
+    /// When set, the cost of the label is `_original_label_cost`, the cost
+    /// before it was changed by the service, for events which have it.
+    pub use_original_label_cost: bool,
+}
 
 #[derive(Default)]
-pub struct DsJsonParserFactory;
+pub struct DsJsonParserFactory {
+    options: DsJsonOptions,
+}
+
+impl DsJsonParserFactory {
+    pub fn new(options: DsJsonOptions) -> DsJsonParserFactory {
+        DsJsonParserFactory { options }
+    }
+}
+
 impl TextModeParserFactory for DsJsonParserFactory {
     type Parser = DsJsonParser;
 
@@ -22,6 +83,7 @@ impl TextModeParserFactory for DsJsonParserFactory {
         features_type: FeaturesType,
         label_type: LabelType,
         hash_seed: u32,
+        hash_function: HashFunction,
         num_bits: u8,
         pool: std::sync::Arc<Pool<SparseFeatures>>,
     ) -> DsJsonParser {
@@ -38,7 +100,9 @@ impl TextModeParserFactory for DsJsonParserFactory {
             _feature_type: features_type,
             _label_type: label_type,
             hash_seed,
+            hash_function,
             num_bits,
+            options: self.options,
             pool,
         }
     }
@@ -48,112 +112,235 @@ pub struct DsJsonParser {
     _feature_type: FeaturesType,
     _label_type: LabelType,
     hash_seed: u32,
+    hash_function: HashFunction,
     num_bits: u8,
+    options: DsJsonOptions,
     pool: std::sync::Arc<Pool<SparseFeatures>>,
 }
 
+fn extract_metadata(json: &Value) -> Result<ExampleMetadata> {
+    let id = match json.get("EventId") {
+        Value::Str(id) => Some(id.to_string()),
+        _ => None,
+    };
+    let timestamp = match json.get("Timestamp") {
+        Value::Str(timestamp) => Some(
+            DateTime::parse_from_rfc3339(timestamp)
+                .map_err(|e| Error::ParserError(format!("Invalid Timestamp {}: {}", timestamp, e)))?
+                .with_timezone(&Utc),
+        ),
+        _ => None,
+    };
+    Ok(ExampleMetadata {
+        id,
+        timestamp,
+        ..Default::default()
+    })
+}
+
 impl DsJsonParser {
-    pub fn handle_features(
-        &self,
-        features: &mut SparseFeatures,
-        object_key: &str,
-        json_value: &Value,
-        namespace_stack: &mut Vec<Namespace>,
-    ) {
-        // All underscore prefixed keys are ignored.
-        if object_key.starts_with('_') {
-            return;
+    fn parse_event<'b>(&self, json: &Value) -> Result<(Features<'b>, Option<Label>)> {
+        let (context, multi) = context_and_actions(json)?;
+
+        let mut shared_ex = self.pool.get_object();
+        walk_features(&mut self.hashing_sink(&mut shared_ex), " ", context)?;
+
+        let mut actions = Vec::new();
+        for item in multi {
+            let mut action = self.pool.get_object();
+            walk_features(&mut self.hashing_sink(&mut action), " ", item)?;
+            actions.push(action);
+        }
+
+        let label = self.extract_label(json)?;
+
+        Ok((
+            Features::SparseCBAdf(CBAdfFeatures {
+                shared: Some(shared_ex),
+                actions,
+            }),
+            label.map(Label::CB),
+        ))
+    }
+
+    fn hashing_sink<'s, 'a>(&self, features: &'s mut SparseFeatures) -> HashingSink<'s, 'a> {
+        HashingSink::new(features, self.hash_seed, self.hash_function, self.num_bits)
+    }
+
+    fn extract_label(&self, json: &Value) -> Result<Option<CBLabel>> {
+        // Events marked with _skipLearn are only predicted on.
+        if let Value::Bool(true) = json.get("_skipLearn") {
+            return Ok(None);
         }
 
-        // skip everything with _
-        match json_value {
-            Value::Null => panic!("Null is not supported"),
-            Value::Bool(true) => {
-                let current_ns = *namespace_stack
-                    .last()
-                    .expect("namespace stack should not be empty here");
-                let current_ns_hash = current_ns.hash();
-                let current_feats = features.get_or_create_namespace(current_ns);
-                current_feats.add_feature(
-                    ParsedFeature::Simple { name: object_key }
-                        .hash(current_ns_hash)
-                        .mask(FeatureMask::from_num_bits(self.num_bits)),
-                    1.0,
-                );
+        let (action, probability) = match (json.get("_labelIndex"), json.get("_label_probability"))
+        {
+            (Value::Number(action), Value::Number(prob)) => (
+                action.as_u64().ok_or_else(|| {
+                    Error::ParserError("_labelIndex must be a non-negative integer".to_string())
+                })? as usize,
+                prob.as_f64().unwrap() as f32,
+            ),
+            (Value::Null, Value::Null) => return Ok(None),
+            _ => {
+                return Err(Error::ParserError(
+                    "Invalid label, _labelIndex and _label_probability must both be present"
+                        .to_string(),
+                ))
             }
-            Value::Bool(false) => (),
-            Value::Number(value) => {
-                let current_ns = *namespace_stack
-                    .last()
-                    .expect("namespace stack should not be empty here");
-                let current_ns_hash = current_ns.hash();
-                let current_feats = features.get_or_create_namespace(current_ns);
-                current_feats.add_feature(
-                    ParsedFeature::Simple { name: object_key }
-                        .hash(current_ns_hash)
-                        .mask(FeatureMask::from_num_bits(self.num_bits)),
-                    value.as_f64().unwrap() as f32,
-                );
+        };
+
+        let cost = match self.options.reward_function {
+            Some(reward_function) => {
+                let outcomes: Vec<f32> = json
+                    .get("o")
+                    .iter_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|outcome| match outcome.get("v") {
+                        Value::Number(v) => v.as_f64().map(|v| v as f32),
+                        _ => None,
+                    })
+                    .collect();
+                -reward_function
+                    .apply(&outcomes)
+                    .unwrap_or(self.options.default_reward)
             }
-            Value::Str(value) => {
-                let current_ns = namespace_stack
-                    .last()
-                    .expect("namespace stack should not be empty here");
-                let current_ns_hash = current_ns.hash();
-                let current_feats = features.get_or_create_namespace(*current_ns);
-                current_feats.add_feature(
-                    ParsedFeature::SimpleWithStringValue {
-                        name: object_key,
-                        value,
-                    }
-                    .hash(current_ns_hash)
-                    .mask(FeatureMask::from_num_bits(self.num_bits)),
-                    1.0,
-                );
+            None => match (
+                self.options.use_original_label_cost,
+                json.get("_original_label_cost"),
+                json.get("_label_cost"),
+            ) {
+                (true, Value::Number(cost), _) | (_, _, Value::Number(cost)) => {
+                    cost.as_f64().unwrap() as f32
+                }
+                _ => {
+                    return Err(Error::ParserError(
+                        "Invalid label, _label_cost must be present".to_string(),
+                    ))
+                }
+            },
+        };
+
+        // Events which were kept when others were dropped stand in for the
+        // dropped ones, so their probability is reduced accordingly.
+        let probability = match json.get("pdrop") {
+            Value::Null => probability,
+            Value::Number(pdrop) => {
+                let pdrop = pdrop.as_f64().unwrap() as f32;
+                if !(0.0..1.0).contains(&pdrop) {
+                    return Err(Error::ParserError(format!(
+                        "pdrop must be at least 0 and less than 1, got {}",
+                        pdrop
+                    )));
+                }
+                probability * (1.0 - pdrop)
             }
-            Value::Array(value) => {
-                namespace_stack.push(Namespace::from_name(object_key, self.hash_seed));
-                let current_ns = *namespace_stack
-                    .last()
-                    .expect("namespace stack should not be empty here");
-                let current_ns_hash = current_ns.hash();
-                for (anon_idx, v) in value.iter().enumerate() {
-                    match v {
-                        Value::Number(value) => {
-                            // Not super efficient but it works
-                            // Doing this in the outside doesn't work as it would mean two mutable borrows.
-                            let current_feats = features.get_or_create_namespace(current_ns);
-                            current_feats.add_feature(
-                                ParsedFeature::Anonymous {
-                                    offset: anon_idx as u32,
-                                }
-                                .hash(current_ns_hash)
-                                .mask(FeatureMask::from_num_bits(self.num_bits)),
-                                value.as_f64().unwrap() as f32,
-                            );
-                        }
-                        Value::Object(_) => {
-                            self.handle_features(features, object_key, v, namespace_stack);
-                        }
-                        // Just ignore null and do nothing
-                        Value::Null => (),
-                        _ => panic!(
-                            "Array of non-number or object is not supported key:{} value:{:?}",
+            other => {
+                return Err(Error::ParserError(format!(
+                    "pdrop must be a number, got {:?}",
+                    other
+                )))
+            }
+        };
+
+        Ok(Some(CBLabel {
+            action,
+            cost,
+            probability,
+        }))
+    }
+}
+
+/// Walks the features of an event. Objects and arrays are namespaces named by
+/// their key, and `key: value` pairs are features of the enclosing namespace.
+fn walk_features<'a>(
+    sink: &mut impl FeatureSink<'a>,
+    object_key: &'a str,
+    json_value: &Value<'a>,
+) -> Result<()> {
+    // All underscore prefixed keys are ignored.
+    if object_key.starts_with('_') {
+        return Ok(());
+    }
+
+    match json_value {
+        // Null is treated as missing.
+        Value::Null => (),
+        Value::Bool(true) => sink.add_feature(
+            ParsedFeature::Simple {
+                name: object_key.into(),
+            },
+            1.0,
+        ),
+        Value::Bool(false) => (),
+        Value::Number(value) => sink.add_feature(
+            ParsedFeature::Simple {
+                name: object_key.into(),
+            },
+            value.as_f64().unwrap() as f32,
+        ),
+        Value::Str(value) => sink.add_feature(
+            ParsedFeature::SimpleWithStringValue {
+                name: object_key.into(),
+                value: value.clone(),
+            },
+            1.0,
+        ),
+        Value::Array(value) => {
+            sink.push_namespace(object_key.into());
+            for (anon_idx, v) in value.iter().enumerate() {
+                match v {
+                    Value::Number(value) => sink.add_feature(
+                        ParsedFeature::Anonymous {
+                            offset: anon_idx as u32,
+                        },
+                        value.as_f64().unwrap() as f32,
+                    ),
+                    Value::Object(_) => walk_features(sink, object_key, v)?,
+                    // Just ignore null and do nothing
+                    Value::Null => (),
+                    _ => {
+                        return Err(Error::ParserError(format!(
+                            "Arrays must contain numbers or objects, but {} contains {:?}",
                             object_key, v
-                        ),
+                        )))
                     }
                 }
-                namespace_stack.pop().unwrap();
             }
-            Value::Object(value) => {
-                namespace_stack.push(Namespace::from_name(object_key, self.hash_seed));
-                for (key, v) in value {
-                    self.handle_features(features, key, v, namespace_stack);
-                }
-                namespace_stack.pop().unwrap();
+            sink.pop_namespace();
+        }
+        Value::Object(value) => {
+            sink.push_namespace(object_key.into());
+            for (key, v) in value {
+                walk_features(sink, key, v)?;
             }
+            sink.pop_namespace();
         }
     }
+    Ok(())
+}
+
+/// Returns the shared context and the actions of an event, which must all be
+/// objects.
+fn context_and_actions<'v, 'a>(json: &'v Value<'a>) -> Result<(&'v Value<'a>, &'v [Value<'a>])> {
+    let context = match field(json, "c") {
+        context @ Value::Object(_) => context,
+        _ => {
+            return Err(Error::ParserError(
+                "Events must have an object of features in c".to_string(),
+            ))
+        }
+    };
+    let multi = match field(context, "_multi") {
+        Value::Array(multi) if multi.iter().all(|item| matches!(item, Value::Object(_))) => multi,
+        _ => {
+            return Err(Error::ParserError(
+                "Events must have an array of action objects in c._multi".to_string(),
+            ))
+        }
+    };
+    Ok((context, multi))
 }
 
 impl TextModeParser for DsJsonParser {
@@ -170,67 +357,54 @@ impl TextModeParser for DsJsonParser {
         Ok(Some(output_buffer))
     }
 
-    fn parse_chunk<'a, 'b>(&self, chunk: &'a str) -> Result<(Features<'b>, Option<Label>)> {
-        let json: Value = serde_json::from_str(chunk).expect("JSON was not well-formatted");
-
-        let mut namespace_stack = Vec::new();
-
-        let mut shared_ex = self.pool.get_object();
-        self.handle_features(&mut shared_ex, " ", json.get("c"), &mut namespace_stack);
-        assert!(namespace_stack.is_empty());
-
-        let mut actions = Vec::new();
-        for item in json.get("c").get("_multi").iter_array().unwrap() {
-            let mut action = self.pool.get_object();
-            self.handle_features(&mut action, " ", item, &mut namespace_stack);
-            actions.push(action);
-            assert!(namespace_stack.is_empty());
-        }
+    fn parse_chunk<'a, 'b>(
+        &self,
+        chunk: &'a str,
+    ) -> Result<(Features<'b>, Option<Label>, ExampleMetadata)> {
+        let json: Value = serde_json::from_str(chunk)?;
+        let metadata = extract_metadata(&json)?;
+        let (features, label) = self.parse_event(&json)?;
+        Ok((features, label, metadata))
+    }
 
-        let label = match (
-            json.get("_label_cost"),
-            json.get("_label_probability"),
-            json.get("_labelIndex"),
-        ) {
-            (Value::Number(cost), Value::Number(prob), Value::Number(action)) => Some(CBLabel {
-                action: action.as_u64().unwrap() as usize,
-                cost: cost.as_f64().unwrap() as f32,
-                probability: prob.as_f64().unwrap() as f32,
-            }),
-            (Value::Null, Value::Null, Value::Null) => None,
-            _ => panic!("Invalid label, all 3 or none must be present"),
-        };
+    fn extract_feature_names<'a>(&self, chunk: &'a str) -> Result<Vec<FeatureNames<'a>>> {
+        let json: Value = serde_json::from_str(chunk)?;
+        let (context, multi) = context_and_actions(&json)?;
 
-        Ok((
-            Features::SparseCBAdf(CBAdfFeatures {
-                shared: Some(shared_ex),
-                actions,
-            }),
-            label.map(Label::CB),
-        ))
-    }
+        // The shared features are learned on together with each action.
+        let mut shared = NameSink::default();
+        walk_features(&mut shared, " ", context)?;
+        let shared = shared.into_names();
 
-    fn extract_feature_names<'a>(
-        &self,
-        _chunk: &'a str,
-    ) -> Result<std::collections::HashMap<ParsedNamespaceInfo<'a>, Vec<ParsedFeature<'a>>>> {
-        todo!()
+        multi
+            .iter()
+            .map(|item| {
+                let mut action = NameSink::with_names(shared.clone());
+                walk_features(&mut action, " ", item)?;
+                Ok(action.into_names())
+            })
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::hash::HashFunction;
     use std::sync::Arc;
 
     use approx::assert_relative_eq;
     use serde_json::json;
 
     use crate::{
+        error::Error,
         object_pool::Pool,
-        parsers::{DsJsonParserFactory, TextModeParser, TextModeParserFactory},
+        parsers::{
+            DsJsonOptions, DsJsonParser, DsJsonParserFactory, FeatureNames, ParsedFeature,
+            ParsedNamespaceInfo, RewardFunction, TextModeParser, TextModeParserFactory,
+        },
         sparse_namespaced_features::Namespace,
         utils::AsInner,
-        CBAdfFeatures, CBLabel, FeaturesType, LabelType,
+        CBAdfFeatures, CBLabel, ExampleMetadata, FeaturesType, LabelType,
     };
     #[test]
     fn extract_dsjson_test_chain_hash() {
@@ -284,12 +458,13 @@ mod test {
             FeaturesType::SparseCBAdf,
             LabelType::CB,
             0,
+            HashFunction::default(),
             18,
             pool,
         );
 
         let input = json_obj.to_string();
-        let (features, label) = parser.parse_chunk(&input).unwrap();
+        let (features, label, _) = parser.parse_chunk(&input).unwrap();
         let cb_label: &CBLabel = label.as_ref().unwrap().as_inner().unwrap();
         assert_eq!(cb_label.action, 3);
         assert_relative_eq!(cb_label.cost, 0.0);
@@ -304,12 +479,12 @@ mod test {
         assert_eq!(shared_default_ns.iter().count(), 1);
 
         let shared_from_url_ns = shared
-            .get_namespace(Namespace::from_name("FromUrl", 0))
+            .get_namespace(Namespace::from_name("FromUrl", 0, HashFunction::default()))
             .unwrap();
         assert_eq!(shared_from_url_ns.iter().count(), 3);
 
         let shared_numbers_ns = shared
-            .get_namespace(Namespace::from_name("numbers", 0))
+            .get_namespace(Namespace::from_name("numbers", 0, HashFunction::default()))
             .unwrap();
         assert_eq!(shared_numbers_ns.iter().count(), 2);
         assert_relative_eq!(
@@ -320,9 +495,201 @@ mod test {
         let action = cb_feats.actions.get(0).unwrap();
         assert_eq!(action.namespaces().count(), 2);
         assert!(action.get_namespace(Namespace::Default).is_none());
-        let action_i_ns = action.get_namespace(Namespace::from_name("i", 0)).unwrap();
+        let action_i_ns = action
+            .get_namespace(Namespace::from_name("i", 0, HashFunction::default()))
+            .unwrap();
         assert_eq!(action_i_ns.iter().count(), 2);
-        let action_j_ns = action.get_namespace(Namespace::from_name("j", 0)).unwrap();
+        let action_j_ns = action
+            .get_namespace(Namespace::from_name("j", 0, HashFunction::default()))
+            .unwrap();
         assert_eq!(action_j_ns.iter().count(), 4);
     }
+
+    fn parser_with_options(options: DsJsonOptions) -> DsJsonParser {
+        DsJsonParserFactory::new(options).create(
+            FeaturesType::SparseCBAdf,
+            LabelType::CB,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        )
+    }
+
+    fn parse_label(parser: &DsJsonParser, event: serde_json::Value) -> Option<CBLabel> {
+        let (_, label, _) = parser.parse_chunk(&event.to_string()).unwrap();
+        label.map(|label| *label.as_inner().unwrap())
+    }
+
+    fn event() -> serde_json::Value {
+        json!({
+          "_label_cost": -1.0,
+          "_label_probability": 0.5,
+          "_labelIndex": 1,
+          "o": [{"v": 1.0}, {"EventId": "abc", "ActionTaken": true}, {"v": 4.0}, {"v": 2.0}],
+          "c": {"_multi": [{"a": 1}, {"a": 2}]}
+        })
+    }
+
+    #[test]
+    fn dsjson_skip_learn_and_pdrop() {
+        let parser = parser_with_options(DsJsonOptions::default());
+
+        let mut skipped = event();
+        skipped["_skipLearn"] = json!(true);
+        assert!(parse_label(&parser, skipped).is_none());
+
+        let mut not_skipped = event();
+        not_skipped["_skipLearn"] = json!(false);
+        assert!(parse_label(&parser, not_skipped).is_some());
+
+        let mut dropped = event();
+        dropped["pdrop"] = json!(0.5);
+        let label = parse_label(&parser, dropped).unwrap();
+        assert_eq!(label.action, 1);
+        assert_relative_eq!(label.cost, -1.0);
+        assert_relative_eq!(label.probability, 0.25);
+
+        let mut invalid = event();
+        invalid["pdrop"] = json!(1.0);
+        assert!(parser.parse_chunk(&invalid.to_string()).is_err());
+    }
+
+    #[test]
+    fn dsjson_reward_functions() {
+        for (reward_function, expected_reward) in [
+            (RewardFunction::Earliest, 1.0),
+            (RewardFunction::Average, 7.0 / 3.0),
+            (RewardFunction::Sum, 7.0),
+            (RewardFunction::Median, 2.0),
+        ] {
+            let parser = parser_with_options(DsJsonOptions {
+                reward_function: Some(reward_function),
+                ..Default::default()
+            });
+            let label = parse_label(&parser, event()).unwrap();
+            assert_relative_eq!(label.cost, -expected_reward);
+        }
+        assert_eq!(
+            RewardFunction::Median.apply(&[3.0, 1.0, 2.0, 10.0]),
+            Some(2.5)
+        );
+
+        // Events without outcomes get the default reward.
+        let parser = parser_with_options(DsJsonOptions {
+            reward_function: Some(RewardFunction::Sum),
+            default_reward: -0.5,
+            ..Default::default()
+        });
+        let mut no_outcomes = event();
+        no_outcomes.as_object_mut().unwrap().remove("o");
+        assert_relative_eq!(parse_label(&parser, no_outcomes).unwrap().cost, 0.5);
+        let mut only_activations = event();
+        only_activations["o"] = json!([{"EventId": "abc", "ActionTaken": true}]);
+        assert_relative_eq!(parse_label(&parser, only_activations).unwrap().cost, 0.5);
+    }
+
+    #[test]
+    fn dsjson_metadata() {
+        let parser = parser_with_options(DsJsonOptions::default());
+        let mut logged = event();
+        logged["EventId"] = json!("13118d9b4c114f8485d9dec417e3aefe");
+        logged["Timestamp"] = json!("2021-02-04T16:31:29.2460000Z");
+        let (_, _, metadata) = parser.parse_chunk(&logged.to_string()).unwrap();
+        assert_eq!(
+            metadata.id.as_deref(),
+            Some("13118d9b4c114f8485d9dec417e3aefe")
+        );
+        assert_eq!(
+            metadata.timestamp.unwrap().to_rfc3339(),
+            "2021-02-04T16:31:29.246+00:00"
+        );
+
+        let (_, _, metadata) = parser.parse_chunk(&event().to_string()).unwrap();
+        assert_eq!(metadata, ExampleMetadata::default());
+
+        logged["Timestamp"] = json!("yesterday");
+        assert!(parser.parse_chunk(&logged.to_string()).is_err());
+    }
+
+    #[test]
+    fn dsjson_original_label_cost() {
+        let mut original = event();
+        original["_original_label_cost"] = json!(-3.0);
+
+        let parser = parser_with_options(DsJsonOptions::default());
+        assert_relative_eq!(parse_label(&parser, original.clone()).unwrap().cost, -1.0);
+
+        let parser = parser_with_options(DsJsonOptions {
+            use_original_label_cost: true,
+            ..Default::default()
+        });
+        assert_relative_eq!(parse_label(&parser, original).unwrap().cost, -3.0);
+        // Events without it fall back to _label_cost.
+        assert_relative_eq!(parse_label(&parser, event()).unwrap().cost, -1.0);
+    }
+
+    #[test]
+    fn dsjson_invalid_events() {
+        let parser = parser_with_options(DsJsonOptions::default());
+        assert!(matches!(
+            parser.parse_chunk(r#"{"c": {"_multi": [{"a": 1}]"#),
+            Err(Error::InvalidJson(_))
+        ));
+        assert!(matches!(
+            parser.parse_chunk(r#"{"c": {"a": 1}}"#),
+            Err(Error::ParserError(_))
+        ));
+        assert!(matches!(
+            parser.parse_chunk(r#"{"c": {"a": ["x"], "_multi": [{"b": 1}]}}"#),
+            Err(Error::ParserError(_))
+        ));
+
+        assert!(matches!(
+            parser.parse_chunk(r#"{"c": {"_multi": [1]}}"#),
+            Err(Error::ParserError(_))
+        ));
+        assert!(matches!(
+            parser.parse_chunk(r#"{"c": 1}"#),
+            Err(Error::ParserError(_))
+        ));
+
+        // Nulls are treated as missing.
+        let (features, _, _) = parser
+            .parse_chunk(r#"{"c": {"a": null, "_multi": [{"b": 1, "c": null}]}}"#)
+            .unwrap();
+        let features: &CBAdfFeatures = features.as_inner().unwrap();
+        assert_eq!(features.actions.len(), 1);
+    }
+
+    #[test]
+    fn dsjson_extract_feature_names() {
+        let parser = parser_with_options(DsJsonOptions::default());
+        let input = json!({
+          "c": {
+            "s": {"u": 1},
+            "t": [{"v": "w"}],
+            "_multi": [{"a": {"x": 1}}, {"b": [0.5], "_tag": "y"}]
+          }
+        })
+        .to_string();
+        let names = parser.extract_feature_names(&input).unwrap();
+
+        let named = |name: &'static str| ParsedNamespaceInfo::Named(name.into());
+        let shared = [
+            (named("s"), vec![ParsedFeature::Simple { name: "u".into() }]),
+            (
+                named("t"),
+                vec![ParsedFeature::SimpleWithStringValue {
+                    name: "v".into(),
+                    value: "w".into(),
+                }],
+            ),
+        ];
+        let mut first: FeatureNames = shared.clone().into_iter().collect();
+        first.insert(named("a"), vec![ParsedFeature::Simple { name: "x".into() }]);
+        let mut second: FeatureNames = shared.into_iter().collect();
+        second.insert(named("b"), vec![ParsedFeature::Anonymous { offset: 0 }]);
+        assert_eq!(names, vec![first, second]);
+    }
 }
@@ -1,90 +1,258 @@
-use core::{f32, panic};
+use core::f32;
+use std::borrow::Cow;
 
-use crate::error::Result;
+use crate::dense_features::DenseFeatures;
+use crate::error::{Error, Result};
+use crate::hash::HashFunction;
 
 use crate::object_pool::Pool;
-use crate::parsers::ParsedFeature;
+use crate::parsers::{field, FeatureSink, HashingSink, NameSink, ParsedFeature};
 use crate::sparse_namespaced_features::{Namespace, SparseFeatures};
 use crate::types::{Features, Label, LabelType};
-use crate::{CBAdfFeatures, CBLabel, FeatureHash, FeatureMask, FeaturesType, SimpleLabel};
+use crate::{CBAdfFeatures, CBLabel, ExampleMetadata, FeaturesType, MixedFeatures, SimpleLabel};
 
-use super::{TextModeParser, TextModeParserFactory};
+use super::{FeatureNames, TextModeParser, TextModeParserFactory};
 
 use serde_json_borrow::Value;
 
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a bool",
+        Value::Number(_) => "a number",
+        Value::Str(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn mixed_array_error(ns_name: &str, first: &Value, item: &Value) -> Error {
+    Error::ParserError(format!(
+        "Arrays must contain only numbers or only strings, but namespace {} mixes {} with {}",
+        ns_name,
+        type_name(first),
+        type_name(item)
+    ))
+}
+
+/// Walks the contents of a namespace. Objects and arrays nested in an object
+/// become their own namespace, named `parent.key`. Nulls are treated as
+/// missing.
+fn walk_namespace<'a>(
+    sink: &mut impl FeatureSink<'a>,
+    ns_name: Cow<'a, str>,
+    value: &Value<'a>,
+) -> Result<()> {
+    if let Value::Null = value {
+        return Ok(());
+    }
+
+    sink.push_namespace(ns_name.clone());
+    let mut nested = Vec::new();
+    match value {
+        Value::Str(value) => sink.add_feature(
+            ParsedFeature::Simple {
+                name: value.clone(),
+            },
+            1.0,
+        ),
+        // The first non-null item decides whether this is an array of
+        // anonymous values or of feature names.
+        Value::Array(ar) => match ar.iter().find(|x| !matches!(x, Value::Null)) {
+            None => (),
+            Some(first @ Value::Number(_)) => {
+                for (offset, item) in ar.iter().enumerate() {
+                    match item {
+                        Value::Number(_) => sink.add_feature(
+                            ParsedFeature::Anonymous {
+                                offset: offset as u32,
+                            },
+                            item.as_f64().unwrap_or_default() as f32,
+                        ),
+                        Value::Null => (),
+                        _ => return Err(mixed_array_error(&ns_name, first, item)),
+                    }
+                }
+            }
+            Some(first @ Value::Str(_)) => {
+                for item in ar {
+                    match item {
+                        Value::Str(name) => {
+                            sink.add_feature(ParsedFeature::Simple { name: name.clone() }, 1.0)
+                        }
+                        Value::Null => (),
+                        _ => return Err(mixed_array_error(&ns_name, first, item)),
+                    }
+                }
+            }
+            Some(item) => {
+                return Err(Error::ParserError(format!(
+                    "Arrays must contain numbers or strings, but namespace {} contains {}",
+                    ns_name,
+                    type_name(item)
+                )))
+            }
+        },
+        Value::Object(contents) => {
+            for (key, value) in contents {
+                match value {
+                    Value::Number(_) => sink.add_feature(
+                        ParsedFeature::Simple {
+                            name: (*key).into(),
+                        },
+                        value.as_f64().unwrap_or_default() as f32,
+                    ),
+                    Value::Str(value) => sink.add_feature(
+                        ParsedFeature::SimpleWithStringValue {
+                            name: (*key).into(),
+                            value: value.clone(),
+                        },
+                        1.0,
+                    ),
+                    Value::Bool(true) => sink.add_feature(
+                        ParsedFeature::Simple {
+                            name: (*key).into(),
+                        },
+                        1.0,
+                    ),
+                    Value::Bool(false) | Value::Null => (),
+                    Value::Array(_) | Value::Object(_) => {
+                        nested.push((format!("{}.{}", ns_name, key), value))
+                    }
+                }
+            }
+        }
+        Value::Bool(_) | Value::Number(_) => {
+            return Err(Error::ParserError(format!(
+                "Namespace {} must be an object, array or string, but is {}",
+                ns_name,
+                type_name(value)
+            )))
+        }
+        Value::Null => unreachable!(),
+    }
+    sink.pop_namespace();
+
+    for (nested_name, nested_value) in nested {
+        walk_namespace(sink, nested_name.into(), nested_value)?;
+    }
+    Ok(())
+}
+
+/// Walks every namespace of a feature object. With `skip_dense`, namespaces
+/// which are dense in mixed features are left out.
+fn walk_features<'a>(
+    sink: &mut impl FeatureSink<'a>,
+    val: &Value<'a>,
+    skip_dense: bool,
+) -> Result<()> {
+    match val {
+        Value::Object(obj) => {
+            for (ns_name, value) in obj {
+                if skip_dense && as_dense_values(value).is_some() {
+                    continue;
+                }
+                walk_namespace(sink, (*ns_name).into(), value)?;
+            }
+            Ok(())
+        }
+        _ => Err(Error::ParserError("Features must be an object".to_owned())),
+    }
+}
+
 pub fn to_features(
     val: &Value,
     mut output: SparseFeatures,
     hash_seed: u32,
+    hash_function: HashFunction,
     num_bits: u8,
-) -> SparseFeatures {
+) -> Result<SparseFeatures> {
+    walk_features(
+        &mut HashingSink::new(&mut output, hash_seed, hash_function, num_bits)
+            .keep_empty_namespaces(),
+        val,
+        false,
+    )?;
+    Ok(output)
+}
+
+/// Returns the values of an array which only contains numbers.
+fn as_dense_values(value: &Value) -> Option<Vec<f32>> {
+    match value {
+        Value::Array(ar) if !ar.is_empty() => ar
+            .iter()
+            .map(|x| match x {
+                Value::Number(_) => x.as_f64().map(|x| x as f32),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Each namespace must be an array of numbers.
+pub fn to_dense_features(
+    val: &Value,
+    mut output: DenseFeatures,
+    hash_seed: u32,
+    hash_function: HashFunction,
+) -> Result<DenseFeatures> {
     match val {
         Value::Object(obj) => {
             for (ns_name, value) in obj {
-                let ns = output.get_or_create_namespace(Namespace::from_name(ns_name, hash_seed));
-                let ns_hash = ns.namespace().hash();
-                let mask = FeatureMask::from_num_bits(num_bits);
-                match value {
-                    Value::Str(_) => todo!(),
-                    Value::Array(ar) => match ar.first() {
-                        Some(Value::Number(_)) => {
-                            let it = (u32::from(ns_hash)..(u32::from(ns_hash) + ar.len() as u32))
-                                .map(|x| FeatureHash::from(x).mask(mask));
-                            ns.add_features_with_iter(
-                                it,
-                                ar.iter().map(|x| {
-                                    x.as_f64().expect("Arrays must contain the same type") as f32
-                                }),
-                            );
-                        }
-                        Some(Value::Str(_)) => {
-                            ns.reserve(ar.len());
-                            for string in ar {
-                                let feat = ParsedFeature::Simple {
-                                    name: string
-                                        .as_str()
-                                        .expect("Arrays must contain the same type"),
-                                };
-                                ns.add_feature(feat.hash(ns_hash).mask(mask), 1.0);
-                            }
-                        }
-                        Some(_) => panic!("Not a number or string"),
-                        None => todo!(),
-                    },
+                if let Value::Null = value {
+                    continue;
+                }
+                let values = as_dense_values(value).ok_or_else(|| {
+                    Error::ParserError(format!(
+                        "Dense features must be arrays of numbers, but namespace {} is not",
+                        ns_name
+                    ))
+                })?;
+                output.add_namespace_with_iter(
+                    Namespace::from_name(ns_name, hash_seed, hash_function),
+                    values,
+                );
+            }
+            Ok(output)
+        }
+        _ => Err(Error::ParserError("Features must be an object".to_owned())),
+    }
+}
 
-                    Value::Object(contents) => {
-                        for (key, value) in contents {
-                            match value {
-                                Value::Number(value) => {
-                                    let feat: ParsedFeature<'_> =
-                                        ParsedFeature::Simple { name: key };
-                                    ns.add_feature(
-                                        feat.hash(ns_hash).mask(mask),
-                                        value.as_f64().unwrap() as f32,
-                                    );
-                                }
-                                Value::Str(value) => {
-                                    let feat =
-                                        ParsedFeature::SimpleWithStringValue { name: key, value };
-                                    ns.add_feature(feat.hash(ns_hash).mask(mask), 1.0);
-                                }
-                                Value::Bool(value) => {
-                                    if *value {
-                                        let feat = ParsedFeature::Simple { name: key };
-                                        ns.add_feature(feat.hash(ns_hash).mask(mask), 1.0);
-                                    }
-                                }
-                                _ => todo!(),
-                            }
-                        }
-                    }
-                    _ => todo!(),
+/// Namespaces which are arrays of numbers are dense, all others are sparse.
+pub fn to_mixed_features(
+    val: &Value,
+    mut output: MixedFeatures,
+    hash_seed: u32,
+    hash_function: HashFunction,
+    num_bits: u8,
+) -> Result<MixedFeatures> {
+    match val {
+        Value::Object(obj) => {
+            for (ns_name, value) in obj {
+                match as_dense_values(value) {
+                    Some(values) => output.dense.add_namespace_with_iter(
+                        Namespace::from_name(ns_name, hash_seed, hash_function),
+                        values,
+                    ),
+                    None => walk_namespace(
+                        &mut HashingSink::new(
+                            &mut output.sparse,
+                            hash_seed,
+                            hash_function,
+                            num_bits,
+                        )
+                        .keep_empty_namespaces(),
+                        (*ns_name).into(),
+                        value,
+                    )?,
                 }
             }
+            Ok(output)
         }
-        _ => panic!("Not an object"),
+        _ => Err(Error::ParserError("Features must be an object".to_owned())),
     }
-    output
 }
 
 #[derive(Default)]
@@ -97,6 +265,7 @@ impl TextModeParserFactory for JsonParserFactory {
         features_type: FeaturesType,
         label_type: LabelType,
         hash_seed: u32,
+        hash_function: HashFunction,
         num_bits: u8,
         pool: std::sync::Arc<Pool<SparseFeatures>>,
     ) -> JsonParser {
@@ -104,6 +273,7 @@ impl TextModeParserFactory for JsonParserFactory {
             features_type,
             label_type,
             hash_seed,
+            hash_function,
             num_bits,
             pool,
         }
@@ -114,6 +284,7 @@ pub struct JsonParser {
     features_type: FeaturesType,
     label_type: LabelType,
     hash_seed: u32,
+    hash_function: HashFunction,
     num_bits: u8,
     pool: std::sync::Arc<Pool<SparseFeatures>>,
 }
@@ -122,34 +293,74 @@ impl<'a, 'b> JsonParser {
     fn parse_features(&self, json_input: &'a Value) -> Result<Features<'b>> {
         match self.features_type {
             FeaturesType::SparseSimple => match json_input.get("features") {
-                Value::Null => panic!("No features found"),
-                val => Ok(
-                    to_features(val, self.pool.get_object(), self.hash_seed, self.num_bits).into(),
-                ),
+                Value::Null => Err(Error::ParserError("No features found".to_owned())),
+                val => Ok(to_features(
+                    val,
+                    self.pool.get_object(),
+                    self.hash_seed,
+                    self.hash_function,
+                    self.num_bits,
+                )?
+                .into()),
             },
             FeaturesType::SparseCBAdf => {
                 let shared = match json_input.get("shared") {
                     Value::Null => None,
                     val => {
-                        let feats =
-                            to_features(val, self.pool.get_object(), self.hash_seed, self.num_bits);
+                        let feats = to_features(
+                            val,
+                            self.pool.get_object(),
+                            self.hash_seed,
+                            self.hash_function,
+                            self.num_bits,
+                        )?;
                         Some(feats)
                     }
                 };
 
                 let actions = match json_input.get("actions") {
-                    Value::Null => panic!("No actions found"),
+                    Value::Null => return Err(Error::ParserError("No actions found".to_owned())),
                     Value::Array(val) => val
                         .iter()
                         .map(|x| {
-                            to_features(x, self.pool.get_object(), self.hash_seed, self.num_bits)
+                            to_features(
+                                x,
+                                self.pool.get_object(),
+                                self.hash_seed,
+                                self.hash_function,
+                                self.num_bits,
+                            )
                         })
-                        .collect(),
-                    _ => panic!("Actions must be an array"),
+                        .collect::<Result<_>>()?,
+                    _ => return Err(Error::ParserError("Actions must be an array".to_owned())),
                 };
 
                 Ok(CBAdfFeatures { shared, actions }.into())
             }
+            FeaturesType::DenseSimple => match json_input.get("features") {
+                Value::Null => Err(Error::ParserError("No features found".to_owned())),
+                val => Ok(to_dense_features(
+                    val,
+                    DenseFeatures::new(),
+                    self.hash_seed,
+                    self.hash_function,
+                )?
+                .into()),
+            },
+            FeaturesType::MixedSimple => match json_input.get("features") {
+                Value::Null => Err(Error::ParserError("No features found".to_owned())),
+                val => Ok(to_mixed_features(
+                    val,
+                    MixedFeatures {
+                        dense: DenseFeatures::new(),
+                        sparse: self.pool.get_object(),
+                    },
+                    self.hash_seed,
+                    self.hash_function,
+                    self.num_bits,
+                )?
+                .into()),
+            },
         }
     }
 }
@@ -162,7 +373,7 @@ impl JsonParser {
                 Value::Number(val) => Some(SimpleLabel::from(val.as_f64().unwrap() as f32)),
                 val => {
                     let l: SimpleLabel =
-                        serde_json::from_value(serde_json::Value::from(val.clone())).unwrap();
+                        serde_json::from_value(serde_json::Value::from(val.clone()))?;
                     Some(l)
                 }
             }
@@ -171,8 +382,7 @@ impl JsonParser {
             LabelType::CB => Ok(match json_input.get("label") {
                 Value::Null => None,
                 val => {
-                    let l: CBLabel =
-                        serde_json::from_value(serde_json::Value::from(val.clone())).unwrap();
+                    let l: CBLabel = serde_json::from_value(serde_json::Value::from(val.clone()))?;
                     Some(l.into())
                 }
             }),
@@ -194,27 +404,87 @@ impl TextModeParser for JsonParser {
         Ok(Some(output_buffer))
     }
 
-    fn parse_chunk<'a, 'b>(&self, chunk: &'a str) -> Result<(Features<'b>, Option<Label>)> {
-        let json: Value = serde_json::from_str(chunk).expect("JSON was not well-formatted");
+    fn parse_chunk<'a, 'b>(
+        &self,
+        chunk: &'a str,
+    ) -> Result<(Features<'b>, Option<Label>, ExampleMetadata)> {
+        let json: Value = serde_json::from_str(chunk)?;
         let features = self.parse_features(&json)?;
         let label = self.parse_label(&json)?;
-        Ok((features, label))
+        let metadata = ExampleMetadata {
+            id: json.get("id").as_str().map(str::to_owned),
+            tag: json.get("tag").as_str().map(str::to_owned),
+            weight: json
+                .get("label")
+                .get("weight")
+                .as_f64()
+                .map(|weight| weight as f32),
+            ..Default::default()
+        };
+        Ok((features, label, metadata))
+    }
+
+    fn extract_feature_names<'a>(&self, chunk: &'a str) -> Result<Vec<FeatureNames<'a>>> {
+        let json: Value = serde_json::from_str(chunk)?;
+        let features = |value: &Value<'a>| -> Result<FeatureNames<'a>> {
+            let mut sink = NameSink::default();
+            walk_features(
+                &mut sink,
+                value,
+                self.features_type == FeaturesType::MixedSimple,
+            )?;
+            Ok(sink.into_names())
+        };
+        match self.features_type {
+            FeaturesType::SparseSimple | FeaturesType::MixedSimple => {
+                Ok(vec![features(field(&json, "features"))?])
+            }
+            FeaturesType::SparseCBAdf => {
+                // The shared features are learned on together with each
+                // action.
+                let shared = match field(&json, "shared") {
+                    Value::Null => FeatureNames::new(),
+                    shared => features(shared)?,
+                };
+                let Value::Array(actions) = field(&json, "actions") else {
+                    return Err(Error::ParserError("Actions must be an array".to_owned()));
+                };
+                actions
+                    .iter()
+                    .map(|action| {
+                        let mut sink = NameSink::with_names(shared.clone());
+                        walk_features(&mut sink, action, false)?;
+                        Ok(sink.into_names())
+                    })
+                    .collect()
+            }
+            FeaturesType::DenseSimple => Err(Error::InvalidArgument(
+                "Dense features are not hashed, so have no feature names".to_owned(),
+            )),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::hash::HashFunction;
     use std::sync::Arc;
 
     use approx::assert_relative_eq;
     use serde_json::json;
 
     use crate::{
+        dense_features::DenseFeatures,
+        error::Error,
         object_pool::Pool,
-        parsers::{JsonParserFactory, TextModeParser, TextModeParserFactory},
+        parsers::{
+            JsonParserFactory, ParsedFeature, ParsedNamespaceInfo, TextModeParser,
+            TextModeParserFactory,
+        },
         sparse_namespaced_features::{Namespace, SparseFeatures},
         utils::AsInner,
-        CBAdfFeatures, CBLabel, FeaturesType, LabelType, SimpleLabel,
+        CBAdfFeatures, CBLabel, ExampleMetadata, FeaturesType, LabelType, MixedFeatures,
+        SimpleLabel,
     };
     #[test]
     fn json_parse_cb() {
@@ -254,12 +524,13 @@ mod test {
             FeaturesType::SparseCBAdf,
             LabelType::CB,
             0,
+            HashFunction::default(),
             18,
             pool,
         );
 
         let input = json_obj.to_string();
-        let (features, label) = parser.parse_chunk(&input).unwrap();
+        let (features, label, _) = parser.parse_chunk(&input).unwrap();
         let cb_label: &CBLabel = label.as_ref().unwrap().as_inner().unwrap();
         assert_eq!(cb_label.action, 3);
         assert_relative_eq!(cb_label.cost, 0.0);
@@ -274,12 +545,12 @@ mod test {
         assert_eq!(shared_default_ns.iter().count(), 1);
 
         let shared_from_url_ns = shared
-            .get_namespace(Namespace::from_name("FromUrl", 0))
+            .get_namespace(Namespace::from_name("FromUrl", 0, HashFunction::default()))
             .unwrap();
         assert_eq!(shared_from_url_ns.iter().count(), 3);
 
         let shared_numbers_ns = shared
-            .get_namespace(Namespace::from_name("numbers", 0))
+            .get_namespace(Namespace::from_name("numbers", 0, HashFunction::default()))
             .unwrap();
         assert_eq!(shared_numbers_ns.iter().count(), 2);
         assert_relative_eq!(
@@ -290,9 +561,13 @@ mod test {
         let action = cb_feats.actions.get(0).unwrap();
         assert_eq!(action.namespaces().count(), 2);
         assert!(action.get_namespace(Namespace::Default).is_none());
-        let action_i_ns = action.get_namespace(Namespace::from_name("i", 0)).unwrap();
+        let action_i_ns = action
+            .get_namespace(Namespace::from_name("i", 0, HashFunction::default()))
+            .unwrap();
         assert_eq!(action_i_ns.iter().count(), 2);
-        let action_j_ns = action.get_namespace(Namespace::from_name("j", 0)).unwrap();
+        let action_j_ns = action
+            .get_namespace(Namespace::from_name("j", 0, HashFunction::default()))
+            .unwrap();
         assert_eq!(action_j_ns.iter().count(), 4);
     }
 
@@ -322,12 +597,13 @@ mod test {
             FeaturesType::SparseSimple,
             LabelType::Simple,
             0,
+            HashFunction::default(),
             18,
             pool,
         );
 
         let input = json_obj.to_string();
-        let (features, label) = parser.parse_chunk(&input).unwrap();
+        let (features, label, _) = parser.parse_chunk(&input).unwrap();
         let lbl: &SimpleLabel = label.as_ref().unwrap().as_inner().unwrap();
         assert_relative_eq!(lbl.value(), 0.2);
         assert_relative_eq!(lbl.weight(), 0.4);
@@ -338,12 +614,12 @@ mod test {
         assert_eq!(features_default_ns.iter().count(), 1);
 
         let features_from_url_ns = features
-            .get_namespace(Namespace::from_name("FromUrl", 0))
+            .get_namespace(Namespace::from_name("FromUrl", 0, HashFunction::default()))
             .unwrap();
         assert_eq!(features_from_url_ns.iter().count(), 3);
 
         let features_numbers_ns = features
-            .get_namespace(Namespace::from_name("numbers", 0))
+            .get_namespace(Namespace::from_name("numbers", 0, HashFunction::default()))
             .unwrap();
         assert_eq!(features_numbers_ns.iter().count(), 2);
         assert_relative_eq!(
@@ -351,4 +627,301 @@ mod test {
             9.6
         );
     }
+
+    #[test]
+    fn json_parse_dense_and_mixed() {
+        let json_obj = json!({
+            "label": 1.0,
+            "features" : {
+                "embedding": [0.5, -1, 2],
+                "words": ["hello", "world"]
+            }
+        });
+        let input = json_obj.to_string();
+        let embedding = Namespace::from_name("embedding", 0, HashFunction::default());
+        let words = Namespace::from_name("words", 0, HashFunction::default());
+
+        let parser = JsonParserFactory.create(
+            FeaturesType::MixedSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (features, _, _) = parser.parse_chunk(&input).unwrap();
+        let features: &MixedFeatures = features.as_inner().unwrap();
+        assert_eq!(
+            features.dense.get_namespace(embedding),
+            Some(&[0.5, -1.0, 2.0][..])
+        );
+        assert!(features.dense.get_namespace(words).is_none());
+        assert_eq!(features.sparse.get_namespace(words).unwrap().size(), 2);
+        assert!(features.sparse.get_namespace(embedding).is_none());
+
+        // Dense features only accept arrays of numbers.
+        let parser = JsonParserFactory.create(
+            FeaturesType::DenseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        assert!(matches!(
+            parser.parse_chunk(&input),
+            Err(Error::ParserError(_))
+        ));
+        let input = json!({"features": {"embedding": [0.5, -1, 2]}}).to_string();
+        let (features, label, _) = parser.parse_chunk(&input).unwrap();
+        assert!(label.is_none());
+        let features: &DenseFeatures = features.as_inner().unwrap();
+        assert_eq!(features.values(), &[0.5, -1.0, 2.0]);
+    }
+
+    #[test]
+    fn json_parse_metadata() {
+        let parser = JsonParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        let input = json!({
+            "id": "row-1",
+            "tag": "holdout",
+            "label": {"value": 1.0, "weight": 0.5},
+            "features": {"a": ["x"]}
+        })
+        .to_string();
+        let (_, _, metadata) = parser.parse_chunk(&input).unwrap();
+        assert_eq!(
+            metadata,
+            ExampleMetadata {
+                id: Some("row-1".to_owned()),
+                tag: Some("holdout".to_owned()),
+                weight: Some(0.5),
+                ..Default::default()
+            }
+        );
+
+        let input = json!({"label": 1.0, "features": {"a": ["x"]}}).to_string();
+        let (_, _, metadata) = parser.parse_chunk(&input).unwrap();
+        assert_eq!(metadata, ExampleMetadata::default());
+    }
+
+    fn parse_sparse(input: serde_json::Value) -> crate::error::Result<SparseFeatures> {
+        let parser = JsonParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (features, _, _) = parser.parse_chunk(&input.to_string())?;
+        let features: &SparseFeatures = features.as_inner().unwrap();
+        Ok(features.clone())
+    }
+
+    #[test]
+    fn json_parse_strings_nulls_and_nesting() {
+        let features = parse_sparse(json!({
+            "features": {
+                "a": "x",
+                "b": {"c": {"d": 2, "e": {"f": true}}, "g": ["y"], "h": null},
+                "i": [],
+                "j": null,
+                "k": ["z", null]
+            }
+        }))
+        .unwrap();
+        let expected = parse_sparse(json!({
+            "features": {
+                "a": ["x"],
+                "b": {},
+                "b.c": {"d": 2},
+                "b.c.e": {"f": true},
+                "b.g": ["y"],
+                "i": [],
+                "k": ["z"]
+            }
+        }))
+        .unwrap();
+        assert_eq!(features, expected);
+
+        let j = Namespace::from_name("j", 0, HashFunction::default());
+        assert!(features.get_namespace(j).is_none());
+        let i = Namespace::from_name("i", 0, HashFunction::default());
+        assert_eq!(features.get_namespace(i).unwrap().size(), 0);
+
+        // Nulls in arrays of numbers keep the position of the other values.
+        let features = parse_sparse(json!({"features": {"n": [1, null, 3]}})).unwrap();
+        let expected = parse_sparse(json!({"features": {"n": [1, 0, 3]}})).unwrap();
+        let n = Namespace::from_name("n", 0, HashFunction::default());
+        let values: Vec<_> = features.get_namespace(n).unwrap().iter().collect();
+        let expected_values: Vec<_> = expected.get_namespace(n).unwrap().iter().collect();
+        assert_eq!(values, vec![expected_values[0], expected_values[2]]);
+    }
+
+    #[test]
+    fn json_parse_errors() {
+        for features in [
+            json!({"a": [1, "x"]}),
+            json!({"a": ["x", 1]}),
+            json!({"a": [true]}),
+            json!({"a": [{"b": 1}]}),
+            json!({"a": {"b": [null, 1, "x"]}}),
+            json!({"a": 1}),
+            json!({"a": false}),
+            json!([1, 2]),
+        ] {
+            let result = parse_sparse(json!({ "features": features.clone() }));
+            assert!(
+                matches!(result, Err(Error::ParserError(_))),
+                "{} should not parse",
+                features
+            );
+        }
+        assert!(matches!(
+            parse_sparse(json!({"label": 1.0})),
+            Err(Error::ParserError(_))
+        ));
+
+        let parser = JsonParserFactory.create(
+            FeaturesType::SparseCBAdf,
+            LabelType::CB,
+            0,
+            HashFunction::default(),
+            18,
+            Arc::new(Pool::new()),
+        );
+        assert!(matches!(
+            parser.parse_chunk(r#"{"shared": {"a": "x"}}"#),
+            Err(Error::ParserError(_))
+        ));
+        assert!(matches!(
+            parser.parse_chunk(r#"{"actions": {"a": "x"}}"#),
+            Err(Error::ParserError(_))
+        ));
+        assert!(matches!(
+            parser.parse_chunk(r#"{"actions": [{"a": "x"}"#),
+            Err(Error::InvalidJson(_))
+        ));
+        assert!(matches!(
+            parser.parse_chunk(r#"{"label": {"action": "x"}, "actions": []}"#),
+            Err(Error::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn json_extract_feature_names() {
+        let parser = |features_type, label_type| {
+            JsonParserFactory.create(
+                features_type,
+                label_type,
+                0,
+                HashFunction::default(),
+                18,
+                Arc::new(Pool::new()),
+            )
+        };
+        let named = |name: &'static str| ParsedNamespaceInfo::Named(name.into());
+        let simple = |name: &'static str| ParsedFeature::Simple { name: name.into() };
+
+        let input = json!({
+            "features": {"a": ["x", "y"], "b": {"c": 2}, "e": [0.5, 1]}
+        })
+        .to_string();
+        let names = parser(FeaturesType::SparseSimple, LabelType::Simple)
+            .extract_feature_names(&input)
+            .unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0][&named("a")], vec![simple("x"), simple("y")]);
+        assert_eq!(names[0][&named("b")], vec![simple("c")]);
+        assert_eq!(
+            names[0][&named("e")],
+            vec![
+                ParsedFeature::Anonymous { offset: 0 },
+                ParsedFeature::Anonymous { offset: 1 }
+            ]
+        );
+
+        // Dense namespaces are not hashed.
+        let names = parser(FeaturesType::MixedSimple, LabelType::Simple)
+            .extract_feature_names(&input)
+            .unwrap();
+        assert!(!names[0].contains_key(&named("e")));
+        assert!(parser(FeaturesType::DenseSimple, LabelType::Simple)
+            .extract_feature_names(&input)
+            .is_err());
+
+        // Every action is returned with the shared features.
+        let input = json!({
+            "shared": {"s": ["u"]},
+            "actions": [{"a": ["x"]}, {"b": ["y"]}]
+        })
+        .to_string();
+        let names = parser(FeaturesType::SparseCBAdf, LabelType::CB)
+            .extract_feature_names(&input)
+            .unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0].len(), 2);
+        assert_eq!(names[0][&named("s")], vec![simple("u")]);
+        assert_eq!(names[0][&named("a")], vec![simple("x")]);
+        assert_eq!(names[1].len(), 2);
+        assert_eq!(names[1][&named("s")], vec![simple("u")]);
+        assert_eq!(names[1][&named("b")], vec![simple("y")]);
+    }
+
+    #[test]
+    fn json_documented_examples_parse() {
+        let docs = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../docs/source/input_formats.md"
+        ))
+        .unwrap();
+
+        let mut variants = Vec::new();
+        let mut lines = docs.lines();
+        while let Some(line) = lines.next() {
+            let Some(variant) = line
+                .strip_prefix("<!-- example: ")
+                .and_then(|rest| rest.strip_suffix(" -->"))
+            else {
+                continue;
+            };
+            assert_eq!(lines.next(), Some("```json"));
+            let block: Vec<&str> = lines.by_ref().take_while(|line| *line != "```").collect();
+            let value: serde_json::Value = serde_json::from_str(&block.join("\n"))
+                .unwrap_or_else(|err| panic!("{} example is not valid JSON: {}", variant, err));
+
+            let (features_type, label_type, input) = match variant {
+                "features" => (
+                    FeaturesType::SparseSimple,
+                    LabelType::Simple,
+                    json!({ "features": value }),
+                ),
+                "simple" => (FeaturesType::SparseSimple, LabelType::Simple, value),
+                "mixed" => (FeaturesType::MixedSimple, LabelType::Simple, value),
+                "cb" => (FeaturesType::SparseCBAdf, LabelType::CB, value),
+                _ => panic!("Unknown example variant {}", variant),
+            };
+            let parser = JsonParserFactory.create(
+                features_type,
+                label_type,
+                0,
+                HashFunction::default(),
+                18,
+                Arc::new(Pool::new()),
+            );
+            parser
+                .parse_chunk(&input.to_string())
+                .unwrap_or_else(|err| panic!("{} example does not parse: {}", variant, err));
+            variants.push(variant);
+        }
+        assert_eq!(variants, ["features", "simple", "mixed", "cb"]);
+    }
 }
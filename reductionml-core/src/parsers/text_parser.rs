@@ -1,9 +1,13 @@
-use std::{io::BufRead, sync::Arc};
+use std::{borrow::Cow, io::BufRead, sync::Arc};
 
 use crate::{
-    error::Result, object_pool::Pool, parsers::ParsedFeature,
-    sparse_namespaced_features::SparseFeatures, workspace::Workspace, Features, FeaturesType,
-    Label, LabelType,
+    error::{Error, Result},
+    hash::HashFunction,
+    object_pool::Pool,
+    parsers::ParsedFeature,
+    sparse_namespaced_features::SparseFeatures,
+    workspace::Workspace,
+    ExampleMetadata, Features, FeaturesType, Label, LabelType,
 };
 
 pub trait TextModeParserFactory {
@@ -13,6 +17,7 @@ pub trait TextModeParserFactory {
         features_type: FeaturesType,
         label_type: LabelType,
         hash_seed: u32,
+        hash_function: HashFunction,
         num_bits: u8,
         pool: Arc<Pool<SparseFeatures>>,
     ) -> Self::Parser;
@@ -25,29 +30,42 @@ pub trait TextModeParserFactory {
                 .input_features_type(),
             workspace.get_entry_reduction().types().input_label_type(),
             workspace.global_config().hash_seed(),
+            workspace.global_config().hash_function(),
             workspace.global_config().num_bits(),
             workspace.features_pool().clone(),
         )
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ParsedNamespaceInfo<'a> {
-    Named(&'a str),
+    Named(Cow<'a, str>),
     Default,
 }
 
+/// The features of each namespace before they are hashed.
+pub type FeatureNames<'a> =
+    std::collections::HashMap<ParsedNamespaceInfo<'a>, Vec<ParsedFeature<'a>>>;
+
 pub trait TextModeParser: Sync {
     fn get_next_chunk(
         &self,
         input: &mut dyn BufRead,
         output_buffer: String,
     ) -> Result<Option<String>>;
-    fn parse_chunk<'a, 'b>(&self, chunk: &'a str) -> Result<(Features<'b>, Option<Label>)>;
-    fn extract_feature_names<'a>(
+    /// Parses a chunk into the features, label and metadata of an example.
+    /// Formats which don't record metadata return the default.
+    fn parse_chunk<'a, 'b>(
         &self,
-        _chunk: &'a str,
-    ) -> Result<std::collections::HashMap<ParsedNamespaceInfo<'a>, Vec<ParsedFeature<'a>>>> {
-        todo!()
+        chunk: &'a str,
+    ) -> Result<(Features<'b>, Option<Label>, ExampleMetadata)>;
+    /// Returns the features of the chunk before they are hashed, as one set of
+    /// names for each group of features which are learned on together. That
+    /// is one for a single line example, or one for each action of a
+    /// multi-line example, including the shared features.
+    fn extract_feature_names<'a>(&self, _chunk: &'a str) -> Result<Vec<FeatureNames<'a>>> {
+        Err(Error::InvalidArgument(
+            "Extracting feature names is not supported for this data format".to_owned(),
+        ))
     }
 }
@@ -1,19 +1,21 @@
 use core::f32;
+use std::borrow::Cow;
 
 use derive_more::TryInto;
 
 use smallvec::SmallVec;
 
 use crate::error::{Error, Result};
+use crate::hash::HashFunction;
 
 use crate::object_pool::Pool;
 use crate::parsers::ParsedFeature;
 use crate::sparse_namespaced_features::{Namespace, SparseFeatures};
-use crate::types::{Features, Label, LabelType};
+use crate::types::{ExampleMetadata, Features, Label, LabelType};
 use crate::utils::AsInner;
 use crate::{CBAdfFeatures, CBLabel, FeatureMask, FeaturesType, SimpleLabel};
 
-use super::{ParsedNamespaceInfo, TextModeParser, TextModeParserFactory};
+use super::{FeatureNames, ParsedNamespaceInfo, TextModeParser, TextModeParserFactory};
 
 #[derive(Clone, Copy)]
 struct CBTextLabel {
@@ -44,23 +46,29 @@ impl AsInner<CBTextLabel> for TextLabel {
     }
 }
 
-// TODO work out where to put tag.
-// Idea - tag is not a concept here but for the cases where it was necessary (ccb) it will be folded into the feature type
 fn finalize_parsed_result_singleline<'a>(
     parsed: TextParseResult,
     _num_bits: u8,
     dest: SparseFeatures,
-) -> (Features<'a>, Option<Label>) {
+) -> (Features<'a>, Option<Label>, ExampleMetadata) {
     let hashed_sparse_features = Features::SparseSimple(dest);
+    let mut metadata = ExampleMetadata {
+        tag: parsed.tag.map(Cow::into_owned),
+        ..Default::default()
+    };
     match parsed.label {
         // TODO fix
-        Some(TextLabel::Simple(x, weight)) => (
-            hashed_sparse_features,
-            Some(Label::Simple(SimpleLabel::new(x, weight.unwrap_or(1.0)))),
-        ),
+        Some(TextLabel::Simple(x, weight)) => {
+            metadata.weight = weight;
+            (
+                hashed_sparse_features,
+                Some(Label::Simple(SimpleLabel::new(x, weight.unwrap_or(1.0)))),
+                metadata,
+            )
+        }
         // TODO binary
         Some(_) => todo!(),
-        None => (hashed_sparse_features, None),
+        None => (hashed_sparse_features, None, metadata),
     }
 }
 
@@ -77,29 +85,55 @@ where
 {
     match (expected_label, expected_features) {
         (LabelType::CB, FeaturesType::SparseCBAdf) => {
+            let txt_labels = parsed
+                .map(|x| {
+                    x.label
+                        .as_ref()
+                        .and_then(AsInner::<CBTextLabel>::as_inner)
+                        .copied()
+                        .ok_or(Error::ParserError("Expected a CB label".to_owned()))
+                })
+                .collect::<Result<SmallVec<[CBTextLabel; 4]>>>()?;
             // First thing to do is to determine if there is a shared example.
-            let mut txt_labels_iter = parsed.map(|x| x.label.unwrap()).peekable();
-            // let mut feats_iter = feats.into_iter();
-            let first_label: &CBTextLabel = txt_labels_iter
-                .peek()
+            let first_is_shared = txt_labels
+                .first()
                 .ok_or(Error::InvalidArgument("".to_owned()))?
-                .as_inner()
-                .expect("Label should be CB");
-            let first_is_shared = first_label.shared;
-
-            // TODO assert not more than 1 is shared.
-            let shared_ex = if first_is_shared {
-                // Consume shared token
-                txt_labels_iter.next();
-                Some(feats_iter.next().unwrap())
+                .shared;
+
+            let (shared_ex, shared_label, action_labels) = if first_is_shared {
+                (
+                    Some(feats_iter.next().unwrap()),
+                    txt_labels[0].acp,
+                    &txt_labels[1..],
+                )
             } else {
-                None
+                (None, None, &txt_labels[..])
             };
 
+            // A label on the shared line gives the chosen action as an index
+            // into the action lines.
+            let mut label: Option<CBLabel> = shared_label.map(|(a, c, p)| CBLabel {
+                action: a as usize,
+                cost: c,
+                probability: p,
+            });
+            if let Some(lbl) = &label {
+                if lbl.action >= action_labels.len() {
+                    return Err(Error::ParserError(format!(
+                        "The shared label chooses action {} but there are only {} actions",
+                        lbl.action,
+                        action_labels.len()
+                    )));
+                }
+            }
+
             // Find the labelled action.
-            let mut label: Option<CBLabel> = None;
-            for (counter, action_label) in txt_labels_iter.enumerate() {
-                let lbl: &CBTextLabel = action_label.as_inner().expect("Label should be CB");
+            for (counter, lbl) in action_labels.iter().enumerate() {
+                if lbl.shared {
+                    return Err(Error::ParserError(
+                        "Only the first line of an example can be shared".to_owned(),
+                    ));
+                }
                 if let Some((_a, c, p)) = lbl.acp {
                     if label.is_some() {
                         return Err(Error::InvalidArgument(
@@ -127,100 +161,223 @@ where
 }
 
 struct TextParseResult<'a> {
-    _tag: Option<&'a str>,
+    tag: Option<Cow<'a, str>>,
     // namespaces: Vec<ParsedNamespace<'a>>,
     label: Option<TextLabel>,
 }
 
+/// Returns the index of the first byte matching `delimiter` that is not
+/// escaped with a backslash. When `quotes` is set, delimiters between double
+/// quotes are skipped too.
+fn find_unescaped(text: &str, quotes: bool, delimiter: impl Fn(u8) -> bool) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut in_quotes = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            // Delimiters are all ASCII so skipping a single byte never lands
+            // on one in the middle of a multi-byte character.
+            b'\\' => i += 1,
+            b'"' if quotes => in_quotes = !in_quotes,
+            byte if !in_quotes && delimiter(byte) => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+struct SplitUnescaped<'a, F> {
+    rest: Option<&'a str>,
+    quotes: bool,
+    delimiter: F,
+}
+
+impl<'a, F: Fn(u8) -> bool> Iterator for SplitUnescaped<'a, F> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest?;
+        match find_unescaped(rest, self.quotes, &self.delimiter) {
+            Some(i) => {
+                self.rest = Some(&rest[i + 1..]);
+                Some(&rest[..i])
+            }
+            None => {
+                self.rest = None;
+                Some(rest)
+            }
+        }
+    }
+}
+
+/// Splits `text` at each unescaped delimiter, like [`str::split`].
+fn split_unescaped<F: Fn(u8) -> bool>(
+    text: &str,
+    quotes: bool,
+    delimiter: F,
+) -> SplitUnescaped<'_, F> {
+    SplitUnescaped {
+        rest: Some(text),
+        quotes,
+        delimiter,
+    }
+}
+
+/// Splits `text` at unescaped whitespace, like [`str::split_ascii_whitespace`].
+fn split_tokens(text: &str, quotes: bool) -> impl Iterator<Item = &str> {
+    split_unescaped(text, quotes, |x| x.is_ascii_whitespace()).filter(|x| !x.is_empty())
+}
+
+/// Splits a token at the first unescaped colon into a name and a value.
+fn split_value(token: &str) -> (&str, Option<&str>) {
+    match find_unescaped(token, false, |x| x == b':') {
+        Some(i) => (&token[..i], Some(&token[i + 1..])),
+        None => (token, None),
+    }
+}
+
+/// Removes the backslashes which escape characters in names, values and tags.
+/// Only allocates if there is something to remove.
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next().unwrap_or('\\')),
+            c => unescaped.push(c),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+fn parse_float(text: &str, what: &str) -> Result<f32> {
+    fast_float::parse(text)
+        .map_err(|_| Error::ParserError(format!("Failed to parse {}: {}", what, text)))
+}
+
+fn parse_cb_label(text: &str) -> Result<(u32, f32, f32)> {
+    let parts: SmallVec<[&str; 3]> = text.split(':').collect();
+    match parts.as_slice() {
+        [action, cost, probability] => Ok((
+            action.parse().map_err(|_| {
+                Error::ParserError(format!("Failed to parse CB label action: {}", action))
+            })?,
+            parse_float(cost, "CB label cost")?,
+            parse_float(probability, "CB label probability")?,
+        )),
+        _ => Err(Error::ParserError(format!(
+            "CB labels must be action:cost:probability but found: {}",
+            text
+        ))),
+    }
+}
+
 fn parse_label(tokens: &[&str], label_type: LabelType) -> Result<Option<TextLabel>> {
     match label_type {
-        LabelType::Simple => match tokens.len() {
-            0 => Ok(None),
-            1 => Ok(Some(TextLabel::Simple(
-                fast_float::parse(tokens[0]).unwrap(),
-                None,
-            ))),
-            2 => Ok(Some(TextLabel::Simple(
-                fast_float::parse(tokens[0]).unwrap(),
-                Some(fast_float::parse(tokens[1]).unwrap()),
+        LabelType::Simple => match tokens {
+            [] => Ok(None),
+            [value] => Ok(Some(TextLabel::Simple(parse_float(value, "label")?, None))),
+            [value, weight] => Ok(Some(TextLabel::Simple(
+                parse_float(value, "label")?,
+                Some(parse_float(weight, "label weight")?),
             ))),
             // Initial not currently supported...
-            3 => todo!(),
-            _ => todo!(),
+            [_, _, _] => Err(Error::ParserError(
+                "Initial values in simple labels are not supported".to_owned(),
+            )),
+            _ => Err(Error::ParserError(format!(
+                "Too many tokens in simple label: {}",
+                tokens.join(" ")
+            ))),
         },
         LabelType::Binary => todo!(),
-        LabelType::CB => match tokens.iter().next() {
-            None => Ok(Some(TextLabel::CB(CBTextLabel {
+        LabelType::CB => match tokens {
+            [] => Ok(Some(TextLabel::CB(CBTextLabel {
                 shared: false,
                 acp: None,
             }))),
-            Some(value) if value.trim() == "shared" => Ok(Some(TextLabel::CB(CBTextLabel {
+            ["shared"] => Ok(Some(TextLabel::CB(CBTextLabel {
                 shared: true,
                 acp: None,
             }))),
-            Some(value) => {
-                let mut tokens = value.split(':');
-                let action = tokens.next().unwrap().parse().unwrap();
-                let cost = fast_float::parse(tokens.next().unwrap()).unwrap();
-                let probability = fast_float::parse(tokens.next().unwrap()).unwrap();
-
-                // TODO: check that there are no more tokens
-
-                Ok(Some(TextLabel::CB(CBTextLabel {
-                    shared: false,
-                    acp: Some((action, cost, probability)),
-                })))
-            }
+            ["shared", label] => Ok(Some(TextLabel::CB(CBTextLabel {
+                shared: true,
+                acp: Some(parse_cb_label(label)?),
+            }))),
+            [label] => Ok(Some(TextLabel::CB(CBTextLabel {
+                shared: false,
+                acp: Some(parse_cb_label(label)?),
+            }))),
+            _ => Err(Error::ParserError(format!(
+                "Too many tokens in CB label: {}",
+                tokens.join(" ")
+            ))),
         },
     }
 }
 
-// TODO - consider conditionally allowing a feature whose name is a number ONLY to be interpreted as an anonymous features
-// This would be to mimic VW's hash "mode" of all vs txt
-fn parse_feature<'a>(feature: &'a str, offset_counter: &mut u32) -> (ParsedFeature<'a>, f32) {
-    // Check if char 0 is a :
-    let first_char_is_colon = feature.starts_with(':');
-    if first_char_is_colon {
-        // Anonymous feature
-        let value = fast_float::parse(&feature[1..]);
-        if let Ok(value) = value {
-            let offset_to_use = *offset_counter;
-            *offset_counter += 1;
-            (
-                ParsedFeature::Anonymous {
-                    offset: offset_to_use,
-                },
-                value,
-            )
-        } else {
-            return (
-                ParsedFeature::SimpleWithStringValue {
-                    name: "",
-                    value: feature[1..].trim(),
-                },
-                1.0,
-            );
-        }
+/// Features named by an integer are hashed to that offset from the namespace,
+/// as VW does. This only applies with Murmur3, which is used for VW
+/// compatibility, so that models hashed with other functions keep their
+/// indices.
+fn parse_feature_index(name: &str, hash_function: HashFunction) -> Option<u32> {
+    if hash_function == HashFunction::Murmur3 && name.bytes().all(|x| x.is_ascii_digit()) {
+        name.parse().ok()
     } else {
-        // Named feature
-        let mut tokens = feature.split(':');
-        let name = tokens.next().unwrap();
-        match tokens.next() {
-            Some(value) => {
-                if let Ok(value) = fast_float::parse(value) {
-                    (ParsedFeature::Simple { name }, value)
-                } else {
-                    (
-                        ParsedFeature::SimpleWithStringValue {
-                            name,
-                            value: value.trim(),
-                        },
-                        1.0,
-                    )
-                }
-            }
-            None => (ParsedFeature::Simple { name }, 1.0),
+        None
+    }
+}
+
+fn parse_feature<'a>(
+    feature: &'a str,
+    offset_counter: &mut u32,
+    hash_function: HashFunction,
+) -> Result<(ParsedFeature<'a>, f32)> {
+    let (name, value) = split_value(feature);
+    let value = match value {
+        None => 1.0,
+        Some("") => {
+            return Err(Error::ParserError(format!(
+                "Missing value for feature: {}",
+                feature
+            )))
         }
+        Some(value) => match fast_float::parse(value) {
+            Ok(value) => value,
+            Err(_) => {
+                return Ok((
+                    ParsedFeature::SimpleWithStringValue {
+                        name: unescape(name),
+                        value: unescape(value),
+                    },
+                    1.0,
+                ))
+            }
+        },
+    };
+    if name.is_empty() {
+        // Anonymous feature
+        let offset_to_use = *offset_counter;
+        *offset_counter += 1;
+        Ok((
+            ParsedFeature::Anonymous {
+                offset: offset_to_use,
+            },
+            value,
+        ))
+    } else if let Some(offset) = parse_feature_index(name, hash_function) {
+        Ok((ParsedFeature::Anonymous { offset }, value))
+    } else {
+        Ok((
+            ParsedFeature::Simple {
+                name: unescape(name),
+            },
+            value,
+        ))
     }
 }
 
@@ -228,110 +385,113 @@ fn parse_namespace_inline(
     namespace_segment: &str,
     dest_namespace: &mut SparseFeatures,
     hash_seed: u32,
+    hash_function: HashFunction,
     num_bits: u8,
 ) -> Result<()> {
-    // Check if first char is a space or not
-    let first_char_is_space = namespace_segment.starts_with(' ');
-    let mut tokens = namespace_segment.split_ascii_whitespace();
+    let (remaining, (namespace_name, namespace_value)) = parse_namespace_info(namespace_segment)?;
 
-    let (namespace_name, namespace_value) = if first_char_is_space {
-        // Anonymous namespace
-        (" ", 1.0)
-    } else {
-        let namespace_info_token = tokens.next().unwrap();
-        let mut namespace_info_tokens = namespace_info_token.split(':');
-        let name = namespace_info_tokens.next().unwrap();
-        let value = match namespace_info_tokens.next() {
-            Some(value) => fast_float::parse(value).unwrap(),
-            None => 1.0,
-        };
-
-        (name, value)
+    let dest = match &namespace_name {
+        ParsedNamespaceInfo::Named(name) => dest_namespace.get_or_create_named_namespace(
+            Namespace::from_name(name, hash_seed, hash_function),
+            name.as_ref(),
+        ),
+        ParsedNamespaceInfo::Default => dest_namespace.get_or_create_namespace(Namespace::Default),
     };
-
-    let namespace_def = Namespace::from_name(namespace_name, hash_seed);
-    let namespace_hash = namespace_def.hash();
-
-    let dest = dest_namespace.get_or_create_namespace(namespace_def);
+    let namespace_hash = dest.namespace().hash();
+    let mask = FeatureMask::from_num_bits(num_bits);
     let mut offset_counter = 0;
-    for token in tokens {
-        let (parsed_feat, feat_value) = parse_feature(token, &mut offset_counter);
-        // let this_ns = dest.get_or_create_namespace_with_capacity(namespace_hash, features.len());
-        let feature_hash = parsed_feat.hash(namespace_hash);
-        let masked_hash = feature_hash.mask(FeatureMask::from_num_bits(num_bits));
-        dest.add_feature(masked_hash, feat_value * namespace_value);
+    for token in split_tokens(remaining, false) {
+        let (parsed_feat, feat_value) = parse_feature(token, &mut offset_counter, hash_function)?;
+        let feature_hash = parsed_feat.hash(namespace_hash, hash_function);
+        dest.add_feature(feature_hash.mask(mask), feat_value * namespace_value);
     }
 
     Ok(())
 }
 
-fn parse_namespace_info_token(namespace_segment: &str) -> Result<(&str, f32)> {
-    let mut tokens: std::str::Split<char> = namespace_segment.split(':');
-    let name = tokens
-        .next()
-        .ok_or(Error::ParserError("Expected namespace name".to_owned()))?;
-    let value = match tokens.next() {
-        Some(value) => fast_float::parse(value).map_err(|err| {
-            Error::ParserError(format!("Failed to parse namespace value: {}", err))
-        })?,
+fn parse_namespace_info_token(namespace_segment: &str) -> Result<(Cow<'_, str>, f32)> {
+    let (name, value) = split_value(namespace_segment);
+    let value = match value {
+        Some(value) => parse_float(value, "namespace value")?,
         None => 1.0,
     };
 
-    Ok((name, value))
+    Ok((unescape(name), value))
 }
 
 // "Consumes" some amount of input and returns the namespace info and the remaining input
-fn parse_namespace_info(input: &str) -> Result<(&str, (ParsedNamespaceInfo, f32))> {
-    let first_char_is_space = input.starts_with(' ');
+fn parse_namespace_info(input: &str) -> Result<(&str, (ParsedNamespaceInfo<'_>, f32))> {
+    let first_char_is_space = input.starts_with(|x: char| x.is_ascii_whitespace());
     // Extract up until the first space
-    if first_char_is_space {
-        Ok((&input[1..], (ParsedNamespaceInfo::Default, 1.0)))
+    if input.is_empty() || first_char_is_space {
+        Ok((input, (ParsedNamespaceInfo::Default, 1.0)))
     } else {
-        let input_until_first_space = input.find(' ').unwrap();
+        let input_until_first_space =
+            find_unescaped(input, false, |x| x.is_ascii_whitespace()).unwrap_or(input.len());
         let namespace_info_token = &input[..input_until_first_space];
         let (ns_name, ns_value) = parse_namespace_info_token(namespace_info_token)?;
-        Ok((
-            &input[input_until_first_space..],
-            (ParsedNamespaceInfo::Named(ns_name), ns_value),
-        ))
+        // `|:0.5` scales the default namespace.
+        let ns_info = if ns_name.is_empty() {
+            ParsedNamespaceInfo::Default
+        } else {
+            ParsedNamespaceInfo::Named(ns_name)
+        };
+        Ok((&input[input_until_first_space..], (ns_info, ns_value)))
     }
 }
 
 fn extract_namespace_features(
     namespace_segment: &str,
-) -> Result<(ParsedNamespaceInfo, Vec<ParsedFeature>)> {
+    hash_function: HashFunction,
+) -> Result<(ParsedNamespaceInfo<'_>, Vec<ParsedFeature<'_>>)> {
     let (remaining, (namespace_name, _namespace_value)) = parse_namespace_info(namespace_segment)?;
 
-    let tokens = remaining.split_ascii_whitespace();
     let mut offset_counter = 0;
-    let extracted_featrues = tokens
-        .map(|x| {
-            let (feat, _value) = parse_feature(x, &mut offset_counter);
-            feat
-        })
-        .collect();
+    let extracted_featrues = split_tokens(remaining, false)
+        .map(|x| parse_feature(x, &mut offset_counter, hash_function).map(|(feat, _value)| feat))
+        .collect::<Result<_>>()?;
     Ok((namespace_name, extracted_featrues))
 }
 
-// TODO revisit this function. Scanning to the last character is not ideal since it is linear time.
+/// Splits a line into the label section and the namespace segments that follow
+/// it. Bars which are escaped or inside a quoted tag don't start a namespace.
+fn split_line(line: &str) -> (&str, impl Iterator<Item = &str>) {
+    let (initial_segment, rest) = match find_unescaped(line, true, |x| x == b'|') {
+        Some(i) => (&line[..i], Some(&line[i + 1..])),
+        None => (line, None),
+    };
+    let segments = rest
+        .into_iter()
+        .flat_map(|rest| split_unescaped(rest, false, |x| x == b'|'));
+    (initial_segment, segments)
+}
+
+/// Tags may be written as `'tag` or `'"tag"`. Quoted tags can contain spaces
+/// and bars.
+fn parse_tag(token: &str) -> Option<Cow<'_, str>> {
+    let tag = token.strip_prefix('\'').unwrap_or(token);
+    let tag = tag
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .unwrap_or(tag);
+    (!tag.is_empty()).then(|| unescape(tag))
+}
+
 fn parse_initial_segment(
     text: &str,
     label_type: LabelType,
-) -> Result<(Option<&str>, Option<TextLabel>)> {
+) -> Result<(Option<Cow<'_, str>>, Option<TextLabel>)> {
     // Is the last char of text a space?
-    let last_char_is_space = text.ends_with(' ');
+    let last_char_is_space = text.ends_with(|x: char| x.is_ascii_whitespace());
 
-    // TODO: avoid this allocation!
-    let mut tokens: Vec<&str> = text.split_whitespace().collect();
+    let mut tokens: SmallVec<[&str; 4]> = split_tokens(text, true).collect();
 
+    // As in VW, the last token is the tag if it starts with a quote or there
+    // is no space between it and the first bar.
     let tag = match tokens.last() {
         Some(&x) if (x.starts_with('\'') || !last_char_is_space) => {
             tokens.pop();
-            if let Some(x) = x.strip_prefix('\'') {
-                Some(x)
-            } else {
-                Some(x)
-            }
+            parse_tag(x)
         }
         Some(_) => None,
         None => None,
@@ -346,17 +506,16 @@ fn parse_text_line_internal<'a>(
     label_type: LabelType,
     dest: &mut SparseFeatures,
     hash_seed: u32,
+    hash_function: HashFunction,
     num_bits: u8,
 ) -> Result<TextParseResult<'a>> {
-    // Get string view up until first bar
-    let mut segments = text.split('|');
-    let initial_segment = segments.next().unwrap();
+    let (initial_segment, segments) = split_line(text);
     let (tag, label) = parse_initial_segment(initial_segment, label_type)?;
 
     for segment in segments {
-        parse_namespace_inline(segment, dest, hash_seed, num_bits)?;
+        parse_namespace_inline(segment, dest, hash_seed, hash_function, num_bits)?;
     }
-    Ok(TextParseResult { _tag: tag, label })
+    Ok(TextParseResult { tag, label })
 }
 
 #[derive(Default)]
@@ -368,6 +527,7 @@ impl TextModeParserFactory for VwTextParserFactory {
         features_type: FeaturesType,
         label_type: LabelType,
         hash_seed: u32,
+        hash_function: HashFunction,
         num_bits: u8,
         pool: std::sync::Arc<Pool<SparseFeatures>>,
     ) -> Self::Parser {
@@ -375,6 +535,7 @@ impl TextModeParserFactory for VwTextParserFactory {
             feature_type: features_type,
             label_type,
             hash_seed,
+            hash_function,
             num_bits,
             pool,
         }
@@ -385,6 +546,7 @@ pub struct VwTextParser {
     feature_type: FeaturesType,
     label_type: LabelType,
     hash_seed: u32,
+    hash_function: HashFunction,
     num_bits: u8,
     pool: std::sync::Arc<Pool<SparseFeatures>>,
 }
@@ -453,7 +615,10 @@ impl TextModeParser for VwTextParser {
         }
     }
 
-    fn parse_chunk<'a, 'b>(&self, chunk: &'a str) -> Result<(Features<'b>, Option<Label>)> {
+    fn parse_chunk<'a, 'b>(
+        &self,
+        chunk: &'a str,
+    ) -> Result<(Features<'b>, Option<Label>, ExampleMetadata)> {
         if self.is_multiline() {
             let mut results = SmallVec::<[TextParseResult<'a>; 4]>::new();
             let mut all_feautures = SmallVec::<[SparseFeatures; 4]>::new();
@@ -464,18 +629,29 @@ impl TextModeParser for VwTextParser {
                     self.label_type,
                     &mut dest,
                     self.hash_seed,
+                    self.hash_function,
                     self.num_bits,
                 )?;
                 results.push(result);
                 all_feautures.push(dest);
             }
-            finalize_parsed_result_multiline(
+            // The tag of a multiline example is the first one given on any of
+            // its lines.
+            let metadata = ExampleMetadata {
+                tag: results
+                    .iter()
+                    .find_map(|x| x.tag.as_deref())
+                    .map(str::to_owned),
+                ..Default::default()
+            };
+            let (features, label) = finalize_parsed_result_multiline(
                 all_feautures.into_iter(),
                 results.into_iter(),
                 self.label_type,
                 self.feature_type,
                 self.num_bits,
-            )
+            )?;
+            Ok((features, label, metadata))
         } else {
             let mut dest = self.pool.get_object();
             let result = parse_text_line_internal(
@@ -483,6 +659,7 @@ impl TextModeParser for VwTextParser {
                 self.label_type,
                 &mut dest,
                 self.hash_seed,
+                self.hash_function,
                 self.num_bits,
             )?;
             Ok(finalize_parsed_result_singleline(
@@ -493,24 +670,44 @@ impl TextModeParser for VwTextParser {
         }
     }
 
-    fn extract_feature_names<'a>(
-        &self,
-        chunk: &'a str,
-    ) -> Result<std::collections::HashMap<ParsedNamespaceInfo<'a>, Vec<ParsedFeature<'a>>>> {
-        if self.is_multiline() {
-            chunk
-                .lines()
-                .flat_map(|line| {
-                    let mut segments = line.split('|');
-                    let _label_section = segments.next().unwrap();
-                    segments.map(extract_namespace_features)
-                })
-                .collect()
-        } else {
-            let mut segments = chunk.split('|');
-            let _label_section = segments.next().unwrap();
-            segments.map(extract_namespace_features).collect()
+    fn extract_feature_names<'a>(&self, chunk: &'a str) -> Result<Vec<FeatureNames<'a>>> {
+        let extract_line = |line: &'a str| -> Result<(bool, FeatureNames<'a>)> {
+            let (initial_segment, segments) = split_line(line);
+            let (_, label) = parse_initial_segment(initial_segment, self.label_type)?;
+            let is_shared = matches!(label, Some(TextLabel::CB(CBTextLabel { shared: true, .. })));
+            let mut names = FeatureNames::new();
+            for segment in segments {
+                let (namespace, features) =
+                    extract_namespace_features(segment, self.hash_function)?;
+                names.entry(namespace).or_default().extend(features);
+            }
+            Ok((is_shared, names))
+        };
+
+        if !self.is_multiline() {
+            return Ok(vec![extract_line(chunk)?.1]);
         }
+        // The shared features are learned on together with each action.
+        let mut shared = FeatureNames::new();
+        let mut actions = Vec::new();
+        for line in chunk.lines() {
+            match extract_line(line)? {
+                (true, names) => shared = names,
+                (false, names) => actions.push(names),
+            }
+        }
+        Ok(actions
+            .into_iter()
+            .map(|mut action| {
+                for (namespace, features) in &shared {
+                    action
+                        .entry(namespace.clone())
+                        .or_default()
+                        .extend(features.iter().cloned());
+                }
+                action
+            })
+            .collect())
     }
 }
 
@@ -524,9 +721,63 @@ impl VwTextParser {
 mod tests {
     use crate::{
         error::Error,
-        parsers::vw_text_parser::{read_multi_lines, read_single_line},
+        hash::HashFunction,
+        object_pool::Pool,
+        parsers::{
+            vw_text_parser::{read_multi_lines, read_single_line},
+            JsonParserFactory, ParsedFeature, ParsedNamespaceInfo, TextModeParser,
+            TextModeParserFactory, VwTextParser, VwTextParserFactory,
+        },
+        sparse_namespaced_features::{Namespace, SparseFeatures},
+        utils::AsInner,
+        CBAdfFeatures, CBLabel, ExampleMetadata, FeatureMask, FeaturesType, LabelType,
     };
-    use std::io::Cursor;
+    use approx::assert_abs_diff_eq;
+    use serde_json::json;
+    use std::{io::Cursor, sync::Arc};
+
+    fn simple_parser() -> VwTextParser {
+        VwTextParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::Murmur3,
+            18,
+            Arc::new(Pool::new()),
+        )
+    }
+
+    fn cb_parser() -> VwTextParser {
+        VwTextParserFactory.create(
+            FeaturesType::SparseCBAdf,
+            LabelType::CB,
+            0,
+            HashFunction::Murmur3,
+            18,
+            Arc::new(Pool::new()),
+        )
+    }
+
+    fn vw_features(input: &str) -> SparseFeatures {
+        let (features, _, _) = simple_parser().parse_chunk(input).unwrap();
+        let features: &SparseFeatures = features.as_inner().unwrap();
+        features.clone()
+    }
+
+    fn json_features(features: serde_json::Value) -> SparseFeatures {
+        let parser = JsonParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::Murmur3,
+            18,
+            Arc::new(Pool::new()),
+        );
+        let input = json!({ "features": features }).to_string();
+        let (features, _, _) = parser.parse_chunk(&input).unwrap();
+        let features: &SparseFeatures = features.as_inner().unwrap();
+        features.clone()
+    }
 
     #[test]
     fn chunk_multiline() -> Result<(), Error> {
@@ -654,4 +905,173 @@ line 4
         assert_eq!(res, None);
         Ok(())
     }
+
+    #[test]
+    fn parse_tag_and_weight() {
+        let parser = VwTextParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::Murmur3,
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (_, _, metadata) = parser.parse_chunk("1 2 'row-7|a x").unwrap();
+        assert_eq!(
+            metadata,
+            ExampleMetadata {
+                tag: Some("row-7".to_owned()),
+                weight: Some(2.0),
+                ..Default::default()
+            }
+        );
+        let (_, _, metadata) = parser.parse_chunk("1 |a x").unwrap();
+        assert_eq!(metadata, ExampleMetadata::default());
+
+        let parser = VwTextParserFactory.create(
+            FeaturesType::SparseCBAdf,
+            LabelType::CB,
+            0,
+            HashFunction::Murmur3,
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (_, _, metadata) = parser
+            .parse_chunk("shared 'user-3|s a\n0:1:0.5 |a x\n|a y")
+            .unwrap();
+        assert_eq!(metadata.tag.as_deref(), Some("user-3"));
+        assert_eq!(metadata.weight, None);
+    }
+
+    #[test]
+    fn parse_namespace_scaling() {
+        assert_abs_diff_eq!(
+            vw_features("1 |a:0.5 x y:3 |b:2 z"),
+            json_features(json!({"a": {"x": 0.5, "y": 1.5}, "b": {"z": 2}}))
+        );
+        assert_abs_diff_eq!(
+            vw_features("1 |:0.5 x"),
+            json_features(json!({":default": {"x": 0.5}}))
+        );
+        assert!(simple_parser().parse_chunk("1 |a:big x").is_err());
+    }
+
+    #[test]
+    fn parse_escaped_characters() {
+        assert_abs_diff_eq!(
+            vw_features(r"1 |my\ namespace a\:b:2 c\|d g:h\ i"),
+            json_features(json!({"my namespace": {"a:b": 2, "c|d": 1, "g": "h i"}}))
+        );
+    }
+
+    #[test]
+    fn parse_string_and_anonymous_features() {
+        let mut expected = json_features(json!({"a": {"color": "red", "size": 10}}));
+        expected.append(&json_features(json!({"a": [1.5, 2]})));
+        assert_abs_diff_eq!(vw_features("1 |a color:red size:10 :1.5 :2"), expected);
+        // Features named by an integer are placed at that offset from the
+        // namespace, like the anonymous features of a JSON array.
+        assert_abs_diff_eq!(
+            vw_features("1 |a 0:1.5 1:2"),
+            json_features(json!({"a": [1.5, 2]}))
+        );
+        assert!(simple_parser().parse_chunk("1 |a x:").is_err());
+    }
+
+    #[test]
+    fn numeric_feature_names_are_hashed_with_xxh3() {
+        let parser = VwTextParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            0,
+            HashFunction::Xxh3,
+            18,
+            Arc::new(Pool::new()),
+        );
+        let (features, _, _) = parser.parse_chunk("1 |a 1 2:0.5 x").unwrap();
+        let features: &SparseFeatures = features.as_inner().unwrap();
+
+        let namespace = Namespace::from_name("a", 0, HashFunction::Xxh3);
+        let mask = FeatureMask::from_num_bits(18);
+        let hash = |name: &str| {
+            let feature = ParsedFeature::Simple { name: name.into() };
+            feature
+                .hash(namespace.hash(), HashFunction::Xxh3)
+                .mask(mask)
+        };
+        let contents: Vec<_> = features.get_namespace(namespace).unwrap().iter().collect();
+        assert_eq!(
+            contents,
+            vec![(hash("1"), 1.0), (hash("2"), 0.5), (hash("x"), 1.0)]
+        );
+
+        let names = parser.extract_feature_names("1 |a 7").unwrap();
+        assert_eq!(
+            names[0][&ParsedNamespaceInfo::Named("a".into())],
+            vec![ParsedFeature::Simple { name: "7".into() }]
+        );
+    }
+
+    #[test]
+    fn parse_tags() {
+        let tag = |input: &str| simple_parser().parse_chunk(input).unwrap().2.tag;
+        assert_eq!(tag("1 'abc |a x").as_deref(), Some("abc"));
+        assert_eq!(tag("1 abc|a x").as_deref(), Some("abc"));
+        assert_eq!(tag("1 2 |a x"), None);
+        assert_eq!(tag(r#"1 '"a|b c" |a x"#).as_deref(), Some("a|b c"));
+        assert_eq!(tag(r"1 'a\|b|a x").as_deref(), Some("a|b"));
+
+        // The bar inside the quoted tag doesn't start a namespace.
+        assert_abs_diff_eq!(
+            vw_features(r#"1 '"a|b" |a x"#),
+            json_features(json!({"a": {"x": 1}}))
+        );
+    }
+
+    #[test]
+    fn parse_cb_label_on_shared_line() {
+        let (features, label, _) = cb_parser()
+            .parse_chunk("shared 1:0.5:0.25 |s a\n|a x\n|a y")
+            .unwrap();
+        let label: &CBLabel = label.as_ref().unwrap().as_inner().unwrap();
+        assert_eq!(
+            (label.action, label.cost, label.probability),
+            (1, 0.5, 0.25)
+        );
+        let features: &CBAdfFeatures = features.as_inner().unwrap();
+        assert!(features.shared.is_some());
+        assert_eq!(features.actions.len(), 2);
+
+        // The chosen action must exist and only one line can have a label.
+        assert!(cb_parser()
+            .parse_chunk("shared 2:0.5:0.25 |s a\n|a x\n|a y")
+            .is_err());
+        assert!(cb_parser()
+            .parse_chunk("shared 1:0.5:0.25 |s a\n0:1:0.5 |a x\n|a y")
+            .is_err());
+        assert!(cb_parser().parse_chunk("|a x\nshared |s a").is_err());
+        assert!(cb_parser().parse_chunk("shared |s a\n0:1 |a x").is_err());
+    }
+
+    #[test]
+    fn extract_feature_names_unescapes() {
+        let names = simple_parser()
+            .extract_feature_names(r"1 |my\ ns a\:b:2 c:d e\\f 7 :1")
+            .unwrap();
+        assert_eq!(
+            names[0][&ParsedNamespaceInfo::Named("my ns".into())],
+            vec![
+                ParsedFeature::Simple { name: "a:b".into() },
+                ParsedFeature::SimpleWithStringValue {
+                    name: "c".into(),
+                    value: "d".into()
+                },
+                ParsedFeature::Simple {
+                    name: "e\\f".into()
+                },
+                ParsedFeature::Anonymous { offset: 7 },
+                ParsedFeature::Anonymous { offset: 0 },
+            ]
+        );
+    }
 }
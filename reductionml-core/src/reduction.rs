@@ -287,7 +287,7 @@ impl ReductionWrapper {
 }
 
 #[typetag::serde(tag = "type")]
-pub trait ReductionImpl: Send {
+pub trait ReductionImpl: Send + Sync {
     fn predict(
         &self,
         features: &mut Features,
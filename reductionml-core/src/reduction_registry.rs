@@ -9,6 +9,7 @@ use crate::{
         BinaryReductionFactory, CBAdfReductionFactory, CBExploreAdfGreedyReductionFactory,
         CBExploreAdfSoftmaxReductionFactory, CBExploreAdfSquareCBReductionFactory,
         CoinRegressorFactory, DebugReductionFactory, ElementwiseInteractionFactory,
+        FeatureTransformFactory, NamespaceFilterFactory,
     },
 };
 
@@ -23,6 +24,8 @@ pub static REDUCTION_REGISTRY: Lazy<ReentrantMutex<ReductionRegistry>> = Lazy::n
     registry.register(Box::<CBExploreAdfSquareCBReductionFactory>::default());
     registry.register(Box::<CBExploreAdfSoftmaxReductionFactory>::default());
     registry.register(Box::<ElementwiseInteractionFactory>::default());
+    registry.register(Box::<FeatureTransformFactory>::default());
+    registry.register(Box::<NamespaceFilterFactory>::default());
     registry.into()
 });
 
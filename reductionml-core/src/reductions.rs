@@ -6,6 +6,8 @@ mod cb_explore_adf_squarecb;
 mod coin;
 mod debug;
 mod elementwise_interaction;
+mod feature_transform;
+mod namespace_filter;
 
 pub use binary::*;
 pub use cb_adf::*;
@@ -15,3 +17,5 @@ pub use cb_explore_adf_squarecb::*;
 pub use coin::*;
 pub use debug::*;
 pub use elementwise_interaction::*;
+pub use feature_transform::*;
+pub use namespace_filter::*;
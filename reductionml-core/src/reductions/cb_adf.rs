@@ -1,5 +1,7 @@
 use crate::error::Result;
 use crate::global_config::GlobalConfig;
+use crate::hash::FNV_PRIME;
+use crate::interactions::{hash_interaction, HashedNamespaceDef, NamespaceDef};
 use crate::reduction::{
     DepthInfo, ReductionImpl, ReductionTypeDescriptionBuilder, ReductionWrapper,
 };
@@ -9,6 +11,7 @@ use crate::reduction_factory::{
 use crate::utils::AsInner;
 
 use crate::reductions::CoinRegressorConfig;
+use crate::sparse_namespaced_features::{Namespace, SparseFeatures, SparseFeaturesNamespace};
 use crate::{impl_default_factory_functions, types::*, ModelIndex};
 use schemars::schema::RootSchema;
 use schemars::{schema_for, JsonSchema};
@@ -33,6 +36,12 @@ pub struct CBAdfConfig {
     #[serde(default = "default_regressor")]
     #[schemars(schema_with = "crate::config_schema::gen_json_reduction_config_schema")]
     regressor: JsonReductionConfig,
+    /// Pairs of namespaces to cross when scoring each action. The first is
+    /// matched against the shared features and the second against the
+    /// action's, so unlike global interactions these never cross shared
+    /// features with each other or action features with each other.
+    #[serde(default)]
+    shared_action_interactions: Vec<(NamespaceDef, NamespaceDef)>,
 }
 
 impl CBAdfConfig {
@@ -74,6 +83,75 @@ struct CBAdfReduction {
     regressor: ReductionWrapper,
     // TODO: have MTR state per interleaved model.
     mtr_state: MtrState,
+    #[serde(default)]
+    shared_action_interactions: Vec<(HashedNamespaceDef, HashedNamespaceDef)>,
+    #[serde(default)]
+    num_bits: u8,
+}
+
+/// Namespace the crosses of shared and action features are added to. Raw
+/// hashes are only produced by reductions, so this can't clash with a parsed
+/// namespace.
+fn shared_action_namespace() -> Namespace {
+    Namespace::RawHash(0x5ca1ab1e.into())
+}
+
+/// Namespaces of `features` which `def` matches.
+fn matching_namespaces<'a>(
+    def: &'a HashedNamespaceDef,
+    features: &'a SparseFeatures,
+) -> impl Iterator<Item = &'a SparseFeaturesNamespace> + 'a {
+    features
+        .namespaces()
+        .filter_map(|(namespace, _)| features.get_namespace(namespace))
+        .filter(move |feats| match def {
+            HashedNamespaceDef::Namespace(namespace) => feats.namespace() == *namespace,
+            HashedNamespaceDef::Wildcard => true,
+            HashedNamespaceDef::Prefix(prefix) => feats
+                .name()
+                .is_some_and(|name| name.starts_with(prefix.as_str())),
+        })
+}
+
+impl CBAdfReduction {
+    /// Crosses the shared features with those of an action for
+    /// `shared_action_interactions`. Features are hashed the same way as an
+    /// interaction of the two namespaces.
+    fn shared_action_crosses(
+        &self,
+        shared: &SparseFeatures,
+        action: &SparseFeatures,
+    ) -> Option<SparseFeatures> {
+        if self.shared_action_interactions.is_empty() {
+            return None;
+        }
+        let masker = FeatureMask::from_num_bits(self.num_bits);
+        let mut crosses = SparseFeatures::new();
+        let crossed = crosses.get_or_create_namespace(shared_action_namespace());
+        let mut seen = Vec::new();
+        for (shared_def, action_def) in &self.shared_action_interactions {
+            for shared_ns in matching_namespaces(shared_def, shared) {
+                for action_ns in matching_namespaces(action_def, action) {
+                    let pair = (shared_ns.namespace(), action_ns.namespace());
+                    if seen.contains(&pair) {
+                        continue;
+                    }
+                    seen.push(pair);
+                    for (shared_index, shared_value) in shared_ns.iter() {
+                        let hash =
+                            (FNV_PRIME as u64).wrapping_mul(u32::from(shared_index) as u64) as u32;
+                        for (action_index, action_value) in action_ns.iter() {
+                            crossed.add_feature(
+                                FeatureHash::from(hash ^ u32::from(action_index)).mask(masker),
+                                shared_value * action_value,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Some(crosses)
+    }
 }
 
 #[derive(Default)]
@@ -92,6 +170,19 @@ impl ReductionFactory for CBAdfReductionFactory {
         let regressor_config = crate::reduction_factory::parse_config(&config.regressor)?;
         let regressor: ReductionWrapper =
             create_reduction(regressor_config.as_ref(), global_config, num_models_above)?;
+        let shared_action_interactions = config
+            .shared_action_interactions
+            .iter()
+            .map(|(shared, action)| {
+                let hashed = hash_interaction(
+                    &vec![shared.clone(), action.clone()],
+                    global_config.hash_seed(),
+                    global_config.hash_function(),
+                )?;
+                let [shared, action]: [HashedNamespaceDef; 2] = hashed.try_into().unwrap();
+                Ok((shared, action))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         let types = ReductionTypeDescriptionBuilder::new(
             LabelType::CB,
@@ -116,6 +207,8 @@ impl ReductionFactory for CBAdfReductionFactory {
                 cb_type: config.cb_type,
                 regressor,
                 mtr_state: Default::default(),
+                shared_action_interactions,
+                num_bits: global_config.num_bits(),
             }),
             types,
             num_models_above,
@@ -145,15 +238,25 @@ impl ReductionImpl for CBAdfReduction {
 
         let mut action_scores = ActionScoresPrediction::default();
         for (counter, action) in cb_adf_features.actions.iter_mut().enumerate() {
+            let crosses = cb_adf_features
+                .shared
+                .as_ref()
+                .and_then(|shared_feats| self.shared_action_crosses(shared_feats, action));
             if let Some(shared_feats) = &cb_adf_features.shared {
                 action.append(shared_feats);
             }
+            if let Some(crosses) = &crosses {
+                action.append(crosses);
+            }
 
             let pred = self
                 .regressor
                 .predict(&mut action.into(), depth_info, 0.into());
             let scalar_pred: &ScalarPrediction = pred.as_inner().unwrap();
             action_scores.0.push((counter, scalar_pred.raw_prediction));
+            if let Some(crosses) = &crosses {
+                action.remove(crosses);
+            }
             if let Some(shared_feats) = &cb_adf_features.shared {
                 action.remove(shared_feats);
             }
@@ -175,9 +278,16 @@ impl ReductionImpl for CBAdfReduction {
         match self.cb_type {
             CBType::Ips => {
                 for (counter, action) in cb_adf_features.actions.iter_mut().enumerate() {
+                    let crosses = cb_adf_features
+                        .shared
+                        .as_ref()
+                        .and_then(|shared_feats| self.shared_action_crosses(shared_feats, action));
                     if let Some(shared_feats) = &cb_adf_features.shared {
                         action.append(shared_feats);
                     }
+                    if let Some(crosses) = &crosses {
+                        action.append(crosses);
+                    }
 
                     self.regressor.learn(
                         &mut action.into(),
@@ -185,6 +295,9 @@ impl ReductionImpl for CBAdfReduction {
                         depth_info,
                         0.into(),
                     );
+                    if let Some(crosses) = &crosses {
+                        action.remove(crosses);
+                    }
                     if let Some(shared_feats) = &cb_adf_features.shared {
                         action.remove(shared_feats);
                     }
@@ -204,13 +317,20 @@ impl ReductionImpl for CBAdfReduction {
                 match cb_adf_features.shared.as_mut() {
                     Some(shared_feats) => {
                         let action = cb_adf_features.actions.get(cb_label.action).unwrap();
+                        let crosses = self.shared_action_crosses(shared_feats, action);
                         shared_feats.append(action);
+                        if let Some(crosses) = &crosses {
+                            shared_feats.append(crosses);
+                        }
                         self.regressor.learn(
                             &mut Features::SparseSimpleRef(shared_feats),
                             &simple_label.into(),
                             depth_info,
                             0.into(),
                         );
+                        if let Some(crosses) = &crosses {
+                            shared_feats.remove(crosses);
+                        }
                         shared_feats.remove(action);
                     }
                     None => {
@@ -225,3 +345,53 @@ impl ReductionImpl for CBAdfReduction {
         vec![&self.regressor]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashFunction;
+
+    fn create(config: serde_json::Value) -> CBAdfReduction {
+        let config: CBAdfConfig = serde_json::from_value(config).unwrap();
+        let wrapper = CBAdfReductionFactory
+            .create(&config, &GlobalConfig::new(18, 0, true, &[]), 1.into())
+            .unwrap();
+        let json = serde_json::to_value(wrapper).unwrap();
+        serde_json::from_value(json["reduction"].clone()).unwrap()
+    }
+
+    fn named_features(namespaces: &[(&str, &[u32])]) -> SparseFeatures {
+        let mut features = SparseFeatures::new();
+        for (name, indices) in namespaces {
+            let ns = features.get_or_create_named_namespace(
+                Namespace::from_name(name, 0, HashFunction::default()),
+                *name,
+            );
+            for index in *indices {
+                ns.add_feature(FeatureIndex::from(*index), 1.0);
+            }
+        }
+        features
+    }
+
+    #[test]
+    fn shared_action_interactions_only_cross_shared_with_action() {
+        let reduction = create(json!({
+            "sharedActionInteractions": [
+                ["Wildcard", {"Prefix": "a"}],
+                [{"Name": "s"}, {"Name": "a"}]
+            ]
+        }));
+        let shared = named_features(&[("s", &[1, 2])]);
+        let action = named_features(&[("a", &[3]), ("b", &[4])]);
+
+        let crosses = reduction.shared_action_crosses(&shared, &action).unwrap();
+        let crossed = crosses.get_namespace(shared_action_namespace()).unwrap();
+        // Each shared feature is crossed with the action's `a` namespace once,
+        // even though both interactions match it.
+        assert_eq!(crossed.size(), 2);
+
+        let reduction = create(json!({}));
+        assert!(reduction.shared_action_crosses(&shared, &action).is_none());
+    }
+}
@@ -1,18 +1,22 @@
 use std::iter::Sum;
 
 use crate::dense_weights::DenseWeights;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::global_config::GlobalConfig;
-use crate::interactions::compile_interactions;
+use crate::interactions::{
+    compile_interactions, CompiledInteractions, Interaction, InteractionMode,
+};
 use crate::loss_function::{LossFunction, LossFunctionImpl, SquaredLoss};
 use crate::reduction::{
     DepthInfo, ReductionImpl, ReductionTypeDescriptionBuilder, ReductionWrapper,
 };
 use crate::reduction_factory::{PascalCaseString, ReductionConfig, ReductionFactory};
-use crate::sparse_namespaced_features::{Namespace, SparseFeatures};
+use crate::sparse_namespaced_features::Namespace;
 use crate::utils::bits_to_max_feature_index;
 use crate::utils::AsInner;
-use crate::weights::{foreach_feature, foreach_feature_with_state, foreach_feature_with_state_mut};
+use crate::weights::{
+    foreach_feature, foreach_feature_with_state, foreach_feature_with_state_mut, RegressorFeatures,
+};
 use crate::{impl_default_factory_functions, types::*, ModelIndex, StateIndex};
 use derive_builder::Builder;
 use schemars::schema::RootSchema;
@@ -56,6 +60,18 @@ pub struct CoinRegressorConfig {
 
     #[serde(default = "default_link_function")]
     link_function: LinkFunction,
+
+    /// Interactions to use for this regressor instead of those in the global
+    /// config.
+    #[serde(default)]
+    #[builder(default)]
+    interactions: Option<Vec<Interaction>>,
+
+    /// Features this regressor takes as input. `DenseSimple` and `MixedSimple`
+    /// avoid hashing each value of dense blocks such as embeddings.
+    #[serde(default = "default_features_type")]
+    #[builder(default = "default_features_type()")]
+    features_type: FeaturesType,
 }
 
 const fn default_alpha() -> f32 {
@@ -70,6 +86,10 @@ fn default_loss_function() -> LossFunction {
     SquaredLoss::default().into()
 }
 
+fn default_features_type() -> FeaturesType {
+    FeaturesType::SparseSimple
+}
+
 fn default_link_function() -> LinkFunction {
     LinkFunction::Identity
 }
@@ -91,6 +111,7 @@ struct CoinRegressorModelState {
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(from = "SerializedCoinRegressor")]
 struct CoinRegressor {
     weights: DenseWeights,
     config: CoinRegressorConfig,
@@ -100,20 +121,72 @@ struct CoinRegressor {
     max_label: f32,
     // TODO allow this to be chosen
     loss_function: LossFunction,
+    interactions: CompiledInteractions,
+    num_bits: u8,
+    constant_feature_enabled: bool,
+}
+
+/// Models saved before interactions were compiled store the hashed pairs and
+/// triples instead of `interactions`.
+#[derive(Deserialize)]
+struct SerializedCoinRegressor {
+    weights: DenseWeights,
+    config: CoinRegressorConfig,
+    model_states: Vec<CoinRegressorModelState>,
+    average_squared_norm_x: f32,
+    min_label: f32,
+    max_label: f32,
+    loss_function: LossFunction,
+    #[serde(default)]
+    interactions: Option<CompiledInteractions>,
+    #[serde(default)]
     pairs: Vec<(Namespace, Namespace)>,
+    #[serde(default)]
     triples: Vec<(Namespace, Namespace, Namespace)>,
     num_bits: u8,
     constant_feature_enabled: bool,
 }
 
+impl From<SerializedCoinRegressor> for CoinRegressor {
+    fn from(value: SerializedCoinRegressor) -> Self {
+        let interactions = value.interactions.unwrap_or_else(|| {
+            let pairs = value.pairs.into_iter().map(|(a, b)| vec![a, b]);
+            let triples = value.triples.into_iter().map(|(a, b, c)| vec![a, b, c]);
+            CompiledInteractions::from_hashed(
+                pairs.chain(triples).collect(),
+                InteractionMode::Combinations,
+            )
+        });
+        CoinRegressor {
+            weights: value.weights,
+            config: value.config,
+            model_states: value.model_states,
+            average_squared_norm_x: value.average_squared_norm_x,
+            min_label: value.min_label,
+            max_label: value.max_label,
+            loss_function: value.loss_function,
+            interactions,
+            num_bits: value.num_bits,
+            constant_feature_enabled: value.constant_feature_enabled,
+        }
+    }
+}
+
 impl CoinRegressor {
     pub fn new(
         config: CoinRegressorConfig,
         global_config: &GlobalConfig,
         num_models_above: ModelIndex,
     ) -> Result<CoinRegressor> {
-        let (pairs, triples) =
-            compile_interactions(global_config.interactions(), global_config.hash_seed());
+        let interactions = compile_interactions(
+            config
+                .interactions
+                .as_deref()
+                .unwrap_or(global_config.interactions()),
+            global_config.interaction_mode(),
+            global_config.hash_seed(),
+            global_config.hash_function(),
+        )?;
 
         let (min_label, max_label) = match config.loss_function {
             LossFunction::Squared(_) => (0.0, 0.0),
@@ -139,8 +212,7 @@ impl CoinRegressor {
             min_label,
             max_label,
             loss_function: LossFunction::Squared(SquaredLoss::new()),
-            pairs,
-            triples,
+            interactions,
             num_bits: global_config.num_bits(),
             constant_feature_enabled: global_config.constant_feature_enabled(),
         })
@@ -164,6 +236,12 @@ impl ReductionFactory for CoinRegressorFactory {
             .downcast_ref::<CoinRegressorConfig>()
             .unwrap();
 
+        if config.features_type == FeaturesType::SparseCBAdf {
+            return Err(Error::InvalidConfiguration(
+                "Coin does not support SparseCBAdf features".to_owned(),
+            ));
+        }
+
         Ok(ReductionWrapper::new(
             self.typename(),
             Box::new(CoinRegressor::new(
@@ -173,7 +251,7 @@ impl ReductionFactory for CoinRegressorFactory {
             )?),
             ReductionTypeDescriptionBuilder::new(
                 LabelType::Simple,
-                FeaturesType::SparseSimple,
+                config.features_type,
                 PredictionType::Scalar,
             )
             .build(),
@@ -190,15 +268,14 @@ impl ReductionImpl for CoinRegressor {
         _depth_info: &mut DepthInfo,
         _model_offset: ModelIndex,
     ) -> Prediction {
-        let sparse_feats: &SparseFeatures = features.as_inner().unwrap();
+        let feats = RegressorFeatures::from_features(features).unwrap();
 
         let mut prediction = 0.0;
         foreach_feature(
             0.into(),
-            sparse_feats,
+            feats,
             &self.weights,
-            &self.pairs,
-            &self.triples,
+            &self.interactions,
             self.num_bits,
             self.constant_feature_enabled,
             |feat_val, weight_val| prediction += feat_val * weight_val,
@@ -225,14 +302,14 @@ impl ReductionImpl for CoinRegressor {
         _depth_info: &mut DepthInfo,
         _model_offset: ModelIndex,
     ) {
-        let sparse_feats: &SparseFeatures = features.as_inner().unwrap();
+        let feats = RegressorFeatures::from_features(features).unwrap();
         let simple_label: &SimpleLabel = label.as_inner().unwrap();
 
         self.min_label = simple_label.value().min(self.min_label);
         self.max_label = simple_label.value().max(self.max_label);
-        let _prediction = self.coin_betting_predict(sparse_feats, simple_label.weight());
+        let _prediction = self.coin_betting_predict(feats, simple_label.weight());
         self.coin_betting_update_after_predict(
-            sparse_feats,
+            feats,
             _prediction,
             simple_label.value(),
             simple_label.weight(),
@@ -261,13 +338,12 @@ impl ReductionImpl for CoinRegressor {
             score += (1.0 / uncertain) * feat_value.signum();
         };
 
-        let feat = features.as_inner().unwrap();
+        let feat = RegressorFeatures::from_features(features).unwrap();
         foreach_feature_with_state(
             ModelIndex::from(0),
             feat,
             &self.weights,
-            &self.pairs,
-            &self.triples,
+            &self.interactions,
             self.num_bits,
             self.constant_feature_enabled,
             inner,
@@ -299,7 +375,7 @@ impl Sum for PredOutcome {
 }
 
 impl CoinRegressor {
-    fn coin_betting_predict(&mut self, features: &SparseFeatures, weight: f32) -> f32 {
+    fn coin_betting_predict(&mut self, features: RegressorFeatures, weight: f32) -> f32 {
         let mut prediction = 0.0;
         let mut normalized_squared_norm_x = 0.0;
 
@@ -329,8 +405,7 @@ impl CoinRegressor {
             0.into(),
             features,
             &self.weights,
-            &self.pairs,
-            &self.triples,
+            &self.interactions,
             self.num_bits,
             self.constant_feature_enabled,
             inner_predict,
@@ -352,7 +427,7 @@ impl CoinRegressor {
 
     fn coin_betting_update_after_predict(
         &mut self,
-        features: &SparseFeatures,
+        features: RegressorFeatures,
         prediction: f32,
         label: f32,
         weight: f32,
@@ -409,8 +484,7 @@ impl CoinRegressor {
             ModelIndex::from(0),
             features,
             &mut self.weights,
-            &self.pairs,
-            &self.triples,
+            &self.interactions,
             self.num_bits,
             self.constant_feature_enabled,
             inner_update,
@@ -422,7 +496,12 @@ impl CoinRegressor {
 mod tests {
     use approx::assert_relative_eq;
 
-    use crate::{interactions::NamespaceDef, sparse_namespaced_features::Namespace};
+    use crate::{
+        dense_features::DenseFeatures,
+        hash::HashFunction,
+        interactions::NamespaceDef,
+        sparse_namespaced_features::{Namespace, SparseFeatures},
+    };
 
     use super::*;
 
@@ -443,6 +522,59 @@ mod tests {
         assert!(matches!(prediction, Prediction::Scalar { .. }));
     }
 
+    #[test]
+    fn dense_features_match_sparse_features() {
+        let global_config = GlobalConfig::new(8, 0, true, &Vec::new());
+        let namespace = Namespace::from_name("embedding", 0, HashFunction::default());
+        let values = [0.5, -1.0, 2.0];
+
+        // Dense value i uses the same weight as a sparse feature at the
+        // namespace hash plus i.
+        let mut sparse = SparseFeatures::new();
+        let start = u32::from(namespace.hash());
+        sparse
+            .get_or_create_namespace(namespace)
+            .add_features_with_iter(
+                (0..values.len() as u32)
+                    .map(|i| FeatureHash::from(start + i).mask(FeatureMask::from_num_bits(8))),
+                values.iter().copied(),
+            );
+        let mut dense = DenseFeatures::new();
+        dense.add_namespace(namespace, &values);
+        let mut mixed = MixedFeatures {
+            dense: DenseFeatures::from_values(vec![1.0]),
+            sparse: sparse.clone(),
+        };
+        let mut sparse_and_default = sparse.clone();
+        sparse_and_default
+            .get_or_create_namespace(Namespace::Default)
+            .add_feature(0.into(), 1.0);
+
+        let mut depth_info = DepthInfo::new();
+        let label = Label::Simple(SimpleLabel::new(1.0, 1.0));
+        let mut predictions = Vec::new();
+        for features in [
+            Features::SparseSimple(sparse),
+            Features::DenseSimple(dense),
+            Features::MixedSimpleRef(&mut mixed),
+            Features::SparseSimple(sparse_and_default),
+        ] {
+            let mut features = features;
+            let mut coin = CoinRegressor::new(
+                CoinRegressorConfig::default(),
+                &global_config,
+                ModelIndex::from(1),
+            )
+            .unwrap();
+            coin.learn(&mut features, &label, &mut depth_info, 0.into());
+            let prediction = coin.predict(&mut features, &mut depth_info, 0.into());
+            let prediction: &ScalarPrediction = prediction.as_inner().unwrap();
+            predictions.push(prediction.raw_prediction);
+        }
+        assert_relative_eq!(predictions[0], predictions[1]);
+        assert_relative_eq!(predictions[2], predictions[3]);
+    }
+
     #[test]
     fn test_learning() {
         let coin_config = CoinRegressorConfig::default();
@@ -591,4 +723,25 @@ mod tests {
             CoinRegressor::new(coin_config, &global_config, ModelIndex::from(1)).unwrap();
         test_learning_e2e(x, yhat, 100000, coin, vec![0.0, 1.0, 2.0, 3.0]);
     }
+
+    #[test]
+    fn test_learning_quadratic_with_regressor_interactions() {
+        fn x(i: i32) -> f32 {
+            (i % 100) as f32 / 10.0
+        }
+        fn yhat(x: f32) -> f32 {
+            x * x - 2.0 * x + 3.0
+        }
+
+        // Interactions declared on the regressor take precedence over the
+        // (empty) global ones.
+        let coin_config = CoinRegressorConfig {
+            interactions: Some(vec![vec![NamespaceDef::Default, NamespaceDef::Default]]),
+            ..Default::default()
+        };
+        let global_config = GlobalConfig::new(4, 0, true, &Vec::new());
+        let coin: CoinRegressor =
+            CoinRegressor::new(coin_config, &global_config, ModelIndex::from(1)).unwrap();
+        test_learning_e2e(x, yhat, 100000, coin, vec![0.0, 1.0, 2.0, 3.0]);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::error::Result;
 use crate::global_config::GlobalConfig;
-use crate::interactions::NamespaceDef;
+use crate::interactions::{InteractionMode, NamespaceDef};
 use crate::reduction::{
     DepthInfo, ReductionImpl, ReductionTypeDescriptionBuilder, ReductionWrapper,
 };
@@ -9,6 +9,7 @@ use crate::reduction_factory::{
 };
 use crate::sparse_namespaced_features::{Namespace, SparseFeatures, SparseFeaturesNamespace};
 use crate::utils::AsInner;
+use crate::weights::foreach_interacted_feature;
 
 use crate::reductions::CoinRegressorConfig;
 use crate::{impl_default_factory_functions, types::*, ModelIndex};
@@ -19,6 +20,31 @@ use serde::{Deserialize, Serialize};
 use serde_default::DefaultFromSerde;
 use serde_json::json;
 
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq, Debug)]
+enum ProductOutput {
+    /// A namespace containing the product of the values at each index that is
+    /// present in every namespace.
+    #[default]
+    Elementwise,
+    /// A single feature containing the sum of the elementwise products. For
+    /// two dense namespaces this is their dot product.
+    DotProduct,
+    /// A namespace containing the product of every combination of features,
+    /// one from each namespace.
+    OuterProduct,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+struct ProductConfig {
+    /// Namespaces to multiply. At least two must be given.
+    namespaces: Vec<NamespaceDef>,
+
+    #[serde(default)]
+    output: ProductOutput,
+}
+
 #[derive(Deserialize, Serialize, JsonSchema, DefaultFromSerde)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "camelCase")]
@@ -27,12 +53,19 @@ struct ElementwiseInteractionConfig {
     #[schemars(schema_with = "crate::config_schema::gen_json_reduction_config_schema")]
     regressor: JsonReductionConfig,
 
-    /// TODO: document that is it pairs for now. But could conceptually be more.
-    /// FIXME: this default doesn't really make sense.
-    #[serde(default = "default_ns")]
-    one: NamespaceDef,
-    #[serde(default = "default_ns")]
-    two: NamespaceDef,
+    /// Products to generate. Each produces a new namespace. When empty a
+    /// single elementwise product of `one` and `two` is generated.
+    #[serde(default)]
+    products: Vec<ProductConfig>,
+
+    /// First namespace of the single pair used when `products` is empty.
+    /// Defaults to the default namespace.
+    #[serde(default)]
+    one: Option<NamespaceDef>,
+    /// Second namespace of the single pair used when `products` is empty.
+    /// Defaults to the default namespace.
+    #[serde(default)]
+    two: Option<NamespaceDef>,
 
     /// Default is false.
     #[serde(default)]
@@ -46,18 +79,71 @@ fn default_regressor() -> JsonReductionConfig {
     )
 }
 
-fn default_ns() -> NamespaceDef {
-    NamespaceDef::Default
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Product {
+    namespaces: Vec<Namespace>,
+    output: ProductOutput,
+}
+
+impl Product {
+    /// Namespace the product is written to. For elementwise products of two
+    /// namespaces this is the product of their hashes.
+    fn composite_namespace(&self) -> Namespace {
+        let composite_hash = self
+            .namespaces
+            .iter()
+            .map(|ns| u32::from(ns.hash()))
+            .reduce(|a, b| (a as u64 * b as u64) as u32)
+            .unwrap();
+        let offset = match self.output {
+            ProductOutput::Elementwise => 0,
+            ProductOutput::DotProduct => 1,
+            ProductOutput::OuterProduct => 2,
+        };
+        Namespace::RawHash(composite_hash.wrapping_add(offset).into())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(from = "SerializedElementwiseInteraction")]
 struct ElementwiseInteraction {
     regressor: ReductionWrapper,
-    pair: (Namespace, Namespace),
+    products: Vec<Product>,
     keep_original_features: bool,
     num_bits: u8,
 }
 
+/// Models saved before multiple products were supported store a single `pair`
+/// which is multiplied elementwise.
+#[derive(Deserialize)]
+struct SerializedElementwiseInteraction {
+    regressor: ReductionWrapper,
+    #[serde(default)]
+    products: Vec<Product>,
+    #[serde(default)]
+    pair: Option<(Namespace, Namespace)>,
+    keep_original_features: bool,
+    num_bits: u8,
+}
+
+impl From<SerializedElementwiseInteraction> for ElementwiseInteraction {
+    fn from(value: SerializedElementwiseInteraction) -> Self {
+        let mut products = value.products;
+        if let Some((one, two)) = value.pair {
+            products.push(Product {
+                namespaces: vec![one, two],
+                output: ProductOutput::Elementwise,
+            });
+        }
+        ElementwiseInteraction {
+            regressor: value.regressor,
+            products,
+            keep_original_features: value.keep_original_features,
+            num_bits: value.num_bits,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct ElementwiseInteractionFactory;
 
@@ -104,16 +190,50 @@ impl ReductionFactory for ElementwiseInteractionFactory {
             )));
         }
 
-        let (one, two) = (
-            config.one.to_namespace(global_config.hash_seed()),
-            config.two.to_namespace(global_config.hash_seed()),
-        );
+        let hash_seed = global_config.hash_seed();
+        let hash_function = global_config.hash_function();
+        let products = if config.products.is_empty() {
+            let one = config.one.clone().unwrap_or(NamespaceDef::Default);
+            let two = config.two.clone().unwrap_or(NamespaceDef::Default);
+            vec![Product {
+                namespaces: vec![
+                    one.to_namespace(hash_seed, hash_function)?,
+                    two.to_namespace(hash_seed, hash_function)?,
+                ],
+                output: ProductOutput::Elementwise,
+            }]
+        } else {
+            if config.one.is_some() || config.two.is_some() {
+                return Err(crate::error::Error::InvalidArgument(
+                    "`one` and `two` cannot be used together with `products`".to_owned(),
+                ));
+            }
+            config
+                .products
+                .iter()
+                .map(|product| {
+                    if product.namespaces.len() < 2 {
+                        return Err(crate::error::Error::InvalidArgument(
+                            "Each product must contain at least two namespaces".to_owned(),
+                        ));
+                    }
+                    Ok(Product {
+                        namespaces: product
+                            .namespaces
+                            .iter()
+                            .map(|ns| ns.to_namespace(hash_seed, hash_function))
+                            .collect::<Result<_>>()?,
+                        output: product.output,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
 
         Ok(ReductionWrapper::new(
             self.typename(),
             Box::new(ElementwiseInteraction {
                 regressor,
-                pair: (one, two),
+                products,
                 keep_original_features: config.keep_original_features,
                 num_bits: global_config.num_bits(),
             }),
@@ -123,55 +243,169 @@ impl ReductionFactory for ElementwiseInteractionFactory {
     }
 }
 
-fn elementwise_multiply(
+/// Returns each present index of the namespace relative to where its dense
+/// features start.
+fn relative_features(
+    namespace: Namespace,
+    features: &SparseFeaturesNamespace,
+    mask: FeatureMask,
+) -> impl Iterator<Item = (u32, f32)> + '_ {
+    let base = u32::from(namespace.hash());
+    features.iter().map(move |(index, value)| {
+        let relative = FeatureHash::from(u32::from(index).wrapping_sub(base)).mask(mask);
+        (u32::from(relative), value)
+    })
+}
+
+/// Merge-joins features sorted by index into `products`, multiplying the
+/// values at matching indices and dropping the indices `other` does not have.
+fn multiply_sorted(products: &mut Vec<(u32, f32)>, other: impl Iterator<Item = (u32, f32)>) {
+    let mut other = other.peekable();
+    products.retain_mut(|(index, product)| {
+        while other
+            .next_if(|(other_index, _)| other_index < index)
+            .is_some()
+        {}
+        match other.next_if(|(other_index, _)| other_index == index) {
+            Some((_, value)) => {
+                *product *= value;
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+/// Computes the features of a product. Returns `None` if any of its
+/// namespaces are not present.
+fn compute_product(
     num_bits: u8,
     features: &SparseFeatures,
-    one: &Namespace,
-    two: &Namespace,
-) -> (Namespace, SparseFeaturesNamespace) {
-    let (one, two) = (one.clone(), two.clone());
-    let composite_hash: u32 = (u32::from(one.hash()) as u64 * u32::from(two.hash()) as u64) as u32;
+    product: &Product,
+) -> Option<(Vec<FeatureIndex>, Vec<f32>)> {
+    let namespaces = product
+        .namespaces
+        .iter()
+        .map(|ns| features.get_namespace(*ns).map(|feats| (*ns, feats)))
+        .collect::<Option<Vec<_>>>()?;
     let mask = FeatureMask::from_num_bits(num_bits);
-    let base_one = FeatureHash::from(u32::from(one.hash())).mask(mask);
-    let base_two = FeatureHash::from(u32::from(two.hash())).mask(mask);
-
-    let composite_ns = Namespace::RawHash(composite_hash.into());
-    let composite_base = FeatureHash::from(u32::from(composite_ns.hash())).mask(mask);
-
-    let mut dest_iter = SparseFeaturesNamespace::new(composite_ns);
-    let mut ns_one_it = features.get_namespace(one).unwrap().iter();
-    let mut ns_two_it = features.get_namespace(two).unwrap().iter();
-    let mut cur_1 = ns_one_it.next();
-    let mut cur_2 = ns_two_it.next();
-    loop {
-        let (idx1, idx2, val1, val2) = match (cur_1, cur_2) {
-            (None, None) => break,
-            (None, Some(_)) => break,
-            (Some(_), None) => break,
-            (Some(a), Some(b)) => {
-                let (idx1, val1) = a;
-                let (idx2, val2) = b;
-                (idx1, idx2, val1, val2)
-            }
-        };
+    let composite_base = u32::from(product.composite_namespace().hash());
+
+    if product.output == ProductOutput::OuterProduct {
+        let mut indices = Vec::new();
+        let mut values = Vec::new();
+        foreach_interacted_feature(
+            features,
+            &product.namespaces,
+            InteractionMode::Permutations,
+            |value, hash| {
+                indices.push(hash.mask(mask));
+                values.push(value);
+            },
+        );
+        return Some((indices, values));
+    }
 
-        let idx1 = u32::from(idx1) - u32::from(base_one);
-        let idx2 = u32::from(idx2) - u32::from(base_two);
+    // Multiply the values at each relative index, keeping only the indices
+    // present in every namespace.
+    let mut namespaces = namespaces.into_iter();
+    let (first_ns, first_feats) = namespaces.next().unwrap();
+    let mut products: Vec<(u32, f32)> = relative_features(first_ns, first_feats, mask).collect();
+    if !products.is_sorted_by_key(|(index, _)| *index) {
+        products.sort_by_key(|(index, _)| *index);
+    }
+    for (ns, feats) in namespaces {
+        // Dense features are added in order, so only sort when they are not.
+        if relative_features(ns, feats, mask).is_sorted_by_key(|(index, _)| index) {
+            multiply_sorted(&mut products, relative_features(ns, feats, mask));
+        } else {
+            let mut sorted: Vec<(u32, f32)> = relative_features(ns, feats, mask).collect();
+            sorted.sort_by_key(|(index, _)| *index);
+            multiply_sorted(&mut products, sorted.into_iter());
+        }
+    }
+    let elementwise = products.into_iter();
+
+    match product.output {
+        ProductOutput::Elementwise => Some(
+            elementwise
+                .map(|(index, value)| {
+                    (
+                        FeatureHash::from(composite_base.wrapping_add(index)).mask(mask),
+                        value,
+                    )
+                })
+                .unzip(),
+        ),
+        ProductOutput::DotProduct => Some((
+            vec![FeatureHash::from(composite_base).mask(mask)],
+            vec![elementwise.map(|(_, value)| value).sum()],
+        )),
+        ProductOutput::OuterProduct => unreachable!(),
+    }
+}
 
-        let dest_index = u32::from(composite_base) + idx1;
+/// Changes made to the features while the regressor is called, so that they
+/// can be undone afterwards. Products are appended to composite namespaces
+/// which are already present, their previous size is kept to restore them.
+struct AppliedProducts {
+    added: Vec<(Namespace, Option<usize>)>,
+    removed: Vec<(Namespace, SparseFeaturesNamespace)>,
+}
 
-        if idx1 == idx2 {
-            dest_iter.add_feature(dest_index.into(), val1 * val2);
-            cur_1 = ns_one_it.next();
-            cur_2 = ns_two_it.next();
-        } else if idx1 < idx2 {
-            cur_1 = ns_one_it.next();
-        } else {
-            cur_2 = ns_two_it.next();
+impl ElementwiseInteraction {
+    fn apply_products(&self, features: &mut SparseFeatures) -> AppliedProducts {
+        let computed: Vec<_> = self
+            .products
+            .iter()
+            .filter_map(|product| {
+                compute_product(self.num_bits, features, product)
+                    .map(|feats| (product.composite_namespace(), feats))
+            })
+            .collect();
+
+        let mut added = Vec::with_capacity(computed.len());
+        for (namespace, (indices, values)) in computed {
+            let existing_size = features.get_namespace(namespace).map(|ns| ns.size());
+            features
+                .get_or_create_namespace(namespace)
+                .add_features(&indices, &values);
+            added.push((namespace, existing_size));
+        }
+
+        let mut removed = Vec::new();
+        if !self.keep_original_features {
+            for product in &self.products {
+                for namespace in &product.namespaces {
+                    if let Some(feats) = features.remove_namespace(*namespace) {
+                        removed.push((*namespace, feats));
+                    }
+                }
+            }
         }
+        AppliedProducts { added, removed }
     }
+}
 
-    (composite_ns, dest_iter)
+impl AppliedProducts {
+    /// Undoes the changes in reverse order, so that a composite namespace
+    /// which was also removed is put back before it is truncated.
+    fn restore(self, features: &mut SparseFeatures) {
+        for (namespace, feats) in self.removed.into_iter().rev() {
+            features.set_namespace(namespace, feats);
+        }
+        for (namespace, existing_size) in self.added.into_iter().rev() {
+            match existing_size {
+                Some(size) => features
+                    .get_namespace_mut(namespace)
+                    .unwrap()
+                    .truncate(size),
+                None => {
+                    features.remove_namespace(namespace);
+                }
+            }
+        }
+    }
 }
 
 #[typetag::serde]
@@ -183,30 +417,12 @@ impl ReductionImpl for ElementwiseInteraction {
         _model_offset: ModelIndex,
     ) -> Prediction {
         let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-
-        let (composite_ns, dest_iter) =
-            elementwise_multiply(self.num_bits, sparse_feats, &self.pair.0, &self.pair.1);
-
-        sparse_feats.set_namespace(composite_ns, dest_iter);
-        if self.keep_original_features {
-            let pred = self
-                .regressor
-                .predict(&mut sparse_feats.into(), depth_info, 0.into());
-            let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-            sparse_feats.remove_namespace(composite_ns);
-            pred
-        } else {
-            let ns1 = sparse_feats.remove_namespace(self.pair.0).unwrap();
-            let ns2 = sparse_feats.remove_namespace(self.pair.1).unwrap();
-            let pred = self
-                .regressor
-                .predict(&mut sparse_feats.into(), depth_info, 0.into());
-            let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-            sparse_feats.remove_namespace(composite_ns);
-            sparse_feats.set_namespace(self.pair.0, ns1);
-            sparse_feats.set_namespace(self.pair.1, ns2);
-            pred
-        }
+        let applied = self.apply_products(sparse_feats);
+        let pred = self
+            .regressor
+            .predict(&mut sparse_feats.into(), depth_info, 0.into());
+        applied.restore(features.as_inner_mut().unwrap());
+        pred
     }
 
     fn predict_then_learn(
@@ -217,36 +433,15 @@ impl ReductionImpl for ElementwiseInteraction {
         _model_offset: ModelIndex,
     ) -> Prediction {
         let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-
-        let (composite_ns, dest_iter) =
-            elementwise_multiply(self.num_bits, sparse_feats, &self.pair.0, &self.pair.1);
-
-        sparse_feats.set_namespace(composite_ns, dest_iter);
-        if self.keep_original_features {
-            let pred = self.regressor.predict_then_learn(
-                &mut sparse_feats.into(),
-                label,
-                depth_info,
-                0.into(),
-            );
-            let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-            sparse_feats.remove_namespace(composite_ns);
-            pred
-        } else {
-            let ns1 = sparse_feats.remove_namespace(self.pair.0).unwrap();
-            let ns2 = sparse_feats.remove_namespace(self.pair.1).unwrap();
-            let pred = self.regressor.predict_then_learn(
-                &mut sparse_feats.into(),
-                label,
-                depth_info,
-                0.into(),
-            );
-            let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-            sparse_feats.remove_namespace(composite_ns);
-            sparse_feats.set_namespace(self.pair.0, ns1);
-            sparse_feats.set_namespace(self.pair.1, ns2);
-            pred
-        }
+        let applied = self.apply_products(sparse_feats);
+        let pred = self.regressor.predict_then_learn(
+            &mut sparse_feats.into(),
+            label,
+            depth_info,
+            0.into(),
+        );
+        applied.restore(features.as_inner_mut().unwrap());
+        pred
     }
 
     fn learn(
@@ -257,29 +452,166 @@ impl ReductionImpl for ElementwiseInteraction {
         _model_offset: ModelIndex,
     ) {
         let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let applied = self.apply_products(sparse_feats);
+        self.regressor
+            .learn(&mut sparse_feats.into(), label, depth_info, 0.into());
+        applied.restore(features.as_inner_mut().unwrap());
+    }
 
-        let (composite_ns, dest_iter) =
-            elementwise_multiply(self.num_bits, sparse_feats, &self.pair.0, &self.pair.1);
+    fn children(&self) -> Vec<&ReductionWrapper> {
+        vec![&self.regressor]
+    }
+}
 
-        sparse_feats.set_namespace(composite_ns, dest_iter);
-        if self.keep_original_features {
-            self.regressor
-                .learn(&mut sparse_feats.into(), label, depth_info, 0.into());
-            let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-            sparse_feats.remove_namespace(composite_ns);
-        } else {
-            let ns1 = sparse_feats.remove_namespace(self.pair.0).unwrap();
-            let ns2 = sparse_feats.remove_namespace(self.pair.1).unwrap();
-            self.regressor
-                .learn(&mut sparse_feats.into(), label, depth_info, 0.into());
-            let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
-            sparse_feats.remove_namespace(composite_ns);
-            sparse_feats.set_namespace(self.pair.0, ns1);
-            sparse_feats.set_namespace(self.pair.1, ns2);
+#[cfg(test)]
+mod tests {
+    use crate::hash::HashFunction;
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const NUM_BITS: u8 = 18;
+
+    fn add_dense(features: &mut SparseFeatures, name: &str, values: &[f32]) -> Namespace {
+        let namespace = Namespace::from_name(name, 0, HashFunction::default());
+        let mask = FeatureMask::from_num_bits(NUM_BITS);
+        let base = u32::from(namespace.hash());
+        let ns = features.get_or_create_namespace(namespace);
+        for (i, value) in values.iter().enumerate() {
+            ns.add_feature(
+                FeatureHash::from(base.wrapping_add(i as u32)).mask(mask),
+                *value,
+            );
         }
+        namespace
     }
 
-    fn children(&self) -> Vec<&ReductionWrapper> {
-        vec![&self.regressor]
+    #[test]
+    fn three_way_elementwise_and_dot_product() {
+        let mut features = SparseFeatures::new();
+        let a = add_dense(&mut features, "a", &[1.0, 2.0, 3.0]);
+        let b = add_dense(&mut features, "b", &[4.0, 5.0, 6.0]);
+        let c = add_dense(&mut features, "c", &[1.0, 0.5]);
+
+        let elementwise = Product {
+            namespaces: vec![a, b, c],
+            output: ProductOutput::Elementwise,
+        };
+        let (indices, values) = compute_product(NUM_BITS, &features, &elementwise).unwrap();
+        assert_eq!(indices.len(), 2);
+        assert_eq!(values, vec![4.0, 5.0]);
+
+        let dot_product = Product {
+            namespaces: vec![a, b],
+            output: ProductOutput::DotProduct,
+        };
+        let (indices, values) = compute_product(NUM_BITS, &features, &dot_product).unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_relative_eq!(values[0], 32.0);
+
+        let outer_product = Product {
+            namespaces: vec![a, c],
+            output: ProductOutput::OuterProduct,
+        };
+        let (_, values) = compute_product(NUM_BITS, &features, &outer_product).unwrap();
+        assert_eq!(values, vec![1.0, 0.5, 2.0, 1.0, 3.0, 1.5]);
+    }
+
+    #[test]
+    fn missing_namespace_produces_nothing_and_features_are_restored() {
+        let mut features = SparseFeatures::new();
+        let a = add_dense(&mut features, "a", &[1.0, 2.0]);
+        let b = add_dense(&mut features, "b", &[3.0, 4.0]);
+        let missing = Namespace::from_name("missing", 0, HashFunction::default());
+        let original = features.clone();
+
+        let reduction = ElementwiseInteraction {
+            regressor: crate::reduction_factory::create_reduction(
+                &CoinRegressorConfig::default(),
+                &GlobalConfig::new(NUM_BITS, 0, false, &[]),
+                1.into(),
+            )
+            .unwrap(),
+            products: vec![
+                Product {
+                    namespaces: vec![a, missing],
+                    output: ProductOutput::Elementwise,
+                },
+                Product {
+                    namespaces: vec![a, b],
+                    output: ProductOutput::DotProduct,
+                },
+            ],
+            keep_original_features: false,
+            num_bits: NUM_BITS,
+        };
+
+        let applied = reduction.apply_products(&mut features);
+        assert_eq!(applied.added.len(), 1);
+        assert!(features.get_namespace(a).is_none());
+        assert!(features.get_namespace(b).is_none());
+        let composite = features
+            .get_namespace(reduction.products[1].composite_namespace())
+            .unwrap();
+        assert_eq!(composite.values(), &[11.0]);
+
+        applied.restore(&mut features);
+        assert_eq!(features, original);
+    }
+
+    #[test]
+    fn unsorted_features_are_multiplied_by_index() {
+        let mut features = SparseFeatures::new();
+        let a = add_dense(&mut features, "a", &[1.0, 2.0, 3.0]);
+        let b = Namespace::from_name("b", 0, HashFunction::default());
+        let base = u32::from(b.hash());
+        let mask = FeatureMask::from_num_bits(NUM_BITS);
+        let ns = features.get_or_create_namespace(b);
+        for (i, value) in [(2, 6.0), (0, 4.0)] {
+            ns.add_feature(FeatureHash::from(base + i).mask(mask), value);
+        }
+
+        let product = Product {
+            namespaces: vec![b, a],
+            output: ProductOutput::Elementwise,
+        };
+        let (_, values) = compute_product(NUM_BITS, &features, &product).unwrap();
+        assert_eq!(values, vec![4.0, 18.0]);
+    }
+
+    #[test]
+    fn existing_composite_namespace_is_restored() {
+        let mut features = SparseFeatures::new();
+        let a = add_dense(&mut features, "a", &[1.0, 2.0]);
+        let b = add_dense(&mut features, "b", &[3.0, 4.0]);
+        let product = Product {
+            namespaces: vec![a, b],
+            output: ProductOutput::DotProduct,
+        };
+        let composite = product.composite_namespace();
+        features
+            .get_or_create_namespace(composite)
+            .add_feature(FeatureIndex::from(7), 0.5);
+        let original = features.clone();
+
+        let reduction = ElementwiseInteraction {
+            regressor: crate::reduction_factory::create_reduction(
+                &CoinRegressorConfig::default(),
+                &GlobalConfig::new(NUM_BITS, 0, false, &[]),
+                1.into(),
+            )
+            .unwrap(),
+            products: vec![product],
+            keep_original_features: true,
+            num_bits: NUM_BITS,
+        };
+
+        let applied = reduction.apply_products(&mut features);
+        assert_eq!(
+            features.get_namespace(composite).unwrap().values(),
+            &[0.5, 11.0]
+        );
+        applied.restore(&mut features);
+        assert_eq!(features, original);
     }
 }
@@ -0,0 +1,720 @@
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::global_config::GlobalConfig;
+use crate::hash::FNV_PRIME;
+use crate::interactions::NamespaceDef;
+use crate::reduction::{
+    DepthInfo, ReductionImpl, ReductionTypeDescriptionBuilder, ReductionWrapper,
+};
+use crate::reduction_factory::{
+    create_reduction, JsonReductionConfig, PascalCaseString, ReductionConfig, ReductionFactory,
+};
+use crate::sparse_namespaced_features::{Namespace, SparseFeatures, SparseFeaturesNamespace};
+use crate::utils::AsInner;
+
+use crate::reductions::CoinRegressorConfig;
+use crate::{impl_default_factory_functions, types::*, ModelIndex};
+
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_default::DefaultFromSerde;
+use serde_json::json;
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all_fields = "camelCase")]
+enum TransformConfig {
+    /// Replaces each value with `ln(1 + |x|)`, keeping the sign of `x`.
+    Log1p,
+    /// Clamps each value to be within `[min, max]`.
+    Clip { min: f32, max: f32 },
+    /// Standardizes each feature to zero mean and unit variance using a
+    /// running mean and variance which is updated as examples are learned.
+    Standardize,
+    /// Replaces each feature with a categorical feature for the bin its value
+    /// falls into. `numBins` bins of equal width span `[min, max]`, and values
+    /// outside of that range go into the first or last bin.
+    UniformBins { min: f32, max: f32, num_bins: u32 },
+    /// Replaces each feature with a categorical feature for the quantile bin
+    /// its value falls into. Bin boundaries are estimated from the first
+    /// `sampleSize` values learned for each feature and are fixed afterwards.
+    QuantileBins {
+        num_bins: u32,
+        #[serde(default = "default_sample_size")]
+        sample_size: usize,
+    },
+}
+
+const fn default_sample_size() -> usize {
+    1000
+}
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+struct NamespaceTransformConfig {
+    namespace: NamespaceDef,
+    /// Transforms to apply to the namespace's features, in order.
+    steps: Vec<TransformConfig>,
+    /// Maximum number of features each `Standardize` and `QuantileBins` step
+    /// keeps state for. Features first learned after the limit is reached are
+    /// left unchanged by `Standardize` and put in the first quantile bin.
+    #[serde(default = "default_max_tracked_features")]
+    max_tracked_features: usize,
+}
+
+const fn default_max_tracked_features() -> usize {
+    10000
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, DefaultFromSerde)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+struct FeatureTransformConfig {
+    #[serde(default = "default_regressor")]
+    #[schemars(schema_with = "crate::config_schema::gen_json_reduction_config_schema")]
+    regressor: JsonReductionConfig,
+
+    #[serde(default)]
+    transforms: Vec<NamespaceTransformConfig>,
+}
+
+fn default_regressor() -> JsonReductionConfig {
+    JsonReductionConfig::new(
+        "Coin".try_into().unwrap(),
+        json!(CoinRegressorConfig::default()),
+    )
+}
+
+impl ReductionConfig for FeatureTransformConfig {
+    fn typename(&self) -> PascalCaseString {
+        "FeatureTransform".try_into().unwrap()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// Per feature state is stored as a list of pairs since map keys must be
+/// strings in the model format.
+mod index_map {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::FeatureIndex;
+
+    pub fn serialize<S, T>(map: &HashMap<FeatureIndex, T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut pairs: Vec<_> = map.iter().collect();
+        pairs.sort_by_key(|(index, _)| **index);
+        pairs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<HashMap<FeatureIndex, T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        let pairs: Vec<(FeatureIndex, T)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+struct RunningStats {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    fn observe(&mut self, value: f32) {
+        self.count += 1;
+        let delta = value as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value as f64 - self.mean);
+    }
+
+    fn standardize(&self, value: f32) -> f32 {
+        let centered = value as f64 - self.mean;
+        if self.count < 2 {
+            return centered as f32;
+        }
+        let std_dev = (self.m2 / (self.count - 1) as f64).sqrt();
+        if std_dev > 0.0 {
+            (centered / std_dev) as f32
+        } else {
+            centered as f32
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+struct QuantileState {
+    /// Sorted values seen so far. Emptied once the boundaries are fixed.
+    samples: Vec<f32>,
+    /// Boundaries estimated from the current samples.
+    boundaries: Vec<f32>,
+    fixed: bool,
+}
+
+impl QuantileState {
+    fn update_boundaries(&mut self, num_bins: u32) {
+        self.boundaries.clear();
+        self.boundaries
+            .extend((1..num_bins as usize).filter_map(|bin| {
+                self.samples
+                    .get(bin * self.samples.len() / num_bins as usize)
+                    .copied()
+            }));
+    }
+
+    fn bin(&self, value: f32) -> u32 {
+        self.boundaries.partition_point(|b| *b <= value) as u32
+    }
+
+    fn observe(&mut self, value: f32, num_bins: u32, sample_size: usize) {
+        if self.fixed || value.is_nan() {
+            return;
+        }
+        let position = self.samples.partition_point(|x| *x < value);
+        self.samples.insert(position, value);
+        self.update_boundaries(num_bins);
+        if self.samples.len() >= sample_size {
+            self.fixed = true;
+            self.samples = Vec::new();
+        }
+    }
+}
+
+/// Returns the state of a feature, creating it unless `max_features` features
+/// are already tracked.
+fn tracked_state<T: Default>(
+    states: &mut HashMap<FeatureIndex, T>,
+    index: FeatureIndex,
+    max_features: usize,
+) -> Option<&mut T> {
+    if states.len() >= max_features && !states.contains_key(&index) {
+        return None;
+    }
+    Some(states.entry(index).or_default())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum TransformStep {
+    Log1p,
+    Clip {
+        min: f32,
+        max: f32,
+    },
+    Standardize {
+        #[serde(with = "index_map")]
+        stats: HashMap<FeatureIndex, RunningStats>,
+    },
+    UniformBins {
+        min: f32,
+        max: f32,
+        num_bins: u32,
+    },
+    QuantileBins {
+        num_bins: u32,
+        sample_size: usize,
+        #[serde(with = "index_map")]
+        states: HashMap<FeatureIndex, QuantileState>,
+    },
+}
+
+/// Categorical feature representing the bin a feature's value fell into.
+fn bin_feature(index: FeatureIndex, bin: u32, mask: FeatureMask) -> (FeatureIndex, f32) {
+    let hash = FNV_PRIME.wrapping_mul(u32::from(index)) ^ bin;
+    (FeatureHash::from(hash).mask(mask), 1.0)
+}
+
+impl TransformStep {
+    fn from_config(config: &TransformConfig) -> TransformStep {
+        match config {
+            TransformConfig::Log1p => TransformStep::Log1p,
+            TransformConfig::Clip { min, max } => TransformStep::Clip {
+                min: *min,
+                max: *max,
+            },
+            TransformConfig::Standardize => TransformStep::Standardize {
+                stats: HashMap::new(),
+            },
+            TransformConfig::UniformBins { min, max, num_bins } => TransformStep::UniformBins {
+                min: *min,
+                max: *max,
+                num_bins: *num_bins,
+            },
+            TransformConfig::QuantileBins {
+                num_bins,
+                sample_size,
+            } => TransformStep::QuantileBins {
+                num_bins: *num_bins,
+                sample_size: *sample_size,
+                states: HashMap::new(),
+            },
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let error = match self {
+            TransformStep::Clip { min, max } if min > max => {
+                "Clip requires min to be less than or equal to max"
+            }
+            TransformStep::UniformBins { min, max, .. } if min >= max => {
+                "UniformBins requires min to be less than max"
+            }
+            TransformStep::UniformBins { num_bins, .. }
+            | TransformStep::QuantileBins { num_bins, .. }
+                if *num_bins == 0 =>
+            {
+                "The number of bins must be greater than 0"
+            }
+            _ => return Ok(()),
+        };
+        Err(crate::error::Error::InvalidArgument(error.to_owned()))
+    }
+
+    fn apply(&self, index: FeatureIndex, value: f32, mask: FeatureMask) -> (FeatureIndex, f32) {
+        match self {
+            TransformStep::Log1p => (index, value.signum() * value.abs().ln_1p()),
+            TransformStep::Clip { min, max } => (index, value.clamp(*min, *max)),
+            TransformStep::Standardize { stats } => match stats.get(&index) {
+                Some(stats) => (index, stats.standardize(value)),
+                None => (index, value),
+            },
+            TransformStep::UniformBins { min, max, num_bins } => {
+                let position = (value - min) / (max - min) * *num_bins as f32;
+                let bin = (position.max(0.0) as u32).min(num_bins - 1);
+                bin_feature(index, bin, mask)
+            }
+            TransformStep::QuantileBins { states, .. } => {
+                let bin = states.get(&index).map_or(0, |state| state.bin(value));
+                bin_feature(index, bin, mask)
+            }
+        }
+    }
+
+    fn observe(&mut self, index: FeatureIndex, value: f32, max_features: usize) {
+        match self {
+            TransformStep::Standardize { stats } => {
+                if let Some(stats) = tracked_state(stats, index, max_features) {
+                    stats.observe(value);
+                }
+            }
+            TransformStep::QuantileBins {
+                num_bins,
+                sample_size,
+                states,
+            } => {
+                if let Some(state) = tracked_state(states, index, max_features) {
+                    state.observe(value, *num_bins, *sample_size);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct NamespaceTransform {
+    namespace: Namespace,
+    steps: Vec<TransformStep>,
+    max_tracked_features: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FeatureTransform {
+    regressor: ReductionWrapper,
+    transforms: Vec<NamespaceTransform>,
+    num_bits: u8,
+}
+
+/// Original namespaces taken out of the features while the regressor is
+/// called, so that they can be restored afterwards.
+struct AppliedTransforms {
+    originals: Vec<(Namespace, SparseFeaturesNamespace)>,
+}
+
+impl FeatureTransform {
+    fn apply_transforms(&self, features: &mut SparseFeatures) -> AppliedTransforms {
+        let mask = FeatureMask::from_num_bits(self.num_bits);
+        let mut originals = Vec::with_capacity(self.transforms.len());
+        for transform in &self.transforms {
+            let original = match features.get_namespace(transform.namespace) {
+                Some(_) => features.remove_namespace(transform.namespace).unwrap(),
+                None => continue,
+            };
+            let transformed =
+                features.get_or_create_named_namespace(transform.namespace, original.name());
+            for (index, value) in original.iter() {
+                let (index, value) = transform
+                    .steps
+                    .iter()
+                    .fold((index, value), |(index, value), step| {
+                        step.apply(index, value, mask)
+                    });
+                transformed.add_feature(index, value);
+            }
+            originals.push((transform.namespace, original));
+        }
+        AppliedTransforms { originals }
+    }
+
+    /// Updates the state of each transform with the original features of a
+    /// learned example.
+    fn observe(&mut self, applied: &AppliedTransforms) {
+        let mask = FeatureMask::from_num_bits(self.num_bits);
+        for (namespace, original) in &applied.originals {
+            let transform = self
+                .transforms
+                .iter_mut()
+                .find(|transform| transform.namespace == *namespace)
+                .unwrap();
+            for (index, value) in original.iter() {
+                let (mut index, mut value) = (index, value);
+                for step in transform.steps.iter_mut() {
+                    let next = step.apply(index, value, mask);
+                    step.observe(index, value, transform.max_tracked_features);
+                    (index, value) = next;
+                }
+            }
+        }
+    }
+}
+
+impl AppliedTransforms {
+    fn restore(self, features: &mut SparseFeatures) {
+        for (namespace, original) in self.originals {
+            features.set_namespace(namespace, original);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FeatureTransformFactory;
+
+impl ReductionFactory for FeatureTransformFactory {
+    impl_default_factory_functions!("FeatureTransform", FeatureTransformConfig);
+    fn create(
+        &self,
+        config: &dyn ReductionConfig,
+        global_config: &GlobalConfig,
+        num_models_above: ModelIndex,
+    ) -> Result<ReductionWrapper> {
+        let config = config
+            .as_any()
+            .downcast_ref::<FeatureTransformConfig>()
+            .unwrap();
+        let regressor_config = crate::reduction_factory::parse_config(&config.regressor)?;
+        let regressor: ReductionWrapper =
+            create_reduction(regressor_config.as_ref(), global_config, num_models_above)?;
+
+        let types = ReductionTypeDescriptionBuilder::new(
+            regressor.types().input_label_type(),
+            FeaturesType::SparseSimple,
+            regressor.types().output_prediction_type(),
+        )
+        .with_input_prediction_type(regressor.types().output_prediction_type())
+        .with_output_features_type(FeaturesType::SparseSimple)
+        .with_output_label_type(regressor.types().input_label_type())
+        .build();
+
+        if let Some(reason) = types.check_and_get_reason(regressor.types()) {
+            return Err(crate::error::Error::InvalidArgument(format!(
+                "Invalid reduction configuration: {}",
+                reason
+            )));
+        }
+
+        let mut transforms = Vec::with_capacity(config.transforms.len());
+        for transform in &config.transforms {
+            let namespace = transform
+                .namespace
+                .to_namespace(global_config.hash_seed(), global_config.hash_function())?;
+            if transforms
+                .iter()
+                .any(|t: &NamespaceTransform| t.namespace == namespace)
+            {
+                return Err(crate::error::Error::InvalidArgument(format!(
+                    "Namespace {:?} is transformed more than once",
+                    transform.namespace
+                )));
+            }
+            let steps = transform
+                .steps
+                .iter()
+                .map(TransformStep::from_config)
+                .collect::<Vec<_>>();
+            for step in &steps {
+                step.validate()?;
+            }
+            transforms.push(NamespaceTransform {
+                namespace,
+                steps,
+                max_tracked_features: transform.max_tracked_features,
+            });
+        }
+
+        Ok(ReductionWrapper::new(
+            self.typename(),
+            Box::new(FeatureTransform {
+                regressor,
+                transforms,
+                num_bits: global_config.num_bits(),
+            }),
+            types,
+            num_models_above,
+        ))
+    }
+}
+
+#[typetag::serde]
+impl ReductionImpl for FeatureTransform {
+    fn predict(
+        &self,
+        features: &mut Features,
+        depth_info: &mut DepthInfo,
+        _model_offset: ModelIndex,
+    ) -> Prediction {
+        let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let applied = self.apply_transforms(sparse_feats);
+        let pred = self
+            .regressor
+            .predict(&mut sparse_feats.into(), depth_info, 0.into());
+        applied.restore(features.as_inner_mut().unwrap());
+        pred
+    }
+
+    fn predict_then_learn(
+        &mut self,
+        features: &mut Features,
+        label: &Label,
+        depth_info: &mut DepthInfo,
+        _model_offset: ModelIndex,
+    ) -> Prediction {
+        let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let applied = self.apply_transforms(sparse_feats);
+        let pred = self.regressor.predict_then_learn(
+            &mut sparse_feats.into(),
+            label,
+            depth_info,
+            0.into(),
+        );
+        self.observe(&applied);
+        applied.restore(features.as_inner_mut().unwrap());
+        pred
+    }
+
+    fn learn(
+        &mut self,
+        features: &mut Features,
+        label: &Label,
+        depth_info: &mut DepthInfo,
+        _model_offset: ModelIndex,
+    ) {
+        let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let applied = self.apply_transforms(sparse_feats);
+        self.regressor
+            .learn(&mut sparse_feats.into(), label, depth_info, 0.into());
+        self.observe(&applied);
+        applied.restore(features.as_inner_mut().unwrap());
+    }
+
+    fn children(&self) -> Vec<&ReductionWrapper> {
+        vec![&self.regressor]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::HashFunction;
+    use crate::interactions::{compile_interactions, InteractionMode};
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const NUM_BITS: u8 = 18;
+
+    fn apply_all(steps: &[TransformStep], index: FeatureIndex, value: f32) -> (FeatureIndex, f32) {
+        let mask = FeatureMask::from_num_bits(NUM_BITS);
+        steps.iter().fold((index, value), |(index, value), step| {
+            step.apply(index, value, mask)
+        })
+    }
+
+    #[test]
+    fn value_transforms() {
+        let index = FeatureIndex::from(7);
+        let steps = [
+            TransformStep::Clip {
+                min: -10.0,
+                max: 10.0,
+            },
+            TransformStep::Log1p,
+        ];
+        let (_, value) = apply_all(&steps, index, 100.0);
+        assert_relative_eq!(value, 11.0_f32.ln());
+        let (_, value) = apply_all(&steps, index, -1.0);
+        assert_relative_eq!(value, -(2.0_f32.ln()));
+
+        let mut standardize = TransformStep::Standardize {
+            stats: HashMap::new(),
+        };
+        for value in [1.0, 2.0, 3.0] {
+            standardize.observe(index, value, 1);
+        }
+        let (_, value) = apply_all(&[standardize], index, 3.0);
+        assert_relative_eq!(value, 1.0);
+    }
+
+    #[test]
+    fn binning_transforms() {
+        let index = FeatureIndex::from(7);
+        let mask = FeatureMask::from_num_bits(NUM_BITS);
+        let uniform = TransformStep::UniformBins {
+            min: 0.0,
+            max: 10.0,
+            num_bins: 5,
+        };
+        assert_eq!(uniform.apply(index, 3.0, mask), bin_feature(index, 1, mask));
+        assert_eq!(
+            uniform.apply(index, -3.0, mask),
+            bin_feature(index, 0, mask)
+        );
+        assert_eq!(
+            uniform.apply(index, 30.0, mask),
+            bin_feature(index, 4, mask)
+        );
+
+        let mut quantile = TransformStep::QuantileBins {
+            num_bins: 2,
+            sample_size: 4,
+            states: HashMap::new(),
+        };
+        quantile.observe(index, 1.0, 1);
+        // Boundaries are estimated while the sample is collected.
+        assert_eq!(
+            quantile.apply(index, 0.5, mask),
+            bin_feature(index, 0, mask)
+        );
+        assert_eq!(
+            quantile.apply(index, 1.5, mask),
+            bin_feature(index, 1, mask)
+        );
+        for value in [4.0, 3.0, 2.0] {
+            quantile.observe(index, value, 1);
+        }
+        // Boundaries are fixed after sample_size values.
+        quantile.observe(index, 100.0, 1);
+        assert_eq!(
+            quantile.apply(index, 2.5, mask),
+            bin_feature(index, 0, mask)
+        );
+        assert_eq!(
+            quantile.apply(index, 3.0, mask),
+            bin_feature(index, 1, mask)
+        );
+    }
+
+    #[test]
+    fn transforms_are_restored_after_use() {
+        let mut features = SparseFeatures::new();
+        let namespace = Namespace::from_name("a", 0, HashFunction::default());
+        features
+            .get_or_create_namespace(namespace)
+            .add_feature(FeatureIndex::from(1), 100.0);
+        let original = features.clone();
+
+        let reduction = FeatureTransform {
+            regressor: create_reduction(
+                &CoinRegressorConfig::default(),
+                &GlobalConfig::new(NUM_BITS, 0, false, &[]),
+                1.into(),
+            )
+            .unwrap(),
+            transforms: vec![NamespaceTransform {
+                namespace,
+                steps: vec![TransformStep::Clip { min: 0.0, max: 1.0 }],
+                max_tracked_features: default_max_tracked_features(),
+            }],
+            num_bits: NUM_BITS,
+        };
+
+        let applied = reduction.apply_transforms(&mut features);
+        assert_eq!(features.get_namespace(namespace).unwrap().values(), &[1.0]);
+        applied.restore(&mut features);
+        assert_eq!(features, original);
+    }
+
+    #[test]
+    fn transformed_namespaces_match_prefix_interactions() {
+        let hash_function = HashFunction::default();
+        let mut features = SparseFeatures::new();
+        let user_age = Namespace::from_name("user_age", 0, hash_function);
+        let item = Namespace::from_name("item", 0, hash_function);
+        features
+            .get_or_create_named_namespace(user_age, "user_age")
+            .add_feature(FeatureIndex::from(1), 100.0);
+        features
+            .get_or_create_named_namespace(item, "item")
+            .add_feature(FeatureIndex::from(2), 1.0);
+        let interactions = compile_interactions(
+            &[vec![
+                NamespaceDef::Prefix("user_".to_owned()),
+                NamespaceDef::Name("item".to_owned()),
+            ]],
+            InteractionMode::Combinations,
+            0,
+            hash_function,
+        )
+        .unwrap();
+
+        let reduction = FeatureTransform {
+            regressor: create_reduction(
+                &CoinRegressorConfig::default(),
+                &GlobalConfig::new(NUM_BITS, 0, false, &[]),
+                1.into(),
+            )
+            .unwrap(),
+            transforms: vec![NamespaceTransform {
+                namespace: user_age,
+                steps: vec![TransformStep::Log1p],
+                max_tracked_features: default_max_tracked_features(),
+            }],
+            num_bits: NUM_BITS,
+        };
+
+        let applied = reduction.apply_transforms(&mut features);
+        let mut crossed = Vec::new();
+        interactions
+            .foreach_interaction(&features, |namespaces, _| crossed.push(namespaces.to_vec()));
+        assert_eq!(crossed, vec![vec![user_age, item]]);
+        applied.restore(&mut features);
+    }
+
+    #[test]
+    fn tracked_features_are_limited() {
+        let mask = FeatureMask::from_num_bits(NUM_BITS);
+        let (tracked, untracked) = (FeatureIndex::from(7), FeatureIndex::from(8));
+        let mut standardize = TransformStep::Standardize {
+            stats: HashMap::new(),
+        };
+        for value in [1.0, 2.0, 3.0] {
+            standardize.observe(tracked, value, 1);
+            standardize.observe(untracked, value, 1);
+        }
+        assert_relative_eq!(standardize.apply(tracked, 3.0, mask).1, 1.0);
+        assert_relative_eq!(standardize.apply(untracked, 3.0, mask).1, 3.0);
+        match standardize {
+            TransformStep::Standardize { stats } => assert_eq!(stats.len(), 1),
+            _ => unreachable!(),
+        }
+    }
+}
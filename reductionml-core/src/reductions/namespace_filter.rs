@@ -0,0 +1,413 @@
+use crate::error::Result;
+use crate::global_config::GlobalConfig;
+use crate::interactions::NamespaceDef;
+use crate::reduction::{
+    DepthInfo, ReductionImpl, ReductionTypeDescriptionBuilder, ReductionWrapper,
+};
+use crate::reduction_factory::{
+    create_reduction, JsonReductionConfig, PascalCaseString, ReductionConfig, ReductionFactory,
+};
+use crate::sparse_namespaced_features::{Namespace, SparseFeatures, SparseFeaturesNamespace};
+use crate::utils::AsInner;
+
+use crate::reductions::CoinRegressorConfig;
+use crate::{impl_default_factory_functions, types::*, ModelIndex};
+
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_default::DefaultFromSerde;
+use serde_json::json;
+
+#[derive(Deserialize, Serialize, JsonSchema, DefaultFromSerde)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+struct NamespaceFilterConfig {
+    #[serde(default = "default_regressor")]
+    #[schemars(schema_with = "crate::config_schema::gen_json_reduction_config_schema")]
+    regressor: JsonReductionConfig,
+
+    /// Namespaces to remove from every example. Cannot be used with `keep`.
+    #[serde(default)]
+    ignore: Vec<NamespaceDef>,
+
+    /// If not empty, only these namespaces are kept. Cannot be used with
+    /// `ignore`.
+    #[serde(default)]
+    keep: Vec<NamespaceDef>,
+
+    /// Probability of dropping each namespace when learning.
+    #[serde(default)]
+    namespace_dropout: f32,
+
+    /// Probability of dropping each feature when learning. The remaining
+    /// features are scaled by `1 / (1 - featureDropout)`.
+    #[serde(default)]
+    feature_dropout: f32,
+
+    /// Seed for the random number generator used for dropout.
+    #[serde(default)]
+    seed: u64,
+}
+
+fn default_regressor() -> JsonReductionConfig {
+    JsonReductionConfig::new(
+        "Coin".try_into().unwrap(),
+        json!(CoinRegressorConfig::default()),
+    )
+}
+
+impl ReductionConfig for NamespaceFilterConfig {
+    fn typename(&self) -> PascalCaseString {
+        "NamespaceFilter".try_into().unwrap()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// SplitMix64. The state is saved with the model so that dropout is
+/// reproducible across a save and load.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Filter {
+    None,
+    Ignore(Vec<Namespace>),
+    Keep(Vec<Namespace>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct NamespaceFilter {
+    regressor: ReductionWrapper,
+    filter: Filter,
+    namespace_dropout: f32,
+    feature_dropout: f32,
+    rng: Rng,
+}
+
+/// Namespaces changed while the regressor is called, so that they can be
+/// restored afterwards.
+#[derive(Default)]
+struct RemovedNamespaces {
+    originals: Vec<(Namespace, SparseFeaturesNamespace)>,
+}
+
+impl RemovedNamespaces {
+    fn remove(&mut self, features: &mut SparseFeatures, namespace: Namespace) {
+        if let Some(original) = features.remove_namespace(namespace) {
+            self.originals.push((namespace, original));
+        }
+    }
+
+    fn restore(self, features: &mut SparseFeatures) {
+        // Restore in reverse so that the first removal of a namespace, which
+        // is the original, is the one that ends up in the features.
+        for (namespace, original) in self.originals.into_iter().rev() {
+            features.set_namespace(namespace, original);
+        }
+    }
+}
+
+fn active_namespaces(features: &SparseFeatures) -> Vec<Namespace> {
+    features
+        .namespaces()
+        .map(|(namespace, _)| namespace)
+        .filter(|namespace| features.get_namespace(*namespace).is_some())
+        .collect()
+}
+
+impl NamespaceFilter {
+    fn has_dropout(&self) -> bool {
+        self.namespace_dropout > 0.0 || self.feature_dropout > 0.0
+    }
+
+    fn apply_filter(&self, features: &mut SparseFeatures, removed: &mut RemovedNamespaces) {
+        match &self.filter {
+            Filter::None => (),
+            Filter::Ignore(ignore) => {
+                for namespace in ignore {
+                    removed.remove(features, *namespace);
+                }
+            }
+            Filter::Keep(keep) => {
+                for namespace in active_namespaces(features) {
+                    if !keep.contains(&namespace) {
+                        removed.remove(features, namespace);
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply_dropout(&mut self, features: &mut SparseFeatures, removed: &mut RemovedNamespaces) {
+        let scale = 1.0 / (1.0 - self.feature_dropout);
+        for namespace in active_namespaces(features) {
+            if self.namespace_dropout > 0.0 && self.rng.next_f32() < self.namespace_dropout {
+                removed.remove(features, namespace);
+                continue;
+            }
+            if self.feature_dropout > 0.0 {
+                removed.remove(features, namespace);
+                let (original_namespace, original) = removed.originals.last().unwrap();
+                debug_assert_eq!(*original_namespace, namespace);
+                let mut indices = Vec::with_capacity(original.size());
+                let mut values = Vec::with_capacity(original.size());
+                for (index, value) in original.iter() {
+                    if self.rng.next_f32() >= self.feature_dropout {
+                        indices.push(index);
+                        values.push(value * scale);
+                    }
+                }
+                features
+                    .get_or_create_named_namespace(namespace, original.name())
+                    .add_features(&indices, &values);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct NamespaceFilterFactory;
+
+impl ReductionFactory for NamespaceFilterFactory {
+    impl_default_factory_functions!("NamespaceFilter", NamespaceFilterConfig);
+    fn create(
+        &self,
+        config: &dyn ReductionConfig,
+        global_config: &GlobalConfig,
+        num_models_above: ModelIndex,
+    ) -> Result<ReductionWrapper> {
+        let config = config
+            .as_any()
+            .downcast_ref::<NamespaceFilterConfig>()
+            .unwrap();
+        let regressor_config = crate::reduction_factory::parse_config(&config.regressor)?;
+        let regressor: ReductionWrapper =
+            create_reduction(regressor_config.as_ref(), global_config, num_models_above)?;
+
+        let types = ReductionTypeDescriptionBuilder::new(
+            regressor.types().input_label_type(),
+            FeaturesType::SparseSimple,
+            regressor.types().output_prediction_type(),
+        )
+        .with_input_prediction_type(regressor.types().output_prediction_type())
+        .with_output_features_type(FeaturesType::SparseSimple)
+        .with_output_label_type(regressor.types().input_label_type())
+        .build();
+
+        if let Some(reason) = types.check_and_get_reason(regressor.types()) {
+            return Err(crate::error::Error::InvalidArgument(format!(
+                "Invalid reduction configuration: {}",
+                reason
+            )));
+        }
+
+        let to_namespaces = |defs: &[NamespaceDef]| -> Result<Vec<Namespace>> {
+            defs.iter()
+                .map(|ns| ns.to_namespace(global_config.hash_seed(), global_config.hash_function()))
+                .collect()
+        };
+        let filter = match (config.ignore.is_empty(), config.keep.is_empty()) {
+            (true, true) => Filter::None,
+            (false, true) => Filter::Ignore(to_namespaces(&config.ignore)?),
+            (true, false) => Filter::Keep(to_namespaces(&config.keep)?),
+            (false, false) => {
+                return Err(crate::error::Error::InvalidArgument(
+                    "`ignore` and `keep` cannot be used together".to_owned(),
+                ))
+            }
+        };
+
+        for (name, probability) in [
+            ("namespaceDropout", config.namespace_dropout),
+            ("featureDropout", config.feature_dropout),
+        ] {
+            if !(0.0..1.0).contains(&probability) {
+                return Err(crate::error::Error::InvalidArgument(format!(
+                    "{} must be in the range [0, 1), got {}",
+                    name, probability
+                )));
+            }
+        }
+
+        Ok(ReductionWrapper::new(
+            self.typename(),
+            Box::new(NamespaceFilter {
+                regressor,
+                filter,
+                namespace_dropout: config.namespace_dropout,
+                feature_dropout: config.feature_dropout,
+                rng: Rng { state: config.seed },
+            }),
+            types,
+            num_models_above,
+        ))
+    }
+}
+
+#[typetag::serde]
+impl ReductionImpl for NamespaceFilter {
+    fn predict(
+        &self,
+        features: &mut Features,
+        depth_info: &mut DepthInfo,
+        _model_offset: ModelIndex,
+    ) -> Prediction {
+        let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let mut removed = RemovedNamespaces::default();
+        self.apply_filter(sparse_feats, &mut removed);
+        let pred = self
+            .regressor
+            .predict(&mut sparse_feats.into(), depth_info, 0.into());
+        removed.restore(features.as_inner_mut().unwrap());
+        pred
+    }
+
+    fn predict_then_learn(
+        &mut self,
+        features: &mut Features,
+        label: &Label,
+        depth_info: &mut DepthInfo,
+        _model_offset: ModelIndex,
+    ) -> Prediction {
+        let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let mut removed = RemovedNamespaces::default();
+        self.apply_filter(sparse_feats, &mut removed);
+        let pred = if self.has_dropout() {
+            // Dropout only applies to learning, so the prediction is made
+            // separately.
+            let pred = self
+                .regressor
+                .predict(&mut sparse_feats.into(), depth_info, 0.into());
+            self.apply_dropout(sparse_feats, &mut removed);
+            self.regressor
+                .learn(&mut sparse_feats.into(), label, depth_info, 0.into());
+            pred
+        } else {
+            self.regressor
+                .predict_then_learn(&mut sparse_feats.into(), label, depth_info, 0.into())
+        };
+        removed.restore(features.as_inner_mut().unwrap());
+        pred
+    }
+
+    fn learn(
+        &mut self,
+        features: &mut Features,
+        label: &Label,
+        depth_info: &mut DepthInfo,
+        _model_offset: ModelIndex,
+    ) {
+        let sparse_feats: &mut SparseFeatures = features.as_inner_mut().unwrap();
+        let mut removed = RemovedNamespaces::default();
+        self.apply_filter(sparse_feats, &mut removed);
+        if self.has_dropout() {
+            self.apply_dropout(sparse_feats, &mut removed);
+        }
+        self.regressor
+            .learn(&mut sparse_feats.into(), label, depth_info, 0.into());
+        removed.restore(features.as_inner_mut().unwrap());
+    }
+
+    fn children(&self) -> Vec<&ReductionWrapper> {
+        vec![&self.regressor]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashFunction;
+
+    fn create(config: serde_json::Value) -> Result<NamespaceFilter> {
+        let config: NamespaceFilterConfig = serde_json::from_value(config).unwrap();
+        let wrapper = NamespaceFilterFactory.create(
+            &config,
+            &GlobalConfig::new(18, 0, true, &[]),
+            1.into(),
+        )?;
+        let json = serde_json::to_value(wrapper).unwrap();
+        Ok(serde_json::from_value(json["reduction"].clone()).unwrap())
+    }
+
+    fn test_features() -> SparseFeatures {
+        let mut features = SparseFeatures::new();
+        for (name, count) in [("a", 100), ("b", 1)] {
+            let ns = features.get_or_create_named_namespace(
+                Namespace::from_name(name, 0, HashFunction::default()),
+                name,
+            );
+            for i in 0..count {
+                ns.add_feature(FeatureIndex::from(i), 1.0);
+            }
+        }
+        features
+    }
+
+    #[test]
+    fn ignore_and_keep() {
+        let (a, b) = (
+            Namespace::from_name("a", 0, HashFunction::default()),
+            Namespace::from_name("b", 0, HashFunction::default()),
+        );
+        let mut features = test_features();
+        let original = features.clone();
+
+        let filter = create(json!({"ignore": [{"Name": "a"}]})).unwrap();
+        let mut removed = RemovedNamespaces::default();
+        filter.apply_filter(&mut features, &mut removed);
+        assert!(features.get_namespace(a).is_none());
+        assert!(features.get_namespace(b).is_some());
+        removed.restore(&mut features);
+        assert_eq!(features, original);
+
+        let filter = create(json!({"keep": [{"Name": "a"}]})).unwrap();
+        let mut removed = RemovedNamespaces::default();
+        filter.apply_filter(&mut features, &mut removed);
+        assert!(features.get_namespace(a).is_some());
+        assert!(features.get_namespace(b).is_none());
+        removed.restore(&mut features);
+        assert_eq!(features, original);
+
+        assert!(create(json!({"keep": ["Default"], "ignore": ["Default"]})).is_err());
+        assert!(create(json!({"featureDropout": 1.0})).is_err());
+    }
+
+    #[test]
+    fn feature_dropout_is_applied_and_restored() {
+        let a = Namespace::from_name("a", 0, HashFunction::default());
+        let mut features = test_features();
+        let original = features.clone();
+
+        let mut filter = create(json!({"featureDropout": 0.5, "seed": 3})).unwrap();
+        let mut removed = RemovedNamespaces::default();
+        filter.apply_dropout(&mut features, &mut removed);
+        let kept = features.get_namespace(a).unwrap();
+        assert!(kept.size() > 20 && kept.size() < 80);
+        assert!(kept.values().iter().all(|v| *v == 2.0));
+        // Prefix interactions match the rebuilt namespace by its name.
+        assert_eq!(kept.name(), Some("a"));
+        removed.restore(&mut features);
+        assert_eq!(features, original);
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::BTreeSet;
 
 use crate::{
-    hash::{hash_bytes, FNV_PRIME},
+    hash::{HashFunction, FNV_PRIME},
     object_pool::PoolReturnable,
     utils::bits_to_max_feature_index,
     FeatureHash, FeatureIndex, FeatureMask, NamespaceHash,
@@ -62,6 +62,10 @@ pub struct SparseFeaturesNamespace {
     namespace: Namespace,
     feature_indices: Vec<FeatureIndex>,
     feature_values: Vec<f32>,
+    /// The name the namespace was hashed from, if it was created by a parser.
+    /// Namespaces are reused when examples are returned to the pool, so this
+    /// is only set once.
+    name: Option<String>,
     /// active is an optimization for usage solely in the SparseFeatures struct
     /// it is used to avoid iterating over the namespace when it is not active
     /// and also allow for object reuse
@@ -107,6 +111,7 @@ impl SparseFeaturesNamespace {
             namespace,
             feature_indices: Vec::new(),
             feature_values: Vec::new(),
+            name: None,
             active: false,
         }
     }
@@ -116,6 +121,7 @@ impl SparseFeaturesNamespace {
             namespace,
             feature_indices: Vec::with_capacity(capacity),
             feature_values: Vec::with_capacity(capacity),
+            name: None,
             active: false,
         }
     }
@@ -128,6 +134,16 @@ impl SparseFeaturesNamespace {
         self.namespace
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name_if_missing(&mut self, name: Option<&str>) {
+        if self.name.is_none() && self.namespace != Namespace::Default {
+            self.name = name.map(str::to_owned);
+        }
+    }
+
     pub fn reserve(&mut self, size: usize) {
         self.feature_indices
             .reserve_exact(size - self.feature_indices.capacity());
@@ -156,6 +172,12 @@ impl SparseFeaturesNamespace {
         assert_eq!(self.feature_indices.len(), self.feature_values.len());
     }
 
+    /// Keeps the first `len` features and drops the rest.
+    pub fn truncate(&mut self, len: usize) {
+        self.feature_indices.truncate(len);
+        self.feature_values.truncate(len);
+    }
+
     fn clear(&mut self) {
         self.feature_indices.clear();
         self.feature_values.clear();
@@ -178,13 +200,19 @@ pub enum Namespace {
 }
 
 impl Namespace {
-    pub fn from_name(namespace_name: &str, hash_seed: u32) -> Namespace {
+    pub fn from_name(
+        namespace_name: &str,
+        hash_seed: u32,
+        hash_function: HashFunction,
+    ) -> Namespace {
         match namespace_name {
             // TODO: consider different hash if hash_seed is not 0
             " " => Namespace::Default,
             ":default" => Namespace::Default,
             _ => {
-                let namespace_hash = hash_bytes(namespace_name.as_bytes(), hash_seed).into();
+                let namespace_hash = hash_function
+                    .hash_bytes(namespace_name.as_bytes(), hash_seed)
+                    .into();
                 Namespace::Named(namespace_hash)
             }
         }
@@ -383,6 +411,20 @@ impl SparseFeatures {
         item
     }
 
+    /// Like [`SparseFeatures::get_or_create_namespace`], but also records the
+    /// name the namespace was hashed from so that interactions can match it by
+    /// prefix. Reductions which rebuild a namespace pass on the name of the
+    /// original, which may be unknown.
+    pub fn get_or_create_named_namespace<'n>(
+        &mut self,
+        namespace: Namespace,
+        name: impl Into<Option<&'n str>>,
+    ) -> &mut SparseFeaturesNamespace {
+        let item = self.get_or_create_namespace(namespace);
+        item.set_name_if_missing(name.into());
+        item
+    }
+
     pub fn get_or_create_namespace_with_capacity(
         &mut self,
         namespace: Namespace,
@@ -402,6 +444,7 @@ impl SparseFeatures {
         for (ns, feats) in &other.namespaces {
             if feats.active {
                 let container = self.get_or_create_namespace_with_capacity(*ns, feats.size());
+                container.set_name_if_missing(feats.name());
                 container.add_features(&feats.feature_indices, &feats.feature_values);
             }
         }
@@ -1,10 +1,13 @@
 use approx::AbsDiffEq;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    object_pool::PoolReturnable, sparse_namespaced_features::SparseFeatures, utils::AsInner,
+    dense_features::DenseFeatures, object_pool::PoolReturnable,
+    sparse_namespaced_features::SparseFeatures, utils::AsInner,
 };
 use derive_more::TryInto;
+use schemars::JsonSchema;
 use std::ops::Deref;
 macro_rules! impl_conversion_traits {
     ($target_type: ident, $enum_variant: ident, $structname: ident) => {
@@ -163,6 +166,25 @@ pub enum LabelType {
     CB,
 }
 
+/// Information about an example which is not used for learning, such as
+/// where it came from. Formats which don't record it leave it empty.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExampleMetadata {
+    /// Identifies the example, such as the EventId of a DSJSON event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// A tag given to the example, such as the tag of a VW text example.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// The importance weight of the example when the input gives one. For
+    /// simple labels this is the same as the weight of the label.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f32>,
+    /// When the example was logged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct CBAdfFeatures {
     pub shared: Option<SparseFeatures>,
@@ -210,6 +232,33 @@ impl PoolReturnable<SparseFeatures> for CBAdfFeatures {
     }
 }
 
+/// Dense features alongside sparse features of the same example. Interactions
+/// are only produced from the sparse features.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct MixedFeatures {
+    pub dense: DenseFeatures,
+    pub sparse: SparseFeatures,
+}
+
+impl AbsDiffEq for MixedFeatures {
+    type Epsilon = f32;
+
+    fn default_epsilon() -> Self::Epsilon {
+        f32::EPSILON
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        self.dense.abs_diff_eq(&other.dense, epsilon)
+            && self.sparse.abs_diff_eq(&other.sparse, epsilon)
+    }
+}
+
+impl PoolReturnable<SparseFeatures> for MixedFeatures {
+    fn clear_and_return_object(self, pool: &crate::object_pool::Pool<SparseFeatures>) {
+        self.sparse.clear_and_return_object(pool);
+    }
+}
+
 macro_rules! impl_conversion_traits_feats {
     ($enum_variant: ident, $enum_variant_ref: ident, $structname: ident) => {
         impl From<$structname> for Features<'_> {
@@ -256,6 +305,10 @@ pub enum Features<'a> {
     SparseSimpleRef(&'a mut SparseFeatures),
     SparseCBAdf(CBAdfFeatures),
     SparseCBAdfRef(&'a mut CBAdfFeatures),
+    DenseSimple(DenseFeatures),
+    DenseSimpleRef(&'a mut DenseFeatures),
+    MixedSimple(MixedFeatures),
+    MixedSimpleRef(&'a mut MixedFeatures),
 }
 
 #[allow(clippy::should_implement_trait)]
@@ -266,6 +319,10 @@ impl<'a> Features<'a> {
             Features::SparseSimpleRef(f) => Features::SparseSimple((*f).clone()),
             Features::SparseCBAdf(f) => Features::SparseCBAdf(f.clone()),
             Features::SparseCBAdfRef(f) => Features::SparseCBAdf((*f).clone()),
+            Features::DenseSimple(f) => Features::DenseSimple(f.clone()),
+            Features::DenseSimpleRef(f) => Features::DenseSimple((*f).clone()),
+            Features::MixedSimple(f) => Features::MixedSimple(f.clone()),
+            Features::MixedSimpleRef(f) => Features::MixedSimple((*f).clone()),
         }
     }
 }
@@ -295,6 +352,22 @@ impl<'a> AbsDiffEq for Features<'a> {
                 let right: &CBAdfFeatures = other.as_inner().unwrap();
                 left.abs_diff_eq(right, epsilon)
             }
+            (
+                Features::DenseSimple(_) | Features::DenseSimpleRef(_),
+                Features::DenseSimple(_) | Features::DenseSimpleRef(_),
+            ) => {
+                let left: &DenseFeatures = self.as_inner().unwrap();
+                let right: &DenseFeatures = other.as_inner().unwrap();
+                left.abs_diff_eq(right, epsilon)
+            }
+            (
+                Features::MixedSimple(_) | Features::MixedSimpleRef(_),
+                Features::MixedSimple(_) | Features::MixedSimpleRef(_),
+            ) => {
+                let left: &MixedFeatures = self.as_inner().unwrap();
+                let right: &MixedFeatures = other.as_inner().unwrap();
+                left.abs_diff_eq(right, epsilon)
+            }
             (_, _) => false,
         }
     }
@@ -305,6 +378,8 @@ impl<'a> AbsDiffEq for Features<'a> {
 
 impl_conversion_traits_feats!(SparseSimple, SparseSimpleRef, SparseFeatures);
 impl_conversion_traits_feats!(SparseCBAdf, SparseCBAdfRef, CBAdfFeatures);
+impl_conversion_traits_feats!(DenseSimple, DenseSimpleRef, DenseFeatures);
+impl_conversion_traits_feats!(MixedSimple, MixedSimpleRef, MixedFeatures);
 
 // impl From<SparseFeatures> for Features<'_> {
 //     fn from(f: SparseFeatures) -> Self {
@@ -350,10 +425,36 @@ impl TryFrom<Features<'_>> for CBAdfFeatures {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+impl TryFrom<Features<'_>> for DenseFeatures {
+    type Error = &'static str;
+
+    fn try_from(value: Features) -> Result<Self, Self::Error> {
+        match value {
+            Features::DenseSimple(f) => Ok(f),
+            _ => Err("Cannot convert to DenseFeatures"),
+        }
+    }
+}
+
+impl TryFrom<Features<'_>> for MixedFeatures {
+    type Error = &'static str;
+
+    fn try_from(value: Features) -> Result<Self, Self::Error> {
+        match value {
+            Features::MixedSimple(f) => Ok(f),
+            _ => Err("Cannot convert to MixedFeatures"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, JsonSchema)]
 pub enum FeaturesType {
     SparseSimple,
     SparseCBAdf,
+    /// A contiguous block of values, see [`DenseFeatures`].
+    DenseSimple,
+    /// Dense and sparse features together, see [`MixedFeatures`].
+    MixedSimple,
 }
 
 impl PoolReturnable<SparseFeatures> for Features<'_> {
@@ -365,6 +466,10 @@ impl PoolReturnable<SparseFeatures> for Features<'_> {
             Features::SparseSimpleRef(_) => (),
             Features::SparseCBAdf(obj) => obj.clear_and_return_object(pool),
             Features::SparseCBAdfRef(_) => (),
+            Features::DenseSimple(_) => (),
+            Features::DenseSimpleRef(_) => (),
+            Features::MixedSimple(obj) => obj.clear_and_return_object(pool),
+            Features::MixedSimpleRef(_) => (),
         }
     }
 }
@@ -0,0 +1,749 @@
+//! Import of Vowpal Wabbit models.
+//!
+//! Linear regressors and contextual bandit ADF models (`--cb_adf` and
+//! `--cb_explore_adf` with epsilon greedy, SquareCB or softmax exploration)
+//! can be read from VW's binary model files as well as from the text written
+//! by `--readable_model` and `--invert_hash`. The resulting [`Workspace`] uses
+//! [`HashFunction::Murmur3`] so that examples hash to the same weights as they
+//! did in VW.
+//!
+//! Only the weights are imported. The learner state of the Coin regressor
+//! starts empty, so the imported model is intended for prediction. Further
+//! training replaces the weight of each feature as it is updated.
+//!
+//! VW interactions refer to namespaces by their first character. Imported
+//! interactions use [`NamespaceDef::Prefix`] so that they apply to the same
+//! namespaces.
+
+use serde_json::{json, Value};
+
+use crate::{
+    dense_weights::DenseWeights,
+    error::{Error, Result},
+    hash::HashFunction,
+    interactions::{Interaction, NamespaceDef},
+    sparse_namespaced_features::constant_feature_index,
+    weights::Weights,
+    workspace::{Configuration, Workspace},
+    FeatureIndex, ModelIndex,
+};
+
+/// Hash of VW's constant feature before masking.
+const VW_CONSTANT_HASH: u32 = 11650396;
+
+/// First versions whose binary models contain the cb_adf and squarecb state.
+const VW_CB_ADF_SAVE_VERSION: (u32, u32, u32) = (8, 3, 3);
+const VW_SQUARECB_SAVE_VERSION: (u32, u32, u32) = (9, 7, 0);
+
+/// Options which are stored in VW models but do not change predictions.
+const IGNORED_OPTIONS: &[&str] = &[
+    "bit_precision",
+    "csoaa_ldf",
+    "csoaa_rank",
+    "random_seed",
+    "hash",
+];
+
+/// Supported options which do not add state to binary models.
+const STATELESS_OPTIONS: &[&str] = &[
+    "hash_seed",
+    "noconstant",
+    "q",
+    "quadratic",
+    "cubic",
+    "interactions",
+    "cb_type",
+    "epsilon",
+    "gamma_scale",
+    "gamma_exponent",
+    "softmax",
+    "lambda",
+    "loss_function",
+    "link",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VwModel {
+    version: String,
+    id: String,
+    min_label: f32,
+    max_label: f32,
+    num_bits: u8,
+    options: String,
+    /// Weight index, without stride, and value.
+    weights: Vec<(u32, f32)>,
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn read_bytes(&mut self, len: usize, field: &str) -> Result<&'a [u8]> {
+        let bytes = self
+            .position
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| {
+                Error::ParserError(format!(
+                    "Unexpected end of VW model while reading {}",
+                    field
+                ))
+            })?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self, field: &str) -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            self.read_bytes(4, field)?.try_into().unwrap(),
+        ))
+    }
+
+    fn read_f32(&mut self, field: &str) -> Result<f32> {
+        Ok(f32::from_le_bytes(
+            self.read_bytes(4, field)?.try_into().unwrap(),
+        ))
+    }
+
+    /// Strings are stored as a 32 bit length followed by the bytes, usually
+    /// including a null terminator.
+    fn read_string(&mut self, field: &str) -> Result<String> {
+        let len = self.read_u32(field)? as usize;
+        let bytes = self.read_bytes(len, field)?;
+        let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| Error::ParserError(format!("Invalid {} in VW model", field)))
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+}
+
+fn parse_header_value<T: std::str::FromStr>(value: &str, field: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::ParserError(format!("Invalid {} in VW model: {}", field, value)))
+}
+
+fn check_num_bits(num_bits: u32) -> Result<u8> {
+    if !(1..=31).contains(&num_bits) {
+        return Err(Error::ParserError(format!(
+            "Unsupported number of bits in VW model: {}",
+            num_bits
+        )));
+    }
+    Ok(num_bits as u8)
+}
+
+/// Parses one weight of a readable model, `index:weight` or
+/// `name:index:weight` for `--invert_hash`. Models saved with
+/// `--save_resume` have the learner state after the weight, which is ignored.
+fn parse_weight_line(line: &str) -> Result<(u32, f32)> {
+    let token = line.split_whitespace().next().unwrap_or_default();
+    let mut parts = token.rsplitn(3, ':');
+    let weight = parts.next().unwrap_or_default();
+    let index = parts.next().ok_or_else(|| {
+        Error::ParserError(format!("Invalid weight in VW readable model: {}", line))
+    })?;
+    Ok((
+        parse_header_value(index, "weight index")?,
+        parse_header_value(weight, "weight")?,
+    ))
+}
+
+/// Parses the major, minor and patch numbers of a VW version string.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.split('.').map(|part| part.parse().ok());
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+/// Skips the state VW's reductions above gd store in binary models. Only the
+/// reductions created by the options [`VwModel::to_configuration`] supports
+/// are known, anything else is rejected.
+fn skip_reduction_state(
+    reader: &mut BinaryReader,
+    version: (u32, u32, u32),
+    options: &str,
+) -> Result<()> {
+    let mut cb_adf = false;
+    let mut squarecb = false;
+    for (name, value) in parse_options(options) {
+        match name.as_str() {
+            "cb_adf" | "cb_explore_adf" => cb_adf = true,
+            "squarecb" => squarecb = true,
+            name if STATELESS_OPTIONS.contains(&name) || IGNORED_OPTIONS.contains(&name) => (),
+            _ => {
+                return Err(Error::ParserError(format!(
+                    "VW model uses unsupported options: {}",
+                    match value {
+                        Some(value) => format!("--{} {}", name, value),
+                        None => format!("--{}", name),
+                    }
+                )))
+            }
+        }
+    }
+    // Reductions are saved from the top of the stack down.
+    if squarecb && version >= VW_SQUARECB_SAVE_VERSION {
+        reader.read_bytes(8, "squarecb counter")?;
+    }
+    if cb_adf && version >= VW_CB_ADF_SAVE_VERSION {
+        reader.read_bytes(8, "cb_adf event sum")?;
+        reader.read_bytes(8, "cb_adf action sum")?;
+    }
+    Ok(())
+}
+
+/// Reads the weights written by VW's gd reduction for a non resumable model:
+/// a zero byte followed by index and weight pairs. Indices are 64 bit for
+/// models with more than 30 bits.
+fn read_binary_weights(reader: &mut BinaryReader, num_bits: u8) -> Result<Vec<(u32, f32)>> {
+    let resume = reader.read_bytes(1, "save_resume flag")?[0];
+    if resume != 0 {
+        return Err(Error::ParserError(
+            "VW models saved with --save_resume are not supported, save them with --predict_only_model or --readable_model instead".to_owned(),
+        ));
+    }
+    let num_weights = 1_u64 << num_bits;
+    let mut weights: Vec<(u32, f32)> = Vec::new();
+    while !reader.remaining().is_empty() {
+        let index = if num_bits < 31 {
+            reader.read_u32("weight index")? as u64
+        } else {
+            u64::from_le_bytes(reader.read_bytes(8, "weight index")?.try_into().unwrap())
+        };
+        let weight = reader.read_f32("weight")?;
+        if index >= num_weights {
+            return Err(Error::ParserError(format!(
+                "VW model has a weight at index {} but only {} bits",
+                index, num_bits
+            )));
+        }
+        weights.push((index as u32, weight));
+    }
+    Ok(weights)
+}
+
+/// Splits a VW options string into option names and their values.
+fn parse_options(options: &str) -> Vec<(String, Option<String>)> {
+    let tokens: Vec<&str> = options.split_whitespace().collect();
+    let mut parsed = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+        let (name, value) = if let Some(name) = token.strip_prefix("--") {
+            match name.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (name.to_owned(), None),
+            }
+        } else if let Some(name) = token.strip_prefix('-') {
+            // Short options may have their value attached, as in -qab.
+            let mut chars = name.chars();
+            let short = chars.next().map(String::from).unwrap_or_default();
+            let rest: String = chars.collect();
+            (short, (!rest.is_empty()).then_some(rest))
+        } else {
+            parsed.push((token.to_owned(), None));
+            continue;
+        };
+        let value = value.or_else(|| {
+            let next = tokens.get(i)?;
+            if !next.starts_with('-') || next.parse::<f64>().is_ok() {
+                i += 1;
+                Some(next.to_string())
+            } else {
+                None
+            }
+        });
+        parsed.push((name, value));
+    }
+    parsed
+}
+
+fn vw_interaction(namespaces: &str) -> Interaction {
+    namespaces
+        .chars()
+        .map(|c| match c {
+            ':' => NamespaceDef::Wildcard,
+            ' ' => NamespaceDef::Default,
+            c => NamespaceDef::Prefix(c.to_string()),
+        })
+        .collect()
+}
+
+fn find_reduction_mut<'a>(value: &'a mut Value, typename: &str) -> Option<&'a mut Value> {
+    match value {
+        Value::Object(map) if map.get("type").and_then(Value::as_str) == Some(typename) => {
+            Some(value)
+        }
+        Value::Object(map) => map
+            .values_mut()
+            .find_map(|v| find_reduction_mut(v, typename)),
+        Value::Array(vec) => vec.iter_mut().find_map(|v| find_reduction_mut(v, typename)),
+        _ => None,
+    }
+}
+
+impl VwModel {
+    /// Reads a binary VW model, or the output of `--readable_model` or
+    /// `--invert_hash`.
+    pub fn from_bytes(data: &[u8]) -> Result<VwModel> {
+        if data.starts_with(b"Version ") {
+            let text = std::str::from_utf8(data).map_err(|_| {
+                Error::ParserError("VW readable model is not valid UTF-8".to_owned())
+            })?;
+            VwModel::from_readable(text)
+        } else {
+            VwModel::from_binary(data)
+        }
+    }
+
+    /// Reads the output of `--readable_model` or `--invert_hash`.
+    pub fn from_readable(text: &str) -> Result<VwModel> {
+        let mut model = VwModel {
+            version: String::new(),
+            id: String::new(),
+            min_label: 0.0,
+            max_label: 0.0,
+            num_bits: 0,
+            options: String::new(),
+            weights: Vec::new(),
+        };
+        let mut lines = text.lines();
+        // Everything up to the line written by gd, `:0` or `:1` for resumable
+        // models, is header and reduction state.
+        for line in lines.by_ref() {
+            let line = line.trim_end();
+            if line == ":0" || line == ":1" {
+                break;
+            } else if let Some(version) = line.strip_prefix("Version ") {
+                model.version = version.trim().to_owned();
+            } else if let Some(id) = line.strip_prefix("Id") {
+                model.id = id.trim().to_owned();
+            } else if let Some(min_label) = line.strip_prefix("Min label:") {
+                model.min_label = parse_header_value(min_label, "min label")?;
+            } else if let Some(max_label) = line.strip_prefix("Max label:") {
+                model.max_label = parse_header_value(max_label, "max label")?;
+            } else if let Some(bits) = line.strip_prefix("bits:") {
+                model.num_bits = check_num_bits(parse_header_value(bits, "bits")?)?;
+            } else if let Some(options) = line.strip_prefix("options:") {
+                model.options = options.trim().to_owned();
+            }
+        }
+        if model.num_bits == 0 {
+            return Err(Error::ParserError(
+                "VW readable model does not contain the number of bits".to_owned(),
+            ));
+        }
+        // Learner state of resumable models has no colon, weights always do.
+        for line in lines {
+            let is_weight = line
+                .split_whitespace()
+                .next()
+                .is_some_and(|token| token.contains(':'));
+            if is_weight {
+                model.weights.push(parse_weight_line(line)?);
+            }
+        }
+        // Feature names given by --invert_hash may map to the same index.
+        model.weights.sort_by_key(|(index, _)| *index);
+        model.weights.dedup_by_key(|(index, _)| *index);
+        Ok(model)
+    }
+
+    /// Reads a binary VW model written by VW 8 or later.
+    pub fn from_binary(data: &[u8]) -> Result<VwModel> {
+        let mut reader = BinaryReader { data, position: 0 };
+        let version = reader.read_string("version")?;
+        let parsed_version = parse_version(&version)
+            .ok_or_else(|| Error::ParserError("File is not a VW model".to_owned()))?;
+        if parsed_version < (8, 0, 0) {
+            return Err(Error::ParserError(format!(
+                "VW models before version 8 are not supported, found version {}",
+                version
+            )));
+        }
+        let id = reader.read_string("id")?;
+        if reader.read_bytes(1, "model marker")? != b"m" {
+            return Err(Error::ParserError("File is not a VW model".to_owned()));
+        }
+        let min_label = reader.read_f32("min label")?;
+        let max_label = reader.read_f32("max label")?;
+        let num_bits = check_num_bits(reader.read_u32("bits")?)?;
+        let _lda = reader.read_u32("lda")?;
+        // ngram and skip definitions are 3 bytes each
+        let ngram_len = reader.read_u32("ngram")? as usize;
+        reader.read_bytes(ngram_len * 3, "ngram")?;
+        let skip_len = reader.read_u32("skip")? as usize;
+        reader.read_bytes(skip_len * 3, "skip")?;
+        let options = reader.read_string("options")?;
+        // The checksum is written with its length like the strings.
+        if reader.read_u32("checksum")? != 4 {
+            return Err(Error::ParserError(
+                "Invalid checksum in VW model".to_owned(),
+            ));
+        }
+        let _checksum = reader.read_u32("checksum")?;
+
+        skip_reduction_state(&mut reader, parsed_version, &options)?;
+        let weights = read_binary_weights(&mut reader, num_bits)?;
+
+        Ok(VwModel {
+            version,
+            id,
+            min_label,
+            max_label,
+            num_bits,
+            options: options.trim().to_owned(),
+            weights,
+        })
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn num_bits(&self) -> u8 {
+        self.num_bits
+    }
+
+    /// Command line options VW stored in the model.
+    pub fn options(&self) -> &str {
+        &self.options
+    }
+
+    /// Non-zero weights as pairs of weight index and value.
+    pub fn weights(&self) -> &[(u32, f32)] {
+        &self.weights
+    }
+
+    /// Translates the options stored in the model into a configuration.
+    pub fn to_configuration(&self) -> Result<Configuration> {
+        let mut hash_seed: u32 = 0;
+        let mut constant_feature_enabled = true;
+        let mut interactions: Vec<Interaction> = Vec::new();
+        let mut cb_adf = false;
+        let mut cb_explore_adf = false;
+        let mut cb_type = "mtr".to_owned();
+        let mut exploration = "CbExploreAdfGreedy";
+        let mut exploration_config = serde_json::Map::new();
+        let mut logistic_loss = false;
+        let mut link_function = "Identity";
+        let mut unsupported = Vec::new();
+
+        let value_of = |name: &str, value: Option<String>| {
+            value.ok_or_else(|| {
+                Error::InvalidArgument(format!("VW option --{} is missing its value", name))
+            })
+        };
+        let number = |name: &str, value: Option<String>| -> Result<f32> {
+            parse_header_value(&value_of(name, value)?, name)
+                .map_err(|e| Error::InvalidArgument(e.to_string()))
+        };
+
+        for (name, value) in parse_options(&self.options) {
+            match name.as_str() {
+                "hash_seed" => {
+                    hash_seed = parse_header_value(&value_of(&name, value)?, &name)?;
+                }
+                "noconstant" => constant_feature_enabled = false,
+                "q" | "quadratic" | "cubic" | "interactions" => {
+                    interactions.push(vw_interaction(&value_of(&name, value)?));
+                }
+                "cb_adf" => cb_adf = true,
+                "cb_explore_adf" => cb_explore_adf = true,
+                "cb_type" => match value.as_deref() {
+                    Some("mtr") | Some("ips") => cb_type = value.unwrap(),
+                    _ => unsupported.push(format!("--cb_type {}", value.unwrap_or_default())),
+                },
+                "epsilon" => {
+                    exploration_config.insert("epsilon".to_owned(), json!(number(&name, value)?));
+                }
+                "squarecb" => exploration = "CbExploreAdfSquareCb",
+                "gamma_scale" => {
+                    exploration_config
+                        .insert("gammaScale".to_owned(), json!(number(&name, value)?));
+                }
+                "gamma_exponent" => {
+                    exploration_config
+                        .insert("gammaExponent".to_owned(), json!(number(&name, value)?));
+                }
+                "softmax" => exploration = "CbExploreAdfSoftmax",
+                "lambda" => {
+                    exploration_config.insert("lambda".to_owned(), json!(number(&name, value)?));
+                }
+                "loss_function" => match value.as_deref() {
+                    Some("squared") => logistic_loss = false,
+                    Some("logistic") => logistic_loss = true,
+                    _ => unsupported.push(format!("--loss_function {}", value.unwrap_or_default())),
+                },
+                "link" => match value.as_deref() {
+                    Some("identity") => link_function = "Identity",
+                    Some("logistic") => link_function = "Logistic",
+                    _ => unsupported.push(format!("--link {}", value.unwrap_or_default())),
+                },
+                name if IGNORED_OPTIONS.contains(&name) => (),
+                _ => unsupported.push(match value {
+                    Some(value) => format!("--{} {}", name, value),
+                    None => format!("--{}", name),
+                }),
+            }
+        }
+
+        if !unsupported.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "VW model uses unsupported options: {}",
+                unsupported.join(" ")
+            )));
+        }
+
+        // Epsilon is the uniform exploration mixed into the other algorithms.
+        if exploration != "CbExploreAdfGreedy" {
+            if let Some(epsilon) = exploration_config.remove("epsilon") {
+                exploration_config.insert("uniformEpsilon".to_owned(), epsilon);
+            }
+        }
+
+        let regressor = json!({
+            "typename": "Coin",
+            "config": {
+                "lossFunction": if logistic_loss {
+                    json!({"Logistic": {}})
+                } else {
+                    json!({"Squared": {}})
+                },
+                "linkFunction": link_function,
+            }
+        });
+        let cb_adf_config = json!({
+            "typename": "CbAdf",
+            "config": {
+                "cbType": cb_type,
+                "regressor": regressor,
+            }
+        });
+        let entry_reduction = if cb_explore_adf {
+            exploration_config.insert("cbAdf".to_owned(), cb_adf_config);
+            json!({
+                "typename": exploration,
+                "config": exploration_config,
+            })
+        } else if cb_adf {
+            cb_adf_config
+        } else {
+            regressor
+        };
+
+        json!({
+            "globalConfig": {
+                "numBits": self.num_bits,
+                "hashSeed": hash_seed,
+                "hashFunction": HashFunction::Murmur3,
+                "constantFeatureEnabled": constant_feature_enabled,
+                "interactions": interactions,
+            },
+            "entryReduction": entry_reduction,
+        })
+        .try_into()
+    }
+
+    /// Creates a workspace equivalent to the VW model.
+    pub fn to_workspace(&self) -> Result<Workspace> {
+        let config = self.to_configuration()?;
+        let workspace = Workspace::new(config)?;
+        let constant_enabled = workspace.global_config().constant_feature_enabled();
+
+        let num_weights = 1_u64 << self.num_bits;
+        if let Some((index, _)) = self
+            .weights
+            .iter()
+            .find(|(index, _)| *index as u64 >= num_weights)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "VW model has a weight at index {} but only {} bits",
+                index, self.num_bits
+            )));
+        }
+
+        let mut value = serde_json::to_value(&workspace)?;
+        let coin = find_reduction_mut(&mut value, "CoinRegressor").ok_or_else(|| {
+            Error::InvalidArgument("Imported VW model has no Coin regressor".to_owned())
+        })?;
+        let mut weights: DenseWeights = serde_json::from_value(coin["weights"].take())?;
+        let vw_constant_index = VW_CONSTANT_HASH & ((1_u32 << self.num_bits) - 1);
+        for (index, weight) in &self.weights {
+            let index = if constant_enabled && *index == vw_constant_index {
+                constant_feature_index(self.num_bits)
+            } else {
+                FeatureIndex::from(*index)
+            };
+            *weights.weight_at_mut(index, ModelIndex::from(0)) = *weight;
+        }
+        coin["weights"] = serde_json::to_value(weights)?;
+        // VW clamps predictions to the labels seen during training, the
+        // logistic loss keeps its own range.
+        if coin["config"]["lossFunction"].get("Squared").is_some() {
+            coin["min_label"] = json!(self.min_label);
+            coin["max_label"] = json!(self.max_label);
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| Error::InvalidArgument(format!("Failed to create workspace: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        object_pool::Pool,
+        parsers::{TextModeParser, TextModeParserFactory, VwTextParserFactory},
+        utils::AsInner,
+        FeaturesType, LabelType, ScalarPrediction,
+    };
+
+    const HEADER: &str = "Version 9.8.0
+Id 
+Min label:-1
+Max label:2
+bits:18
+lda:0
+0 ngram:
+0 skip:
+";
+
+    fn predict(workspace: &Workspace, line: &str) -> f32 {
+        let global_config = workspace.global_config();
+        let parser = VwTextParserFactory.create(
+            FeaturesType::SparseSimple,
+            LabelType::Simple,
+            global_config.hash_seed(),
+            global_config.hash_function(),
+            global_config.num_bits(),
+            Arc::new(Pool::new()),
+        );
+        let (mut features, _, _) = parser.parse_chunk(line).unwrap();
+        let prediction = workspace.predict(&mut features);
+        let prediction: &ScalarPrediction = prediction.as_inner().unwrap();
+        prediction.prediction
+    }
+
+    #[test]
+    fn readable_and_invert_hash_models() {
+        // 116060 is VW's constant and 203039 is myNamespace^feature.
+        let readable = format!(
+            "{HEADER}options: --hash_seed 0\nChecksum: 1234\n:0\n116060:0.25\n203039:0.5\n"
+        );
+        let invert_hash = format!(
+            "{HEADER}options:\nChecksum: 1234\n:0\nConstant:116060:0.25\nmyNamespace^feature:203039:0.5\n"
+        );
+        for text in [readable, invert_hash] {
+            let model = VwModel::from_bytes(text.as_bytes()).unwrap();
+            assert_eq!(model.version(), "9.8.0");
+            assert_eq!(model.num_bits(), 18);
+            assert_eq!(model.weights(), &[(116060, 0.25), (203039, 0.5)]);
+
+            let workspace = model.to_workspace().unwrap();
+            assert_relative_eq!(predict(&workspace, "|myNamespace feature"), 0.75);
+            assert_relative_eq!(predict(&workspace, "|other feature"), 0.25);
+        }
+
+        // Predictions are clamped to the label range seen by VW.
+        let text = format!("{HEADER}options:\nChecksum: 1234\n:0\n203039:5\n");
+        let workspace = VwModel::from_bytes(text.as_bytes())
+            .unwrap()
+            .to_workspace()
+            .unwrap();
+        assert_relative_eq!(predict(&workspace, "|myNamespace feature"), 2.0);
+    }
+
+    #[test]
+    fn binary_model() {
+        let data = include_bytes!("../tests/data/vw_model/cb_explore_adf_squarecb.model");
+        let model = VwModel::from_bytes(data).unwrap();
+        assert_eq!(model.version(), "9.8.0");
+        assert_eq!(model.num_bits(), 18);
+        assert_eq!(
+            model.options(),
+            "--cb_explore_adf --cb_type mtr --csoaa_ldf multiline --csoaa_rank --squarecb --gamma_scale 10 --quadratic ua --random_seed 0"
+        );
+        assert_eq!(model.weights(), &[(116060, 0.25), (203039, -0.5)]);
+
+        let workspace = model.to_workspace().unwrap();
+        assert_eq!(
+            workspace.get_entry_reduction().typename(),
+            "CbExploreAdfSquareCb"
+        );
+
+        // Trailing bytes which are not a whole weight are rejected.
+        let mut truncated = data.to_vec();
+        truncated.pop();
+        assert!(VwModel::from_bytes(&truncated).is_err());
+
+        // Models without the squarecb state, which is 8 bytes before the
+        // cb_adf state, have the data of gd in the wrong place.
+        let without_counter = [&data[..data.len() - 41], &data[data.len() - 33..]].concat();
+        assert!(VwModel::from_bytes(&without_counter).is_err());
+
+        let mut resumable = data[..data.len() - 16].to_vec();
+        *resumable.last_mut().unwrap() = 1;
+        let err = VwModel::from_bytes(&resumable).err().unwrap();
+        assert!(err.to_string().contains("--save_resume"));
+
+        let mut unsupported = data.to_vec();
+        let position = unsupported
+            .windows(10)
+            .position(|window| window == b"--squarecb")
+            .unwrap();
+        unsupported[position..position + 10].copy_from_slice(b"--oaa 3   ");
+        let err = VwModel::from_bytes(&unsupported).err().unwrap();
+        assert!(err.to_string().contains("--oaa 3"));
+    }
+
+    #[test]
+    fn cb_explore_adf_options() {
+        let text = format!(
+            "{HEADER}options: --cb_explore_adf --cb_type mtr --csoaa_ldf multiline --csoaa_rank --squarecb --epsilon 0.1 -q ab --quadratic=a: --noconstant\nChecksum: 1234\nevent_sum 7\naction_sum 21\n:0\n"
+        );
+        let workspace = VwModel::from_bytes(text.as_bytes())
+            .unwrap()
+            .to_workspace()
+            .unwrap();
+        assert_eq!(
+            workspace.get_entry_reduction().typename(),
+            "CbExploreAdfSquareCb"
+        );
+        let global_config = workspace.global_config();
+        assert_eq!(global_config.hash_function(), HashFunction::Murmur3);
+        assert!(!global_config.constant_feature_enabled());
+        let expected: Vec<Interaction> = vec![
+            vec![
+                NamespaceDef::Prefix("a".to_owned()),
+                NamespaceDef::Prefix("b".to_owned()),
+            ],
+            vec![NamespaceDef::Prefix("a".to_owned()), NamespaceDef::Wildcard],
+        ];
+        assert_eq!(json!(global_config.interactions()), json!(expected));
+
+        let text = format!("{HEADER}options: --oaa 3\nChecksum: 1234\n:0\n");
+        let err = VwModel::from_bytes(text.as_bytes())
+            .unwrap()
+            .to_configuration()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("--oaa 3"));
+    }
+}
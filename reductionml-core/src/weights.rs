@@ -1,7 +1,14 @@
+use smallvec::{smallvec, SmallVec};
+
 use crate::{
+    dense_features::DenseFeatures,
     hash::FNV_PRIME,
-    sparse_namespaced_features::{constant_feature_index, Namespace, SparseFeatures},
-    FeatureHash, FeatureIndex, FeatureMask, ModelIndex,
+    interactions::{repeat_positions, CompiledInteractions, InteractionMode},
+    sparse_namespaced_features::{
+        constant_feature_index, Namespace, SparseFeatures, SparseFeaturesNamespace,
+    },
+    utils::AsInner,
+    FeatureHash, FeatureIndex, FeatureMask, Features, MixedFeatures, ModelIndex,
 };
 
 pub trait Weights {
@@ -14,15 +21,154 @@ pub trait Weights {
     fn state_at_mut(&mut self, feature_index: FeatureIndex, model_index: ModelIndex) -> &mut [f32];
 }
 
+/// The features of a single example given to a regressor, which can have a
+/// sparse part, a dense part or both.
+#[derive(Clone, Copy)]
+pub struct RegressorFeatures<'a> {
+    sparse: Option<&'a SparseFeatures>,
+    dense: Option<&'a DenseFeatures>,
+}
+
+impl<'a> RegressorFeatures<'a> {
+    /// Returns `None` for features which are not for a single example.
+    pub fn from_features(features: &'a Features) -> Option<RegressorFeatures<'a>> {
+        if let Some(sparse) = AsInner::<SparseFeatures>::as_inner(features) {
+            Some(sparse.into())
+        } else if let Some(dense) = AsInner::<DenseFeatures>::as_inner(features) {
+            Some(dense.into())
+        } else {
+            AsInner::<MixedFeatures>::as_inner(features).map(Into::into)
+        }
+    }
+
+    pub fn sparse(&self) -> Option<&'a SparseFeatures> {
+        self.sparse
+    }
+
+    pub fn dense(&self) -> Option<&'a DenseFeatures> {
+        self.dense
+    }
+}
+
+impl<'a> From<&'a SparseFeatures> for RegressorFeatures<'a> {
+    fn from(sparse: &'a SparseFeatures) -> Self {
+        RegressorFeatures {
+            sparse: Some(sparse),
+            dense: None,
+        }
+    }
+}
+
+impl<'a> From<&'a DenseFeatures> for RegressorFeatures<'a> {
+    fn from(dense: &'a DenseFeatures) -> Self {
+        RegressorFeatures {
+            sparse: None,
+            dense: Some(dense),
+        }
+    }
+}
+
+impl<'a> From<&'a MixedFeatures> for RegressorFeatures<'a> {
+    fn from(mixed: &'a MixedFeatures) -> Self {
+        RegressorFeatures {
+            sparse: Some(&mixed.sparse),
+            dense: Some(&mixed.dense),
+        }
+    }
+}
+
+/// A namespace taking part in an interaction along with the position of the
+/// nearest earlier occurrence of the same namespace, if combinations are being
+/// produced and it is repeated.
+type InteractionTerm<'a> = (&'a SparseFeaturesNamespace, Option<usize>);
+
+/// Interactions of this length or shorter are iterated without allocating.
+const INLINE_INTERACTION_LENGTH: usize = 4;
+
+fn foreach_interacted_feature_impl<F>(
+    terms: &[InteractionTerm],
+    offsets: &mut [usize],
+    depth: usize,
+    hash_so_far: u32,
+    value_so_far: f32,
+    func: &mut F,
+) where
+    F: FnMut(f32, FeatureHash),
+{
+    let (namespace, repeat_of) = terms[depth];
+    let start = repeat_of.map_or(0, |position| offsets[position]);
+    for (offset, (index, value)) in namespace.iter().enumerate().skip(start) {
+        let hash = hash_so_far ^ u32::from(index);
+        if depth + 1 == terms.len() {
+            func(value_so_far * value, hash.into());
+        } else {
+            offsets[depth] = offset;
+            foreach_interacted_feature_impl(
+                terms,
+                offsets,
+                depth + 1,
+                (FNV_PRIME as u64).wrapping_mul(hash as u64) as u32,
+                value_so_far * value,
+                func,
+            );
+        }
+    }
+}
+
+/// Calls `func` with the value and unmasked hash of every feature produced by
+/// crossing the given namespaces. Hashing is the same as
+/// [`crate::inverse_hash_table::Feature::Interacted`].
+///
+/// In [`InteractionMode::Combinations`] a namespace which appears more than
+/// once only produces each combination of its features once, no matter where
+/// the repeats are in the interaction. In [`InteractionMode::Permutations`]
+/// the full cartesian product is produced.
+pub fn foreach_interacted_feature<F>(
+    features: &SparseFeatures,
+    interaction: &[Namespace],
+    mode: InteractionMode,
+    func: F,
+) where
+    F: FnMut(f32, FeatureHash),
+{
+    let repeat_of: SmallVec<[Option<usize>; INLINE_INTERACTION_LENGTH]> =
+        repeat_positions(interaction, mode).collect();
+    foreach_interacted_feature_with_repeats(features, interaction, &repeat_of, func);
+}
+
+/// Like [`foreach_interacted_feature`], with the [`repeat_positions`] of the
+/// interaction already known, as given by
+/// [`CompiledInteractions::foreach_interaction`].
+pub fn foreach_interacted_feature_with_repeats<F>(
+    features: &SparseFeatures,
+    interaction: &[Namespace],
+    repeat_of: &[Option<usize>],
+    mut func: F,
+) where
+    F: FnMut(f32, FeatureHash),
+{
+    let mut terms: SmallVec<[InteractionTerm; INLINE_INTERACTION_LENGTH]> = SmallVec::new();
+    for (ns, repeat_of) in interaction.iter().zip(repeat_of) {
+        match features.get_namespace(*ns) {
+            Some(namespace) => terms.push((namespace, *repeat_of)),
+            None => return,
+        }
+    }
+    if terms.is_empty() {
+        return;
+    }
+    let mut offsets: SmallVec<[usize; INLINE_INTERACTION_LENGTH]> = smallvec![0; terms.len()];
+    foreach_interacted_feature_impl(&terms, &mut offsets, 0, 0, 1.0, &mut func);
+}
+
 macro_rules! generate_foreach_feature_func {
     ($func_name: ident, $weight_type: ty, $inner_func_type: ty, $weight_at_func: ident) => {
         #[allow(clippy::too_many_arguments)]
         pub fn $func_name<F, W>(
             model_offset: ModelIndex,
-            features: &SparseFeatures,
+            features: RegressorFeatures,
             weights: $weight_type,
-            quadratic_interactions: &[(Namespace, Namespace)],
-            cubic_interactions: &[(Namespace, Namespace, Namespace)],
+            interactions: &CompiledInteractions,
             num_bits: u8,
             constant_feature_enabled: bool,
             mut func: F,
@@ -30,55 +176,36 @@ macro_rules! generate_foreach_feature_func {
             F: FnMut(f32, $inner_func_type),
             W: Weights,
         {
-            for (index, value) in features.all_features() {
-                let model_weight = weights.$weight_at_func(index, model_offset);
-                func(value, model_weight);
-            }
-
             let masker = FeatureMask::from_num_bits(num_bits);
-            // quadratics
-            for (ns1, ns2) in quadratic_interactions {
-                let same_ns = ns1 == ns2;
-                if let Some(ns1) = features.get_namespace(*ns1) {
-                    for (i, feat1) in ns1.iter().enumerate() {
-                        let multiplied =
-                            (FNV_PRIME as u64).wrapping_mul(u32::from(feat1.0) as u64) as u32;
-                        if let Some(ns2) = features.get_namespace(*ns2) {
-                            for feat2 in ns2.iter().skip(if same_ns { i } else { 0 }) {
-                                let idx =
-                                    FeatureHash::from(multiplied ^ u32::from(feat2.0)).mask(masker);
-                                let model_weight = weights.$weight_at_func(idx, model_offset);
-                                func(feat1.1 * feat2.1, model_weight);
-                            }
-                        }
-                    }
+            if let Some(sparse) = features.sparse {
+                for (index, value) in sparse.all_features() {
+                    let model_weight = weights.$weight_at_func(index, model_offset);
+                    func(value, model_weight);
                 }
+
+                interactions.foreach_interaction(sparse, |interaction, repeat_of| {
+                    foreach_interacted_feature_with_repeats(
+                        sparse,
+                        interaction,
+                        repeat_of,
+                        |value, hash| {
+                            let model_weight =
+                                weights.$weight_at_func(hash.mask(masker), model_offset);
+                            func(value, model_weight);
+                        },
+                    );
+                });
             }
 
-            // cubics
-            for (ns1, ns2, ns3) in cubic_interactions {
-                let same_ns = ns1 == ns2;
-                let same_ns2 = ns2 == ns3;
-                if let Some(ns1) = features.get_namespace(*ns1) {
-                    for (i, feat1) in ns1.iter().enumerate() {
-                        let halfhash1 =
-                            (FNV_PRIME as u64).wrapping_mul(u32::from(feat1.0) as u64) as u32;
-                        if let Some(ns2) = features.get_namespace(*ns2) {
-                            for feat2 in ns2.iter().skip(if same_ns { i } else { 0 }) {
-                                let halfhash2 = (FNV_PRIME as u64)
-                                    .wrapping_mul((halfhash1 ^ u32::from(feat2.0)) as u64)
-                                    as u32;
-                                if let Some(ns3) = features.get_namespace(*ns3) {
-                                    for feat3 in ns3.iter().skip(if same_ns2 { i } else { 0 }) {
-                                        let idx = FeatureHash::from(halfhash2 ^ u32::from(feat3.0))
-                                            .mask(masker);
-                                        let model_weight =
-                                            weights.$weight_at_func(idx, model_offset);
-                                        func(feat1.1 * feat2.1 * feat3.1, model_weight);
-                                    }
-                                }
-                            }
-                        }
+            // Dense values use consecutive weights starting at the namespace hash.
+            if let Some(dense) = features.dense {
+                for (namespace, values) in dense.namespaces() {
+                    let start = u32::from(namespace.hash());
+                    for (offset, value) in values.iter().enumerate() {
+                        let index =
+                            FeatureHash::from(start.wrapping_add(offset as u32)).mask(masker);
+                        let model_weight = weights.$weight_at_func(index, model_offset);
+                        func(*value, model_weight);
                     }
                 }
             }
@@ -100,3 +227,161 @@ generate_foreach_feature_func!(
     &mut [f32],
     state_at_mut
 );
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::HashFunction;
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::inverse_hash_table::Feature;
+
+    const NUM_BITS: u8 = 18;
+
+    fn simple(namespace: &str, name: &str) -> Feature {
+        Feature::Simple {
+            namespace: namespace.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    fn add_features(features: &mut SparseFeatures, namespace: &str, names: &[&str]) {
+        let masker = FeatureMask::from_num_bits(NUM_BITS);
+        let ns = features.get_or_create_namespace(Namespace::from_name(
+            namespace,
+            0,
+            HashFunction::default(),
+        ));
+        for name in names {
+            ns.add_feature(
+                simple(namespace, name)
+                    .hash(0, HashFunction::default())
+                    .mask(masker),
+                1.0,
+            );
+        }
+    }
+
+    fn interacted(
+        features: &SparseFeatures,
+        interaction: &[&str],
+        mode: InteractionMode,
+    ) -> Vec<FeatureIndex> {
+        let masker = FeatureMask::from_num_bits(NUM_BITS);
+        let namespaces: Vec<Namespace> = interaction
+            .iter()
+            .map(|ns| Namespace::from_name(ns, 0, HashFunction::default()))
+            .collect();
+        let mut produced = Vec::new();
+        foreach_interacted_feature(features, &namespaces, mode, |_, hash| {
+            produced.push(hash.mask(masker))
+        });
+        produced
+    }
+
+    fn expected(terms: &[Vec<Feature>]) -> Vec<FeatureIndex> {
+        let masker = FeatureMask::from_num_bits(NUM_BITS);
+        terms
+            .iter()
+            .map(|terms| {
+                Feature::Interacted {
+                    terms: terms.clone(),
+                }
+                .hash(0, HashFunction::default())
+                .mask(masker)
+            })
+            .collect()
+    }
+
+    fn test_features() -> SparseFeatures {
+        let mut features = SparseFeatures::new();
+        add_features(&mut features, "a", &["x", "y"]);
+        add_features(&mut features, "b", &["z"]);
+        features
+    }
+
+    #[test]
+    fn quadratic_same_namespace_matches_inverse_hash() {
+        let features = test_features();
+        let (x, y) = (simple("a", "x"), simple("a", "y"));
+
+        assert_eq!(
+            interacted(&features, &["a", "a"], InteractionMode::Combinations),
+            expected(&[
+                vec![x.clone(), x.clone()],
+                vec![x.clone(), y.clone()],
+                vec![y.clone(), y.clone()],
+            ])
+        );
+        assert_eq!(
+            interacted(&features, &["a", "a"], InteractionMode::Permutations),
+            expected(&[
+                vec![x.clone(), x.clone()],
+                vec![x.clone(), y.clone()],
+                vec![y.clone(), x],
+                vec![y.clone(), y],
+            ])
+        );
+    }
+
+    #[test]
+    fn cubic_repeated_namespaces_match_inverse_hash() {
+        let features = test_features();
+        let (x, y, z) = (simple("a", "x"), simple("a", "y"), simple("b", "z"));
+
+        // Repeat at the end, which must not be constrained by the first term.
+        assert_eq!(
+            interacted(&features, &["b", "a", "a"], InteractionMode::Combinations),
+            expected(&[
+                vec![z.clone(), x.clone(), x.clone()],
+                vec![z.clone(), x.clone(), y.clone()],
+                vec![z.clone(), y.clone(), y.clone()],
+            ])
+        );
+
+        // Repeat which is not adjacent.
+        assert_eq!(
+            interacted(&features, &["a", "b", "a"], InteractionMode::Combinations),
+            expected(&[
+                vec![x.clone(), z.clone(), x.clone()],
+                vec![x.clone(), z.clone(), y.clone()],
+                vec![y.clone(), z.clone(), y.clone()],
+            ])
+        );
+
+        let permutations: BTreeSet<_> =
+            interacted(&features, &["a", "b", "a"], InteractionMode::Permutations)
+                .into_iter()
+                .collect();
+        assert_eq!(
+            permutations,
+            expected(&[
+                vec![x.clone(), z.clone(), x.clone()],
+                vec![x.clone(), z.clone(), y.clone()],
+                vec![y.clone(), z.clone(), x],
+                vec![y.clone(), z, y],
+            ])
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn quartic_matches_inverse_hash() {
+        let features = test_features();
+        let (x, y, z) = (simple("a", "x"), simple("a", "y"), simple("b", "z"));
+
+        assert_eq!(
+            interacted(
+                &features,
+                &["a", "b", "b", "a"],
+                InteractionMode::Combinations
+            ),
+            expected(&[
+                vec![x.clone(), z.clone(), z.clone(), x.clone()],
+                vec![x.clone(), z.clone(), z.clone(), y.clone()],
+                vec![y.clone(), z.clone(), z, y],
+            ])
+        );
+    }
+}
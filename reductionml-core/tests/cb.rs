@@ -85,6 +85,7 @@ fn test_greedy_predict_json() {
         FeaturesType::SparseCBAdf,
         LabelType::CB,
         0,
+        global_config.hash_function(),
         global_config.num_bits(),
         pool,
     );
@@ -113,7 +114,7 @@ fn test_greedy_predict_json() {
             }
         ]
     });
-    let (mut features, label) = json_parser.parse_chunk(&input.to_string()).unwrap();
+    let (mut features, label, _) = json_parser.parse_chunk(&input.to_string()).unwrap();
 
     let mut depth_info = DepthInfo::new();
     let prediction = cb_explore_adf_greedy.predict_then_learn(
@@ -202,6 +203,7 @@ fn test_learning_e2e(
             FeaturesType::SparseCBAdf,
             LabelType::CB,
             0,
+            global_config.hash_function(),
             global_config.num_bits(),
             pool.clone(),
         );
@@ -210,7 +212,7 @@ fn test_learning_e2e(
             let ctx = context(i);
             let (chosen_action, p) = chosen(&ctx, i);
             let reward = r(&ctx, actions[usize::try_from(chosen_action).unwrap()], i);
-            let (mut features, label) = json_parser
+            let (mut features, label, _) = json_parser
                 .parse_chunk(&ex_learn(&ctx, action0, action1, chosen_action, p, reward))
                 .unwrap();
 
@@ -218,7 +220,7 @@ fn test_learning_e2e(
             learner.learn(&mut features, &label.unwrap(), &mut depth_info, 0.into());
         }
         for (ctx, expected) in test_set {
-            let (mut features, _) = json_parser
+            let (mut features, _, _) = json_parser
                 .parse_chunk(&ex_pred(ctx, action0, action1))
                 .unwrap();
             let prediction = learner.predict(&mut features, &mut DepthInfo::new(), 0.into());
@@ -442,3 +444,53 @@ fn test_cb_nonstationary_deterministic_actions_with_personalization() {
         &[("Tom".to_owned(), 1), ("Anna".to_owned(), 0)],
     );
 }
+
+#[test]
+fn test_cb_personalization_with_shared_action_interactions() {
+    fn context(i: i32) -> String {
+        if i % 4 < 2 {
+            "Tom".to_owned()
+        } else {
+            "Anna".to_owned()
+        }
+    }
+    fn chosen(_context: &str, i: i32) -> (i32, f32) {
+        (i % 2, 0.5)
+    }
+    fn r(context: &str, action: &str, _i: i32) -> f32 {
+        // Tom likes politics and Anna likes sports.
+        if (context == "Tom") == (action == "Politics") {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    // The shared user is only crossed with the action's content by the CbAdf
+    // reduction, there are no global interactions.
+    let global_config = GlobalConfig::new(5, 0, true, &Vec::new());
+    let config: CBExploreAdfGreedyConfig = serde_json::from_value(json!({
+        "cbAdf": {
+            "typename": "CbAdf",
+            "config": {
+                "sharedActionInteractions": [[{"Name": "user"}, {"Name": "content"}]]
+            }
+        }
+    }))
+    .unwrap();
+    let mut learners = [CBExploreAdfGreedyReductionFactory
+        .create(&config, &global_config, 1.into())
+        .unwrap()];
+
+    test_learning_e2e(
+        context,
+        "Politics",
+        "Sports",
+        chosen,
+        r,
+        1000,
+        &mut learners,
+        &global_config,
+        &[("Tom".to_owned(), 0), ("Anna".to_owned(), 1)],
+    );
+}
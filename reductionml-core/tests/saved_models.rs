@@ -0,0 +1,56 @@
+//! Models in `tests/data/models` were saved by earlier versions. They must
+//! keep loading and make the same predictions as the version that saved them.
+
+use std::{path::Path, sync::Arc};
+
+use approx::assert_relative_eq;
+use reductionml_core::{
+    object_pool::Pool,
+    parsers::{TextModeParser, TextModeParserFactory, VwTextParserFactory},
+    utils::AsInner,
+    workspace::Workspace,
+    FeaturesType, LabelType, ScalarPrediction,
+};
+
+fn predict_saved_model(name: &str) -> Vec<f32> {
+    let data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/models");
+    let model = std::fs::read(data_dir.join(name)).unwrap();
+    let workspace = Workspace::create_from_model(&model).unwrap();
+    // Saving again uses the current format, which must round trip.
+    let workspace = Workspace::create_from_model(&workspace.serialize_model().unwrap()).unwrap();
+    let global_config = workspace.global_config();
+    let parser = VwTextParserFactory.create(
+        FeaturesType::SparseSimple,
+        LabelType::Simple,
+        global_config.hash_seed(),
+        global_config.hash_function(),
+        global_config.num_bits(),
+        Arc::new(Pool::new()),
+    );
+
+    let data = std::fs::read_to_string(data_dir.join("train.txt")).unwrap();
+    data.lines()
+        .map(|line| {
+            let (mut features, _, _) = parser.parse_chunk(line).unwrap();
+            let prediction = workspace.predict(&mut features);
+            let prediction: &ScalarPrediction = prediction.as_inner().unwrap();
+            prediction.prediction
+        })
+        .collect()
+}
+
+#[test]
+fn coin_with_pairs_and_triples() {
+    let predictions = predict_saved_model("coin.model");
+    for (actual, expected) in predictions.iter().zip([0.86743736, 0.2608879, 0.45792484]) {
+        assert_relative_eq!(*actual, expected);
+    }
+}
+
+#[test]
+fn elementwise_interaction_with_pair() {
+    let predictions = predict_saved_model("elementwise_interaction.model");
+    for (actual, expected) in predictions.iter().zip([0.65, 0.15000002, 0.65]) {
+        assert_relative_eq!(*actual, expected);
+    }
+}
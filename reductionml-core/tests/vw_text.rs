@@ -0,0 +1,97 @@
+//! Golden file tests for the VW text parser. Each input in `tests/data/vw_text`
+//! is parsed and compared against the `.golden.json` file next to it. Run with
+//! `UPDATE_GOLDEN=1` to regenerate the expected output after an intended
+//! change, and review the difference.
+
+use std::{io::BufReader, path::Path, sync::Arc};
+
+use reductionml_core::{
+    hash::HashFunction,
+    object_pool::Pool,
+    parsers::{TextModeParser, TextModeParserFactory, VwTextParserFactory},
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    utils::AsInner,
+    CBAdfFeatures, Features, FeaturesType, LabelType,
+};
+use serde_json::{json, Map, Value};
+
+fn sparse_to_json(features: &SparseFeatures) -> Value {
+    let mut namespaces = Map::new();
+    for (namespace, _) in features.namespaces() {
+        // Skip namespaces left over from pooled objects.
+        let Some(contents) = features.get_namespace(namespace) else {
+            continue;
+        };
+        let name = match namespace {
+            Namespace::Default => "default".to_owned(),
+            Namespace::Named(hash) | Namespace::RawHash(hash) => u32::from(hash).to_string(),
+        };
+        let values: Vec<Value> = contents
+            .iter()
+            .map(|(index, value)| json!([u32::from(index), value]))
+            .collect();
+        namespaces.insert(name, values.into());
+    }
+    namespaces.into()
+}
+
+fn features_to_json(features: &Features) -> Value {
+    match features {
+        Features::SparseSimple(features) => sparse_to_json(features),
+        Features::SparseCBAdf(_) => {
+            let features: &CBAdfFeatures = features.as_inner().unwrap();
+            json!({
+                "shared": features.shared.as_ref().map(sparse_to_json),
+                "actions": features.actions.iter().map(sparse_to_json).collect::<Vec<_>>(),
+            })
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn check_golden(name: &str, features_type: FeaturesType, label_type: LabelType) {
+    let data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/vw_text");
+    let parser = VwTextParserFactory.create(
+        features_type,
+        label_type,
+        0,
+        HashFunction::Murmur3,
+        18,
+        Arc::new(Pool::new()),
+    );
+
+    let file = std::fs::File::open(data_dir.join(format!("{}.txt", name))).unwrap();
+    let mut input = BufReader::new(file);
+    let mut examples = Vec::new();
+    let mut buffer = String::new();
+    while let Some(chunk) = parser.get_next_chunk(&mut input, buffer).unwrap() {
+        let (features, label, metadata) = parser
+            .parse_chunk(&chunk)
+            .unwrap_or_else(|err| panic!("Failed to parse {:?}: {}", chunk, err));
+        examples.push(json!({
+            "input": chunk,
+            "label": label,
+            "metadata": metadata,
+            "features": features_to_json(&features),
+        }));
+        buffer = chunk;
+    }
+    let actual = serde_json::to_string_pretty(&examples).unwrap() + "\n";
+
+    let golden_path = data_dir.join(format!("{}.golden.json", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden_path, &actual).unwrap();
+    }
+    let expected = std::fs::read_to_string(&golden_path).unwrap();
+    assert_eq!(actual, expected, "{} differs from the golden file", name);
+}
+
+#[test]
+fn vw_text_simple_golden() {
+    check_golden("simple", FeaturesType::SparseSimple, LabelType::Simple);
+}
+
+#[test]
+fn vw_text_cb_golden() {
+    check_golden("cb", FeaturesType::SparseCBAdf, LabelType::CB);
+}
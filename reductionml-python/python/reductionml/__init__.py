@@ -10,8 +10,11 @@ ActionProbsPred = _reductionml.ActionProbsPred
 ActionScoresPred = _reductionml.ActionScoresPred
 CbAdfFeatures = _reductionml.CbAdfFeatures
 CbLabel = _reductionml.CbLabel
+DenseFeatures = _reductionml.DenseFeatures
 FormatType = _reductionml.FormatType
+HashFunction = _reductionml.HashFunction
 JsonParser = _reductionml.JsonParser
+MixedFeatures = _reductionml.MixedFeatures
 ScalarPred = _reductionml.ScalarPred
 SparseFeatures = _reductionml.SparseFeatures
 TextParser = _reductionml.TextParser
@@ -28,16 +31,20 @@ __all__ = [
     "CbAdfFeatures",
     "CbLabel",
     "Config",
+    "DenseFeatures",
     "EntryReductionConfig",
     "Features",
     "FeaturesType",
     "FormatType",
     "GlobalConfig",
+    "HashFunction",
     "Interaction",
     "JsonParser",
     "Label",
     "LabelType",
+    "MixedFeatures",
     "NameInteraction",
+    "PrefixInteraction",
     "Prediction",
     "PredictionType",
     "ReductionTypesDescription",
@@ -53,7 +60,13 @@ class NameInteraction(TypedDict):
     name: str
 
 
-Interaction = Union[Literal["Default"], NameInteraction]
+class PrefixInteraction(TypedDict):
+    prefix: str
+
+
+Interaction = Union[
+    Literal["Default"], Literal["Wildcard"], NameInteraction, PrefixInteraction
+]
 
 
 class GlobalConfig(TypedDict):
@@ -61,8 +74,10 @@ class GlobalConfig(TypedDict):
 
     numBits: NotRequired[int]
     hashSeed: NotRequired[int]
+    hashFunction: NotRequired[Literal["Xxh3", "Murmur3"]]
     constantFeatureEnabled: NotRequired[bool]
     interactions: NotRequired[List[Interaction]]
+    interactionMode: NotRequired[Literal["Combinations", "Permutations"]]
 
 
 class EntryReductionConfig(TypedDict):
@@ -79,7 +94,7 @@ class Config(TypedDict):
     entryReduction: EntryReductionConfig
 
 
-Features = typing.Union[SparseFeatures, CbAdfFeatures]
+Features = typing.Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures]
 Label = typing.Union[SimpleLabel, CbLabel]
 Prediction = typing.Union[ScalarPred, ActionScoresPred, ActionProbsPred]
 
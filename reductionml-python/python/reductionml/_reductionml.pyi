@@ -47,6 +47,22 @@ class SparseFeatures:
 class CbAdfFeatures:
     def __init__(self, /, *args, **kwargs) -> None: ...
 
+@final
+class DenseFeatures:
+    def __init__(self, values: Optional[List[float]] = None) -> None: ...
+    def add_namespace(
+        self,
+        namespace: str,
+        values: List[float],
+        hash_seed: int = 0,
+        hash_function: HashFunction = HashFunction.Xxh3,
+    ) -> None: ...
+    def __len__(self) -> int: ...
+
+@final
+class MixedFeatures:
+    def __init__(self, /, *args, **kwargs) -> None: ...
+
 # TODO: are integers correct here?
 @final
 class FormatType(Enum):
@@ -54,6 +70,11 @@ class FormatType(Enum):
     Json = 2
     DsJson = 3
 
+@final
+class HashFunction(Enum):
+    Xxh3 = 1
+    Murmur3 = 2
+
 @overload
 def create_parser(
     format_type: Literal[FormatType.VwText],
@@ -61,6 +82,7 @@ def create_parser(
     label_type: LabelType,
     hash_seed: int,
     num_bits: int,
+    hash_function: HashFunction = HashFunction.Xxh3,
 ) -> TextParser: ...
 @overload
 def create_parser(
@@ -69,13 +91,22 @@ def create_parser(
     label_type: LabelType,
     hash_seed: int,
     num_bits: int,
+    hash_function: HashFunction = HashFunction.Xxh3,
 ) -> JsonParser: ...
 @final
 class TextParser:
     def parse(
         self, input: str
     ) -> Tuple[
-        Union[SparseFeatures, CbAdfFeatures], Optional[Union[SimpleLabel, CbLabel]]
+        Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures], Optional[Union[SimpleLabel, CbLabel]]
+    ]: ...
+    def parse_file(
+        self, path: str
+    ) -> List[
+        Tuple[
+            Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures],
+            Optional[Union[SimpleLabel, CbLabel]],
+        ]
     ]: ...
 
 @final
@@ -83,7 +114,15 @@ class JsonParser:
     def parse(
         self, input: Union[str, Dict[str, Any]]
     ) -> Tuple[
-        Union[SparseFeatures, CbAdfFeatures], Optional[Union[SimpleLabel, CbLabel]]
+        Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures], Optional[Union[SimpleLabel, CbLabel]]
+    ]: ...
+    def parse_file(
+        self, path: str
+    ) -> List[
+        Tuple[
+            Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures],
+            Optional[Union[SimpleLabel, CbLabel]],
+        ]
     ]: ...
 
 # TODO: are integers correct here?
@@ -91,6 +130,8 @@ class JsonParser:
 class FeaturesType(Enum):
     SparseSimple = (1,)
     SparseCbAdf = (2,)
+    DenseSimple = (3,)
+    MixedSimple = (4,)
 
 # TODO: are integers correct here?
 @final
@@ -149,16 +190,16 @@ class Workspace:
         format_type: FormatType,
     ) -> Union[TextParser, JsonParser]: ...
     def predict(
-        self, features: Union[SparseFeatures, CbAdfFeatures]
+        self, features: Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures]
     ) -> Union[ScalarPred, ActionScoresPred, ActionProbsPred]: ...
     def learn(
         self,
-        features: Union[SparseFeatures, CbAdfFeatures],
+        features: Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures],
         label: Union[SimpleLabel, CbLabel],
     ) -> None: ...
     def predict_then_learn(
         self,
-        features: Union[SparseFeatures, CbAdfFeatures],
+        features: Union[SparseFeatures, CbAdfFeatures, DenseFeatures, MixedFeatures],
         label: Union[SimpleLabel, CbLabel],
     ) -> Union[ScalarPred, ActionScoresPred, ActionProbsPred]: ...
 
@@ -1,10 +1,13 @@
 use pyo3::prelude::*;
 use reductionml_core::{
-    error::Error, object_pool::PoolReturnable, sparse_namespaced_features::SparseFeatures,
-    CBAdfFeatures, Features,
+    dense_features::DenseFeatures,
+    error::Error,
+    object_pool::PoolReturnable,
+    sparse_namespaced_features::{Namespace, SparseFeatures},
+    CBAdfFeatures, Features, MixedFeatures,
 };
 
-use crate::SPARSE_FEATURES_POOL;
+use crate::{parsers::WrappedHashFunction, SPARSE_FEATURES_POOL};
 
 #[pyclass]
 #[pyo3(name = "SparseFeatures")]
@@ -16,6 +19,16 @@ pub(crate) struct WrappedSparseFeatures(
 #[pyo3(name = "CbAdfFeatures")]
 pub(crate) struct WrappedCbAdfFeatures(Option<reductionml_core::CBAdfFeatures>);
 
+#[pyclass]
+#[pyo3(name = "DenseFeatures")]
+/// A contiguous block of values, such as an embedding. Values passed to the
+/// constructor are placed in the default namespace.
+pub(crate) struct WrappedDenseFeatures(DenseFeatures);
+
+#[pyclass]
+#[pyo3(name = "MixedFeatures")]
+pub(crate) struct WrappedMixedFeatures(Option<MixedFeatures>);
+
 #[pymethods]
 impl WrappedSparseFeatures {
     #[new]
@@ -48,10 +61,64 @@ impl Drop for WrappedCbAdfFeatures {
     }
 }
 
+#[pymethods]
+impl WrappedDenseFeatures {
+    #[new]
+    #[pyo3(signature = (values = None))]
+    pub(crate) fn new(values: Option<Vec<f32>>) -> Self {
+        match values {
+            Some(values) => Self(DenseFeatures::from_values(values)),
+            None => Self(DenseFeatures::new()),
+        }
+    }
+
+    /// Appends the values of a namespace. The hash seed and function must
+    /// match those of the workspace.
+    #[pyo3(signature = (namespace, values, hash_seed = 0, hash_function = WrappedHashFunction::Xxh3))]
+    pub(crate) fn add_namespace(
+        &mut self,
+        namespace: &str,
+        values: Vec<f32>,
+        hash_seed: u32,
+        hash_function: WrappedHashFunction,
+    ) {
+        self.0.add_namespace(
+            Namespace::from_name(namespace, hash_seed, hash_function.into()),
+            &values,
+        );
+    }
+
+    pub(crate) fn __len__(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[pymethods]
+impl WrappedMixedFeatures {
+    #[new]
+    pub(crate) fn new() -> Self {
+        Self(Some(MixedFeatures {
+            dense: DenseFeatures::new(),
+            sparse: SPARSE_FEATURES_POOL.as_ref().get_object(),
+        }))
+    }
+}
+
+impl Drop for WrappedMixedFeatures {
+    fn drop(&mut self) {
+        if let Some(features) = self.0.take() {
+            features.clear_and_return_object(SPARSE_FEATURES_POOL.as_ref());
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(FromPyObject)]
 pub(crate) enum WrappedFeatures<'a> {
     SparseSimpleRef(PyRefMut<'a, WrappedSparseFeatures>),
     CbAdfFeaturesRef(PyRefMut<'a, WrappedCbAdfFeatures>),
+    DenseFeaturesRef(PyRefMut<'a, WrappedDenseFeatures>),
+    MixedFeaturesRef(PyRefMut<'a, WrappedMixedFeatures>),
 }
 
 impl WrappedFeatures<'_> {
@@ -64,6 +131,10 @@ impl WrappedFeatures<'_> {
             WrappedFeatures::CbAdfFeaturesRef(ref mut r) => {
                 Features::SparseCBAdfRef(r.0.as_mut().unwrap())
             }
+            WrappedFeatures::DenseFeaturesRef(ref mut r) => Features::DenseSimpleRef(&mut r.0),
+            WrappedFeatures::MixedFeaturesRef(ref mut r) => {
+                Features::MixedSimpleRef(r.0.as_mut().unwrap())
+            }
         }
     }
 }
@@ -71,6 +142,8 @@ impl WrappedFeatures<'_> {
 pub(crate) enum WrappedFeaturesForReturn {
     SparseSimple(SparseFeatures),
     CbAdfFeatures(CBAdfFeatures),
+    DenseFeatures(DenseFeatures),
+    MixedFeatures(MixedFeatures),
 }
 
 impl TryInto<WrappedFeaturesForReturn> for Features<'_> {
@@ -79,9 +152,16 @@ impl TryInto<WrappedFeaturesForReturn> for Features<'_> {
     fn try_into(self) -> Result<WrappedFeaturesForReturn, Self::Error> {
         match self {
             Features::SparseSimple(feats) => Ok(WrappedFeaturesForReturn::SparseSimple(feats)),
-            Features::SparseSimpleRef(_) => todo!(),
             Features::SparseCBAdf(feats) => Ok(WrappedFeaturesForReturn::CbAdfFeatures(feats)),
-            Features::SparseCBAdfRef(_) => todo!(),
+            Features::DenseSimple(feats) => Ok(WrappedFeaturesForReturn::DenseFeatures(feats)),
+            Features::MixedSimple(feats) => Ok(WrappedFeaturesForReturn::MixedFeatures(feats)),
+            // Parsers always produce owned features.
+            Features::SparseSimpleRef(_)
+            | Features::SparseCBAdfRef(_)
+            | Features::DenseSimpleRef(_)
+            | Features::MixedSimpleRef(_) => Err(Error::InvalidArgument(
+                "Borrowed features cannot be returned to Python".to_owned(),
+            )),
         }
     }
 }
@@ -95,6 +175,12 @@ impl IntoPy<PyObject> for WrappedFeaturesForReturn {
             WrappedFeaturesForReturn::CbAdfFeatures(feats) => {
                 WrappedCbAdfFeatures(Some(feats)).into_py(py)
             }
+            WrappedFeaturesForReturn::DenseFeatures(feats) => {
+                WrappedDenseFeatures(feats).into_py(py)
+            }
+            WrappedFeaturesForReturn::MixedFeatures(feats) => {
+                WrappedMixedFeatures(Some(feats)).into_py(py)
+            }
         }
     }
 }
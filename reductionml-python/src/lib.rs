@@ -36,9 +36,13 @@ impl From<WrappedError> for PyErr {
 ///
 /// - SparseSimple - Corresponds with :py:class:`reductionml.SparseFeatures`
 /// - SparseCbAdf - Corresponds with :py:class:`reductionml.CbAdfFeatures`
+/// - DenseSimple - Corresponds with :py:class:`reductionml.DenseFeatures`
+/// - MixedSimple - Corresponds with :py:class:`reductionml.MixedFeatures`
 pub(crate) enum WrappedFeaturesType {
     SparseSimple,
     SparseCbAdf,
+    DenseSimple,
+    MixedSimple,
 }
 
 impl From<FeaturesType> for WrappedFeaturesType {
@@ -46,6 +50,8 @@ impl From<FeaturesType> for WrappedFeaturesType {
         match x {
             FeaturesType::SparseSimple => WrappedFeaturesType::SparseSimple,
             FeaturesType::SparseCBAdf => WrappedFeaturesType::SparseCbAdf,
+            FeaturesType::DenseSimple => WrappedFeaturesType::DenseSimple,
+            FeaturesType::MixedSimple => WrappedFeaturesType::MixedSimple,
         }
     }
 }
@@ -55,6 +61,8 @@ impl From<WrappedFeaturesType> for FeaturesType {
         match x {
             WrappedFeaturesType::SparseSimple => FeaturesType::SparseSimple,
             WrappedFeaturesType::SparseCbAdf => FeaturesType::SparseCBAdf,
+            WrappedFeaturesType::DenseSimple => FeaturesType::DenseSimple,
+            WrappedFeaturesType::MixedSimple => FeaturesType::MixedSimple,
         }
     }
 }
@@ -75,6 +83,8 @@ fn _reductionml(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<WrappedFeaturesType>()?;
     m.add_class::<features::WrappedSparseFeatures>()?;
     m.add_class::<features::WrappedCbAdfFeatures>()?;
+    m.add_class::<features::WrappedDenseFeatures>()?;
+    m.add_class::<features::WrappedMixedFeatures>()?;
 
     // Labels
     m.add_class::<labels::WrappedSimpleLabel>()?;
@@ -89,6 +99,7 @@ fn _reductionml(_py: Python, m: &PyModule) -> PyResult<()> {
 
     // Parsers
     m.add_class::<parsers::FormatType>()?;
+    m.add_class::<parsers::WrappedHashFunction>()?;
     m.add_class::<parsers::WrappedParserTextOnly>()?;
     m.add_class::<parsers::WrappedParserTextAndJson>()?;
     m.add_function(wrap_pyfunction!(parsers::create_parser, m)?)?;
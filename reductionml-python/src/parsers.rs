@@ -3,6 +3,8 @@ use std::{borrow::Cow, sync::Arc};
 use pyo3::{prelude::*, types::PyDict};
 use pythonize::depythonize;
 use reductionml_core::{
+    compression::open_data_file,
+    hash::HashFunction,
     parsers::{TextModeParser, TextModeParserFactory},
     FeaturesType, LabelType,
 };
@@ -48,12 +50,39 @@ pub(crate) enum FormatType {
     DsJson,
 }
 
+#[pyclass]
+#[pyo3(name = "HashFunction")]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum WrappedHashFunction {
+    Xxh3,
+    Murmur3,
+}
+
+impl From<HashFunction> for WrappedHashFunction {
+    fn from(x: HashFunction) -> Self {
+        match x {
+            HashFunction::Xxh3 => WrappedHashFunction::Xxh3,
+            HashFunction::Murmur3 => WrappedHashFunction::Murmur3,
+        }
+    }
+}
+
+impl From<WrappedHashFunction> for HashFunction {
+    fn from(x: WrappedHashFunction) -> Self {
+        match x {
+            WrappedHashFunction::Xxh3 => HashFunction::Xxh3,
+            WrappedHashFunction::Murmur3 => HashFunction::Murmur3,
+        }
+    }
+}
+
 impl FormatType {
     pub(crate) fn get_parser(
         &self,
         features_type: FeaturesType,
         label_type: LabelType,
         hash_seed: u32,
+        hash_function: HashFunction,
         num_bits: u8,
     ) -> Box<dyn TextModeParser> {
         match self {
@@ -62,6 +91,7 @@ impl FormatType {
                     features_type,
                     label_type,
                     hash_seed,
+                    hash_function,
                     num_bits,
                     SPARSE_FEATURES_POOL.clone(),
                 ),
@@ -71,6 +101,7 @@ impl FormatType {
                     features_type,
                     label_type,
                     hash_seed,
+                    hash_function,
                     num_bits,
                     SPARSE_FEATURES_POOL.clone(),
                 ),
@@ -80,6 +111,7 @@ impl FormatType {
                     features_type,
                     label_type,
                     hash_seed,
+                    hash_function,
                     num_bits,
                     SPARSE_FEATURES_POOL.clone(),
                 ),
@@ -89,15 +121,22 @@ impl FormatType {
 }
 
 #[pyfunction]
+#[pyo3(signature = (format_type, features_type, label_type, hash_seed, num_bits, hash_function = WrappedHashFunction::Xxh3))]
 pub(crate) fn create_parser(
     format_type: FormatType,
     features_type: WrappedFeaturesType,
     label_type: WrappedLabelType,
     hash_seed: u32,
     num_bits: u8,
+    hash_function: WrappedHashFunction,
 ) -> Result<WrappedParser, PyErr> {
-    let parser =
-        format_type.get_parser(features_type.into(), label_type.into(), hash_seed, num_bits);
+    let parser = format_type.get_parser(
+        features_type.into(),
+        label_type.into(),
+        hash_seed,
+        hash_function.into(),
+        num_bits,
+    );
     match format_type {
         FormatType::VwText => Ok(WrappedParser::WrappedParserTextOnly(WrappedParserTextOnly(
             parser.into(),
@@ -111,6 +150,28 @@ pub(crate) fn create_parser(
     }
 }
 
+/// Parses every example of a file, decompressing it first if it is gzip or
+/// zstd compressed.
+fn parse_file(
+    parser: &dyn TextModeParser,
+    path: &str,
+) -> Result<Vec<(WrappedFeaturesForReturn, Option<WrappedLabel>)>, PyErr> {
+    let mut input = open_data_file(path)
+        .map_err(|x| WrappedError::from(reductionml_core::error::Error::from(x)))?;
+    let mut examples = Vec::new();
+    let mut buffer = String::new();
+    while let Some(chunk) = parser
+        .get_next_chunk(&mut input, buffer)
+        .map_err(WrappedError::from)?
+    {
+        let (feats, label, _) = parser.parse_chunk(&chunk).map_err(WrappedError::from)?;
+        let feats: WrappedFeaturesForReturn = feats.try_into().map_err(WrappedError::from)?;
+        examples.push((feats, label.map(|x| x.into())));
+        buffer = chunk;
+    }
+    Ok(examples)
+}
+
 #[pymethods]
 impl WrappedParserTextOnly {
     /// parse(input: str) -> typing.Tuple[typing.Union[SparseFeatures, CbAdfFeatures], typing.Optional[Union[SimpleLabel, CbLabel]]]
@@ -118,7 +179,7 @@ impl WrappedParserTextOnly {
         &self,
         input: &str,
     ) -> Result<(WrappedFeaturesForReturn, Option<WrappedLabel>), PyErr> {
-        let (feats, label) = self
+        let (feats, label, _) = self
             .0
             .parse_chunk(input)
             .map_err(|x| WrappedError::from(x))?;
@@ -127,6 +188,15 @@ impl WrappedParserTextOnly {
         let label: Option<WrappedLabel> = label.map(|x| x.into());
         Ok((feats, label))
     }
+
+    /// parse_file(path: str) -> typing.List[typing.Tuple[typing.Union[SparseFeatures, CbAdfFeatures], typing.Optional[Union[SimpleLabel, CbLabel]]]]
+    #[pyo3(name = "parse_file")]
+    fn parse_file_py(
+        &self,
+        path: &str,
+    ) -> Result<Vec<(WrappedFeaturesForReturn, Option<WrappedLabel>)>, PyErr> {
+        parse_file(self.0.as_ref(), path)
+    }
 }
 
 #[derive(FromPyObject)]
@@ -151,7 +221,7 @@ impl WrappedParserTextAndJson {
             }
         };
 
-        let (feats, label) = self
+        let (feats, label, _) = self
             .0
             .parse_chunk(&input)
             .map_err(|x| WrappedError::from(x))?;
@@ -160,4 +230,13 @@ impl WrappedParserTextAndJson {
         let label: Option<WrappedLabel> = label.map(|x| x.into());
         Ok((feats, label))
     }
+
+    /// parse_file(path: str) -> typing.List[typing.Tuple[typing.Union[SparseFeatures, CbAdfFeatures], typing.Optional[Union[SimpleLabel, CbLabel]]]]
+    #[pyo3(name = "parse_file")]
+    fn parse_file_py(
+        &self,
+        path: &str,
+    ) -> Result<Vec<(WrappedFeaturesForReturn, Option<WrappedLabel>)>, PyErr> {
+        parse_file(self.0.as_ref(), path)
+    }
 }
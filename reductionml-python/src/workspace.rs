@@ -162,6 +162,7 @@ impl WrappedWorkspace {
         let features_type = self.0.get_entry_reduction().types().input_features_type();
         let label_type = self.0.get_entry_reduction().types().input_label_type();
         let hash_seed = self.0.global_config().hash_seed();
+        let hash_function = self.0.global_config().hash_function();
         let num_bits = self.0.global_config().num_bits();
 
         create_parser(
@@ -170,6 +171,7 @@ impl WrappedWorkspace {
             label_type.into(),
             hash_seed,
             num_bits,
+            hash_function.into(),
         )
     }
 
@@ -49,3 +49,35 @@ def test_package_json():
     assert ws.entry_reduction_types.output_prediction_type == reml.PredictionType.Scalar
     assert ws.entry_reduction_types.output_label_type == None
     assert ws.entry_reduction_types.input_prediction_type == None
+
+
+def test_dense_features():
+    config = {
+        "entryReduction": {"config": {"featuresType": "DenseSimple"}, "typename": "Coin"},
+        "globalConfig": {},
+    }
+    w = reml.Workspace.create_from_config(config)
+    assert w.entry_reduction_types.input_features_type == reml.FeaturesType.DenseSimple
+    features = reml.DenseFeatures([0.5, 1.0])
+    features.add_namespace("embedding", [1.0, 2.0, 3.0])
+    assert len(features) == 5
+    w.learn(features, reml.SimpleLabel(1.0, 1.0))
+    pred = w.predict(features)
+    assert isinstance(pred, reml.ScalarPred)
+
+
+def test_parse_compressed_file(tmp_path):
+    import gzip
+
+    path = tmp_path / "data.txt.gz"
+    with gzip.open(path, "wt") as f:
+        f.write("1 |f a b\n0 |f c\n")
+
+    config = {"entryReduction": {"config": {}, "typename": "Coin"}, "globalConfig": {}}
+    w = reml.Workspace.create_from_config(config)
+    text_parser = w.create_parser(reml.FormatType.VwText)
+    examples = text_parser.parse_file(str(path))
+    assert len(examples) == 2
+    for features, label in examples:
+        assert isinstance(features, reml.SparseFeatures)
+        assert isinstance(label, reml.SimpleLabel)